@@ -1,6 +1,59 @@
+use crate::rng::Rng;
+use crate::{Error, Nonce};
+use std::collections::BTreeMap;
+
+/// Most metadata entries an account can hold.
+pub const MAX_METADATA_ENTRIES: usize = 16;
+/// Longest metadata key, in bytes.
+pub const MAX_METADATA_KEY_LENGTH: usize = 32;
+/// Longest metadata value, in bytes.
+pub const MAX_METADATA_VALUE_LENGTH: usize = 256;
+
 /// Amount is a number of tokens.
 pub type Amount = u64;
 
+/// The public key owning an account.
+pub type PublicKey = Vec<u8>;
+
+/// The ed25519 secret key of an account, signing its transactions.
+pub type Keypair = ed25519_dalek::SigningKey;
+
+/// A new keypair, whose secret is drawn from `rng`.
+pub fn generate_keypair<R: Rng>(rng: &R) -> Keypair {
+    let mut secret = [0; ed25519_dalek::SECRET_KEY_LENGTH];
+    rng.fill(&mut secret);
+    Keypair::from_bytes(&secret)
+}
+
+/// What kind of entity an account belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountType {
+    /// Owned by a user, through their key.
+    #[default]
+    User,
+
+    /// Holds the code and storage of a contract.
+    Contract,
+
+    /// Controlled by the protocol itself, e.g. the treasury or the staking pool.
+    System,
+}
+
+impl AccountType {
+    /// Can the account send transactions? Only users sign transactions:
+    /// contract and system accounts only move tokens as the protocol says.
+    /// Every kind of account can receive tokens.
+    pub fn may_send(self) -> bool {
+        self == AccountType::User
+    }
+
+    /// Can the account act as the system authority, e.g. to freeze accounts?
+    pub fn is_authority(self) -> bool {
+        self == AccountType::System
+    }
+}
+
 /**
 An account can hold tokens.
 
@@ -14,12 +67,67 @@ let account = Account::new();
 pub struct Account {
     /// Number of tokens held.
     pub tokens: Amount,
+
+    /// Key owning the account, signing its transactions. An account without
+    /// a key cannot send transactions.
+    pub public_key: Option<PublicKey>,
+
+    /// What kind of entity the account belongs to.
+    pub account_type: AccountType,
+
+    /// Small key-values for wallets and explorers to display, e.g.
+    /// `display_name` or `url`. Sorted, to be hashed in a stable order.
+    pub metadata: BTreeMap<String, String>,
+
+    /// A frozen account cannot send tokens, until it is unfrozen.
+    pub frozen: bool,
+
+    /// Nonce the next transaction sent from the account must use, so that
+    /// no transaction can be included twice. The first one uses 1.
+    pub nonce: Nonce,
 }
 
 impl Account {
     /// Constructor
     pub fn new() -> Self {
-        Self { tokens: 0 }
+        Self {
+            tokens: 0,
+            public_key: None,
+            account_type: AccountType::User,
+            metadata: BTreeMap::new(),
+            frozen: false,
+            nonce: 1,
+        }
+    }
+
+    /// Set a metadata entry, or remove it without a value, within the
+    /// limits on metadata size.
+    pub fn set_metadata(&mut self, key: &str, value: Option<&str>) -> Result<(), Error> {
+        if key.is_empty() || key.len() > MAX_METADATA_KEY_LENGTH {
+            return Err(format!(
+                "metadata key must be 1 to {} bytes long",
+                MAX_METADATA_KEY_LENGTH
+            ));
+        }
+
+        match value {
+            None => {
+                self.metadata.remove(key);
+            }
+            Some(value) => {
+                if value.len() > MAX_METADATA_VALUE_LENGTH {
+                    return Err(format!(
+                        "metadata value must be at most {} bytes long",
+                        MAX_METADATA_VALUE_LENGTH
+                    ));
+                }
+                if !self.metadata.contains_key(key) && self.metadata.len() >= MAX_METADATA_ENTRIES {
+                    return Err("too many metadata entries".to_string());
+                }
+                self.metadata.insert(key.to_string(), value.to_string());
+            }
+        }
+        Ok(())
     }
 }
 
@@ -28,3 +136,41 @@ impl Default for Account {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod account_tests {
+    use super::*;
+
+    #[test]
+    fn test_set_metadata() {
+        let mut account = Account::new();
+        assert_eq!(Ok(()), account.set_metadata("display_name", Some("Alice")));
+        assert_eq!(
+            Some("Alice"),
+            account.metadata.get("display_name").map(String::as_str)
+        );
+
+        assert_eq!(Ok(()), account.set_metadata("display_name", None));
+        assert!(account.metadata.is_empty());
+
+        assert!(account.set_metadata("", Some("empty key")).is_err());
+        assert!(account
+            .set_metadata("url", Some(&"a".repeat(MAX_METADATA_VALUE_LENGTH + 1)))
+            .is_err());
+    }
+
+    #[test]
+    fn test_metadata_entries_limit() {
+        let mut account = Account::new();
+        for i in 0..MAX_METADATA_ENTRIES {
+            account.set_metadata(&i.to_string(), Some("value")).unwrap();
+        }
+
+        assert_eq!(
+            Err("too many metadata entries".to_string()),
+            account.set_metadata("one more", Some("value"))
+        );
+        // existing entries can still be updated
+        assert_eq!(Ok(()), account.set_metadata("0", Some("new value")));
+    }
+}
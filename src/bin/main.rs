@@ -1,45 +1,47 @@
 extern crate blockchain;
 
+use blockchain::account::generate_keypair;
 use blockchain::block::Block;
 use blockchain::blockchain::Blockchain;
+use blockchain::genesis::GenesisConfig;
+use blockchain::id::Id;
+use blockchain::rng::OsRng;
 use blockchain::transaction::{Transaction, TransactionRecord};
 use blockchain::Error;
+use tracing_subscriber::EnvFilter;
 
 #[cfg(not(tarpaulin_include))]
 fn main() -> Result<(), Error> {
-    let mut chain = Blockchain::new();
-    let mut block = Block::new();
-
-    block.transactions.push(Transaction::new(
-        0,
-        TransactionRecord::CreateUserAccount("someone".into()),
-        None,
-    ));
+    // e.g. RUST_LOG=info,blockchain::transaction=debug
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
 
-    block.transactions.push(Transaction::new(
-        0,
-        TransactionRecord::CreateUserAccount("someone else".into()),
-        None,
-    ));
+    let someone = generate_keypair(&OsRng);
+    let someone_else = generate_keypair(&OsRng);
+    let genesis = GenesisConfig::default()
+        .with_allocation(someone.verifying_key().as_bytes(), 400)
+        .with_allocation(someone_else.verifying_key().as_bytes(), 0);
+    let mut chain = Blockchain::from_genesis(&genesis)?;
 
-    block.transactions.push(Transaction::new(
-        0,
-        TransactionRecord::MintTokens {
-            to: "someone".into(),
-            amount: 400,
-        },
-        None,
-    ));
+    let mut block = Block::new();
+    block.header.height = 1;
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
 
-    block.transactions.push(Transaction::new(
-        0,
+    let mut transaction = Transaction::new(
+        1,
         TransactionRecord::SendTokens {
-            to: "someone else".into(),
+            to: Id::from_public_key(someone_else.verifying_key().as_bytes()),
             amount: 200,
         },
-        Some("someone".into()),
-    ));
+        Some(Id::from_public_key(someone.verifying_key().as_bytes())),
+    );
+    transaction.sign(&someone);
+    block.transactions.push(transaction);
 
-    block.hash = Some(block.calculate_hash());
-    chain.add_block(block)
+    block.seal();
+    chain.add_block(block)?;
+    Ok(())
 }
@@ -1,9 +1,133 @@
+use crate::bloom::{BloomFilter, BLOOM_SIZE};
+use crate::clock::{Clock, SystemClock};
+use crate::encoding::{
+    put_bytes, put_optional_bytes, put_time, put_u32, put_u64, Reader, CANONICAL_VERSION,
+};
+use crate::id::Id;
+use crate::merkle::MerkleTree;
 use crate::transaction::Transaction;
-use crate::Hash;
+use crate::{Error, Hash};
+use std::convert::TryFrom;
+use std::time::SystemTime;
 
 /**
-A block contains a number of transactions.
-It is only valid in the context of the blockchain: its hash depends on the
+Everything a block's hash commits to. Its transactions are summed up by
+their root, so headers can be validated and followed without them.
+
+```
+# use crate::blockchain::block::Block;
+let mut block = Block::new();
+block.mine(4);
+
+let header = &block.header;
+assert!(header.meets_difficulty(&header.calculate_hash()));
+assert_eq!(block.hash, Some(header.calculate_hash()));
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockHeader {
+    /// Number of blocks before this one in the chain.
+    pub height: usize,
+
+    /// Hash of the previous block.
+    pub previous_hash: Option<Hash>,
+
+    /// Root of the Merkle tree of the transactions of the block.
+    pub merkle_root: Hash,
+
+    /// Bloom filter of all the accounts touched by the block's transactions,
+    /// so that light clients can filter blocks from their headers.
+    pub bloom: BloomFilter,
+
+    /// Account credited with the fees of the block's transactions, usually
+    /// the producer's.
+    pub beneficiary: Option<Id>,
+
+    /// Local time of creation.
+    pub timestamp: SystemTime,
+
+    /// Proof of work: found by mining, so that the hash meets the difficulty.
+    pub nonce: u64,
+
+    /// Minimum number of leading zero bits of the hash.
+    pub difficulty: u32,
+}
+
+impl BlockHeader {
+    /// Calculate the hash of the block: the hash of the header's canonical
+    /// encoding.
+    pub fn calculate_hash(&self) -> Hash {
+        blake3::hash(&self.to_canonical_bytes()).as_bytes().to_vec()
+    }
+
+    /**
+    Encode the header, to be hashed. All integers are big endian, and
+    variable-length fields are prefixed with their length as a `u64`:
+
+    | bytes     | content                                                 |
+    |-----------|---------------------------------------------------------|
+    | 1         | version, `CANONICAL_VERSION`                            |
+    | 8         | height                                                  |
+    | 1 (+ ...) | `0` for the genesis block, or `1` and the previous hash |
+    | 8 + ...   | merkle root                                             |
+    | 8 + 256   | bloom filter of the touched accounts                    |
+    | 1 (+ ...) | `0` without a beneficiary, or `1` and its ID            |
+    | 8 + 4     | timestamp: seconds and nanoseconds since the UNIX epoch |
+    | 8         | nonce                                                   |
+    | 4         | difficulty                                              |
+
+    The nonce comes last but for the difficulty, so that miners only
+    change the end of the encoding.
+    */
+    pub fn to_canonical_bytes(&self) -> Vec<u8> {
+        let mut out = vec![CANONICAL_VERSION];
+        put_u64(&mut out, self.height as u64);
+        put_optional_bytes(&mut out, self.previous_hash.as_ref());
+        put_bytes(&mut out, &self.merkle_root);
+        put_bytes(&mut out, self.bloom.as_bytes());
+        put_optional_bytes(&mut out, self.beneficiary.as_ref());
+        put_time(&mut out, self.timestamp);
+        put_u64(&mut out, self.nonce);
+        put_u32(&mut out, self.difficulty);
+        out
+    }
+
+    /// Decode a header from its canonical encoding.
+    pub fn from_canonical_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        let version = reader.get_u8()?;
+        if version != CANONICAL_VERSION {
+            return Err(format!("unsupported encoding version {}", version));
+        }
+
+        let header = BlockHeader {
+            height: usize::try_from(reader.get_u64()?).map_err(|_| "height out of range")?,
+            previous_hash: reader.get_optional_bytes()?.map(<[u8]>::to_vec),
+            merkle_root: reader.get_bytes()?.to_vec(),
+            bloom: BloomFilter::from_bytes(reader.get_bytes()?)?,
+            beneficiary: reader.get_optional_string()?.map(Id::from),
+            timestamp: reader.get_time()?,
+            nonce: reader.get_u64()?,
+            difficulty: reader.get_u32()?,
+        };
+        reader.finish()?;
+        Ok(header)
+    }
+
+    /// Does the hash meet this header's difficulty?
+    pub fn meets_difficulty(&self, hash: &[u8]) -> bool {
+        hash_meets_difficulty(hash, self.difficulty)
+    }
+
+    /// Expected number of hashes to mine this header: 2 to the power of its
+    /// difficulty, saturating past `u128::MAX`.
+    pub fn work(&self) -> u128 {
+        1u128.checked_shl(self.difficulty).unwrap_or(u128::MAX)
+    }
+}
+
+/**
+A block contains a number of transactions, and a header chaining it to the
 previous block.
 
 ```
@@ -13,35 +137,171 @@ previous block.
 
 let mut block = Block::new();
 
-let transaction = Transaction::new(5, TransactionRecord::CreateUserAccount("hi".into()), None);
+let transaction = Transaction::new(5, TransactionRecord::create_user_account(b"hi"), None);
 block.transactions.push(transaction);
+block.seal();
 
-println!("{:02X?}", block.calculate_hash());
+println!("{:02X?}", block.hash);
 ```
 */
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Block {
+    /// What the block's hash commits to.
+    pub header: BlockHeader,
+
     /// All transactions contained in this block.
     pub transactions: Vec<Transaction>,
 
-    /// Hash of the full block, i.e. hash all transactions hashes.
+    /// Hash of the block, i.e. the hash of its header.
     pub hash: Option<Hash>,
-
-    /// Hash of the previous block.
-    pub previous_hash: Option<Hash>,
 }
 
 impl Block {
-    /// Calculate the cryptographic hash of this block.
+    /// Calculate the cryptographic hash of this block, from its header.
     pub fn calculate_hash(&self) -> Hash {
-        self.transactions
-            .iter()
-            .fold(&mut blake3::Hasher::new(), |hasher, transaction| {
-                hasher.update(&transaction.calculate_hash())
-            })
-            .finalize()
-            .as_bytes()
-            .to_vec()
+        self.header.calculate_hash()
+    }
+
+    /// Root of the Merkle tree of the transactions, as committed to by the
+    /// header.
+    pub fn merkle_root(&self) -> &[u8] {
+        &self.header.merkle_root
+    }
+
+    /// Build the Merkle tree of the transactions of this block.
+    pub fn merkle_tree(&self) -> MerkleTree {
+        MerkleTree::new(self.transactions.iter().map(Transaction::calculate_hash))
+    }
+
+    /// Calculate the root of the Merkle tree of the transactions of this
+    /// block.
+    pub fn calculate_merkle_root(&self) -> Hash {
+        self.merkle_tree().root()
+    }
+
+    /// Commit to the transactions of the block, and set its hash. Call it
+    /// once the block is complete, if it doesn't need to be mined.
+    pub fn seal(&mut self) {
+        self.header.merkle_root = self.calculate_merkle_root();
+        self.header.bloom = self.calculate_bloom();
+        self.hash = Some(self.calculate_hash());
+    }
+
+    /**
+    Commit to the transactions of the block, then search for a nonce giving
+    a hash with at least `difficulty` leading zero bits, i.e. a hash below
+    the target, and set the block's hash.
+    Each additional bit of difficulty doubles the expected work.
+
+    ```
+    # use crate::blockchain::block::Block;
+    let mut block = Block::new();
+    block.mine(8);
+
+    assert!(block.is_hash_valid());
+    assert!(block.meets_difficulty());
+    assert_eq!(0, block.hash.unwrap()[0]);
+    ```
+
+    Returns the number of hashes computed, e.g. to measure the hashrate.
+    Panics if the difficulty is above `MAX_DIFFICULTY`, as no hash could
+    ever meet it.
+    */
+    pub fn mine(&mut self, difficulty: u32) -> u64 {
+        assert!(difficulty <= MAX_DIFFICULTY, "difficulty is too high");
+
+        self.header.merkle_root = self.calculate_merkle_root();
+        self.header.bloom = self.calculate_bloom();
+        self.header.difficulty = difficulty;
+        self.header.nonce = 0;
+        let mut bytes = self.header.to_canonical_bytes();
+        // the nonce is followed by the difficulty
+        let nonce_at = bytes.len() - std::mem::size_of::<u64>() - std::mem::size_of::<u32>();
+        let mut hashes: u64 = 0;
+        loop {
+            bytes[nonce_at..nonce_at + std::mem::size_of::<u64>()]
+                .copy_from_slice(&self.header.nonce.to_be_bytes());
+            let hash = blake3::hash(&bytes).as_bytes().to_vec();
+            hashes = hashes.saturating_add(1);
+            if hash_meets_difficulty(&hash, difficulty) {
+                self.hash = Some(hash);
+                return hashes;
+            }
+            self.header.nonce = self.header.nonce.wrapping_add(1);
+        }
+    }
+
+    /// Does this block's hash meet its own difficulty?
+    pub fn meets_difficulty(&self) -> bool {
+        self.hash
+            .as_ref()
+            .is_some_and(|hash| self.header.meets_difficulty(hash))
+    }
+
+    /// Encode everything this block's hash commits to: its header.
+    pub fn to_canonical_bytes(&self) -> Vec<u8> {
+        self.header.to_canonical_bytes()
+    }
+
+    /// Size of this block once serialized, in bytes.
+    pub fn size(&self) -> usize {
+        let hash_size = |hash: &Option<Hash>| 1 + hash.as_ref().map_or(0, Vec::len);
+
+        hash_size(&self.hash)
+            // height
+            + std::mem::size_of::<u64>()
+            + hash_size(&self.header.previous_hash)
+            + std::mem::size_of::<u64>()
+            + self.header.merkle_root.len()
+            + std::mem::size_of::<u64>()
+            + BLOOM_SIZE
+            + 1
+            + self
+                .header
+                .beneficiary
+                .as_ref()
+                .map_or(0, |id| std::mem::size_of::<u64>() + id.as_ref().len())
+            // seconds and nanoseconds since the UNIX epoch
+            + std::mem::size_of::<u64>()
+            + std::mem::size_of::<u32>()
+            // nonce and difficulty
+            + std::mem::size_of::<u64>()
+            + std::mem::size_of::<u32>()
+            // number of transactions
+            + std::mem::size_of::<u64>()
+            + self.transactions.iter().map(Transaction::size).sum::<usize>()
+    }
+
+    /// Iterate over the transactions of this block, in order.
+    pub fn iter_transactions(&self) -> std::slice::Iter<'_, Transaction> {
+        self.transactions.iter()
+    }
+
+    /// Build the bloom filter of all the accounts touched by this block.
+    pub fn calculate_bloom(&self) -> BloomFilter {
+        let mut bloom = BloomFilter::new();
+        for transaction in &self.transactions {
+            for id in transaction.touched_accounts() {
+                bloom.insert(id);
+            }
+        }
+        bloom
+    }
+
+    /// Might this block involve the account, according to its header's
+    /// bloom filter?
+    pub fn may_involve(&self, id: &Id) -> bool {
+        self.header.bloom.contains(id)
+    }
+
+    /// Does this block involve the account?
+    /// Uses the bloom filter to skip scanning transactions when possible.
+    pub fn involves(&self, id: &Id) -> bool {
+        self.may_involve(id)
+            && self
+                .transactions
+                .iter()
+                .any(|transaction| transaction.touched_accounts().contains(&id))
     }
 
     /// Is this block's hash valid?
@@ -52,16 +312,55 @@ impl Block {
         }
     }
 
+    /// Does the header commit to the transactions of this block?
+    pub fn is_merkle_root_valid(&self) -> bool {
+        self.merkle_root() == self.calculate_merkle_root().as_slice()
+    }
+
+    /// Does the header's bloom filter match the transactions of this block?
+    pub fn is_bloom_valid(&self) -> bool {
+        self.header.bloom == self.calculate_bloom()
+    }
+
     /// Constructor
     pub fn new() -> Self {
+        Self::with_clock(&SystemClock)
+    }
+
+    /// Constructor, timestamping the block with the given clock.
+    pub fn with_clock<C: Clock>(clock: &C) -> Self {
         Block {
+            header: BlockHeader {
+                height: 0,
+                previous_hash: None,
+                merkle_root: MerkleTree::new(Vec::<Hash>::new()).root(),
+                bloom: BloomFilter::new(),
+                beneficiary: None,
+                timestamp: clock.now(),
+                nonce: 0,
+                difficulty: 0,
+            },
             transactions: vec![],
             hash: None,
-            previous_hash: None,
         }
     }
 }
 
+/// Highest possible difficulty: every bit of the hash is zero.
+pub const MAX_DIFFICULTY: u32 = 8 * blake3::OUT_LEN as u32;
+
+/// Does the hash start with at least `difficulty` zero bits?
+pub fn hash_meets_difficulty(hash: &[u8], difficulty: u32) -> bool {
+    let mut zeros = 0;
+    for byte in hash {
+        zeros += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    zeros >= difficulty
+}
+
 impl Default for Block {
     fn default() -> Self {
         Self::new()
@@ -70,43 +369,153 @@ impl Default for Block {
 
 #[test]
 fn test_calculate_hash_is_deterministic() {
-    let block1 = Block::new();
-    let block2 = Block::new();
+    let clock = crate::clock::MockClock::new(std::time::UNIX_EPOCH);
+    let block1 = Block::with_clock(&clock);
+    let block2 = Block::with_clock(&clock);
     assert_eq!(block1.calculate_hash(), block2.calculate_hash());
 }
 
 #[test]
 fn test_calculate_hash_is_deterministic_with_transactions() {
+    use crate::clock::MockClock;
     use crate::transaction::TransactionRecord;
 
-    let mut block1 = Block::new();
-    let mut block2 = Block::new();
+    let clock = MockClock::new(std::time::UNIX_EPOCH);
+    let mut block1 = Block::with_clock(&clock);
+    let mut block2 = Block::with_clock(&clock);
 
-    let transaction1 = Transaction::new(5, TransactionRecord::CreateUserAccount("hi".into()), None);
-    let mut transaction2 =
-        Transaction::new(5, TransactionRecord::CreateUserAccount("hi".into()), None);
+    let record = || TransactionRecord::create_user_account(b"hi");
+    let transaction1 = Transaction::with_clock(5, record(), None, &clock);
+    let transaction2 = Transaction::with_clock(5, record(), None, &clock);
     // make sure transactions are equal, even though that's
     // not what we're testing here
-    transaction2.created_at = transaction1.created_at;
     assert_eq!(format!("{:?}", transaction1), format!("{:?}", transaction2));
 
     block1.transactions.push(transaction1);
     block2.transactions.push(transaction2);
-    assert_eq!(block1.calculate_hash(), block2.calculate_hash())
+    block1.seal();
+    block2.seal();
+    assert_eq!(block1.hash, block2.hash)
 }
 
 #[test]
 fn test_calculate_hash_does_not_collide() {
     use crate::transaction::TransactionRecord;
 
-    let block1 = Block::new();
-    let mut block2 = Block::new();
+    let mut block1 = Block::new();
+    let mut block2 = block1.clone();
 
     block2.transactions.push(Transaction::new(
         5,
-        TransactionRecord::CreateUserAccount("hi".into()),
+        TransactionRecord::create_user_account(b"hi"),
+        None,
+    ));
+    block1.seal();
+    block2.seal();
+
+    assert_ne!(block1.hash, block2.hash);
+}
+
+#[test]
+fn test_bloom_involves_accounts() {
+    use crate::transaction::TransactionRecord;
+
+    let mut block = Block::new();
+    block.transactions.push(Transaction::new(
+        0,
+        TransactionRecord::SendTokens {
+            to: "receiver".into(),
+            amount: 5,
+        },
+        Some("sender".into()),
+    ));
+    block.seal();
+    assert!(block.is_bloom_valid());
+    assert!(block.involves(&"sender".into()));
+    assert!(block.involves(&"receiver".into()));
+    assert!(!block.involves(&"nobody".into()));
+}
+
+#[test]
+fn test_calculate_hash_depends_on_the_whole_header() {
+    let block = Block::new();
+    let changes: [fn(&mut BlockHeader); 7] = [
+        |header| header.height = 1,
+        |header| header.previous_hash = Some(vec![]),
+        |header| header.merkle_root = vec![],
+        |header| header.bloom.insert("someone"),
+        |header| header.timestamp += std::time::Duration::from_nanos(1),
+        |header| header.nonce = 1,
+        |header| header.difficulty = 1,
+    ];
+    for change in &changes {
+        let mut other = block.clone();
+        change(&mut other.header);
+        assert_ne!(block.calculate_hash(), other.calculate_hash());
+    }
+}
+
+#[test]
+fn test_merkle_root_commits_to_transactions() {
+    use crate::transaction::TransactionRecord;
+
+    let mut block = Block::new();
+    block.seal();
+    assert!(block.is_merkle_root_valid());
+
+    block.transactions.push(Transaction::new(
+        5,
+        TransactionRecord::create_user_account(b"hi"),
         None,
     ));
+    assert!(block.is_hash_valid());
+    assert!(!block.is_merkle_root_valid());
 
-    assert_ne!(block1.calculate_hash(), block2.calculate_hash());
+    block.seal();
+    assert!(block.is_merkle_root_valid());
+    assert_eq!(block.merkle_root(), block.merkle_tree().root().as_slice());
+}
+
+#[test]
+fn test_header_encoding_roundtrip() {
+    let mut block = Block::new();
+    block.header.height = 3;
+    block.header.previous_hash = Some(vec![1; 32]);
+    block.mine(4);
+
+    let bytes = block.header.to_canonical_bytes();
+    assert_eq!(Ok(block.header), BlockHeader::from_canonical_bytes(&bytes));
+    for length in 0..bytes.len() {
+        assert!(BlockHeader::from_canonical_bytes(&bytes[..length]).is_err());
+    }
+}
+
+#[test]
+fn test_mine() {
+    use crate::clock::MockClock;
+
+    // a fixed timestamp, for the same nonce on every run
+    let mut block = Block::with_clock(&MockClock::new(std::time::UNIX_EPOCH));
+    block.mine(12);
+    assert_eq!(12, block.header.difficulty);
+    assert!(block.is_hash_valid());
+    assert!(block.meets_difficulty());
+
+    let hash = block.hash.clone().unwrap();
+    assert_eq!([0, 0], [hash[0], hash[1] & 0xF0]);
+
+    // nonces are tried in order: the ones before the mined nonce all fail
+    assert!(block.header.nonce > 0);
+    block.header.nonce -= 1;
+    block.seal();
+    assert!(!block.meets_difficulty());
+}
+
+#[test]
+fn test_hash_meets_difficulty() {
+    assert!(hash_meets_difficulty(&[0xFF], 0));
+    assert!(hash_meets_difficulty(&[0x00, 0x7F], 9));
+    assert!(!hash_meets_difficulty(&[0x00, 0x7F], 10));
+    assert!(hash_meets_difficulty(&[0; 32], MAX_DIFFICULTY));
+    assert!(!hash_meets_difficulty(&[0; 32], MAX_DIFFICULTY + 1));
 }
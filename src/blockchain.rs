@@ -1,128 +1,3726 @@
-use crate::account::Account;
-use crate::block::Block;
+use crate::account::{Account, Amount};
+use crate::block::{Block, BlockHeader};
+use crate::clock::{Clock, SystemClock};
+use crate::difficulty::DifficultyAdjuster;
+use crate::error::BlockchainError;
+use crate::events::{ChainEvent, EventBus, FINALIZED_DEPTH, SAFE_DEPTH};
+use crate::genesis::GenesisConfig;
 use crate::id::Id;
-use crate::transaction::Transaction;
+use crate::light::MAX_FUTURE_DRIFT;
+use crate::mempool::{Mempool, DEFAULT_MEMPOOL_SIZE};
+use crate::merkle::MerkleProof;
+use crate::metrics::Metrics;
+use crate::names::NameRecord;
+use crate::overlay::Overlay;
+use crate::page::Page;
+use crate::params::{ChainParams, ParamsSchedule};
+use crate::storage::{ChainStore, FileStore, MemoryStore, Snapshot};
+use crate::transaction::{Transaction, TransactionEvent, TransactionRecord};
+use crate::watch::WatchList;
 use crate::world::WorldState;
-use crate::{Error, Hash};
-use std::collections::HashMap;
+use crate::{Hash, Nonce};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::ops::{Bound, RangeBounds};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-/// Contains the state of the blockchain.
+/// How many checked blocks can wait to be applied when adding several blocks.
+const PIPELINE_DEPTH: usize = 4;
+/// How many blocks each worker checks in a batch, when adding several blocks.
+const BLOCKS_PER_WORKER: usize = 8;
+/// How many receipts of dropped pending transactions are kept.
+pub const MAX_FAILED_RECEIPTS: usize = 1024;
+
+/// A page of the transactions involving an account, along with the height of
+/// their block. The cursor is the (block height, position in the block) of a
+/// transaction.
+pub type HistoryPage = Page<(usize, Transaction), (usize, usize)>;
+
+/// Contains the state of the blockchain, keeping its blocks and state in a
+/// `ChainStore`: in memory unless told otherwise. Only the headers of the
+/// blocks stay loaded: once flushed, bodies are read from the store when
+/// needed.
 #[derive(Debug)]
-pub struct Blockchain {
-    /// All the blocks composing the blockchain.
-    blocks: Vec<Block>,
+pub struct Blockchain<S = MemoryStore, C = SystemClock> {
+    /// Headers of all the blocks composing the blockchain.
+    headers: Vec<BlockHeader>,
+
+    /// Hash of each block, by height.
+    hashes: Vec<Hash>,
+
+    /// Blocks added since the last flush, not in the store yet.
+    unflushed: Vec<Block>,
+
+    /// Size of all the blocks once serialized, in bytes.
+    chain_size: usize,
 
     /// All accounts, it is the current "world state".
     accounts: HashMap<Id, Account>,
 
+    /// Registered names, including expired ones.
+    names: HashMap<String, NameRecord>,
+
     /// In-progress transactions.
-    pending_transactions: Vec<Transaction>,
+    pending_transactions: Mempool,
+
+    /// Where each transaction of the chain was included, by transaction hash.
+    transactions_index: HashMap<Hash, TransactionLocation>,
+
+    /// Hashes of the transactions carrying each memo, oldest first.
+    memo_index: BTreeMap<String, Vec<Hash>>,
+
+    /// Events emitted by the transactions of each block, by block height.
+    events: Vec<Vec<TransactionEvent>>,
+
+    /// Height of the first block whose events are known: the events of the
+    /// blocks loaded from a store are not, until the chain is reindexed.
+    history_from: usize,
+
+    /// Balance of each account once the blocks loaded from a store were
+    /// applied, as restored from their snapshot.
+    loaded_balances: HashMap<Id, Amount>,
+
+    /// Receipts of the transactions of each block, by block height.
+    receipts: Vec<Vec<Receipt>>,
+
+    /// Receipts of the latest pending transactions dropped because they
+    /// failed, by transaction hash, oldest first.
+    failed_receipts: VecDeque<(Hash, Receipt)>,
+
+    /// Events emitted by the block currently being applied.
+    pending_events: Vec<TransactionEvent>,
+
+    /// Collects the fees of the block currently being applied.
+    beneficiary: Option<Id>,
+
+    /// Accounts to notify subscribers about.
+    watch_list: WatchList,
+
+    /// Counters and histograms about the activity of the chain.
+    metrics: Arc<Metrics>,
+
+    /// Where to announce what happens to the chain.
+    event_bus: EventBus<ChainEvent>,
+
+    /// Genesis the chain started from, if not a bare genesis block.
+    genesis: Option<GenesisConfig>,
+
+    /// Maximum number of tokens that can ever exist, if limited.
+    max_supply: Option<Amount>,
+
+    /// Number of tokens created since genesis.
+    minted: Amount,
+
+    /// Economic rules of the chain, by activation height.
+    params: ParamsSchedule,
+
+    /// Adjusts the difficulty of new blocks to the observed block times.
+    difficulty_adjuster: DifficultyAdjuster,
+
+    /// Where the blocks and the state are flushed to.
+    store: S,
+
+    /// Tells the time, to reject new blocks from too far in the future.
+    clock: C,
 }
 
-impl Blockchain {
+/// Statistics about the most recent blocks of the chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainStats {
+    /// Number of blocks the statistics are computed over.
+    pub blocks: usize,
+
+    /// Average time between two consecutive blocks, if it can be known.
+    pub average_block_time: Option<Duration>,
+
+    /// Average number of transactions per block.
+    pub transactions_per_block: f64,
+
+    /// Number of distinct accounts involved in transactions.
+    pub active_accounts: usize,
+
+    /// Size of the whole chain once serialized, in bytes.
+    pub chain_size: usize,
+
+    /// Fees paid by the transactions.
+    pub total_fees: Amount,
+
+    /// Average fee paid per transaction.
+    pub average_fee: f64,
+}
+
+/// Selects events by account and/or topic. Empty criteria match everything.
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    /// Only keep events involving this account.
+    pub account: Option<Id>,
+
+    /// Only keep events with this topic.
+    pub topic: Option<String>,
+}
+
+impl EventFilter {
+    /// Does the event match this filter?
+    pub fn matches(&self, event: &TransactionEvent) -> bool {
+        self.account.as_ref().is_none_or(|id| event.involves(id))
+            && self
+                .topic
+                .as_ref()
+                .is_none_or(|topic| event.topic() == topic)
+    }
+}
+
+/// Where a transaction was included in the chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionLocation {
+    /// Hash of the block including the transaction.
+    pub block_hash: Hash,
+
+    /// Height of the block including the transaction.
+    pub block_height: usize,
+
+    /// Position of the transaction in the block.
+    pub index: usize,
+}
+
+/// What applying a transaction did, kept along with its block. Pending
+/// transactions dropped because they failed get one too, for a while.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Receipt {
+    /// Why the transaction failed, if it did. A failed transaction has no
+    /// effect.
+    pub error: Option<BlockchainError>,
+
+    /// Events emitted by the transaction, in order.
+    pub events: Vec<TransactionEvent>,
+
+    /// Number of tokens held by each account the transaction involves,
+    /// once it was applied or when it failed.
+    pub balances: Vec<(Id, Amount)>,
+}
+
+impl Receipt {
+    /// Did the transaction apply?
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+impl<S: ChainStore, C: Clock> Blockchain<S, C> {
+    /// Is the chain waiting for its genesis block?
+    fn is_genesis(&self) -> bool {
+        self.headers.is_empty()
+    }
+
     /// Get the hash of the last block in the chain.
-    fn get_last_block_hash(&self) -> Option<&Hash> {
-        self.blocks.last()?.hash.as_ref()
+    pub fn get_last_block_hash(&self) -> Option<&Hash> {
+        self.hashes.last()
+    }
+
+    /// The block at that height, read from the store if it was flushed.
+    pub fn get_block(&self, height: usize) -> Result<Option<Block>, BlockchainError> {
+        if height >= self.headers.len() {
+            return Ok(None);
+        }
+        self.load_block(height).map(Some)
+    }
+
+    /// Load the block at a height of the chain: from the blocks added since
+    /// the last flush, or from the store, checking it against its header.
+    fn load_block(&self, height: usize) -> Result<Block, BlockchainError> {
+        let flushed = self.headers.len() - self.unflushed.len();
+        if let Some(index) = height.checked_sub(flushed) {
+            return Ok(self.unflushed[index].clone());
+        }
+
+        let block = self.store.get_block_by_height(height)?.ok_or_else(|| {
+            BlockchainError::Storage(format!("missing block at height {}", height))
+        })?;
+        if block.header != self.headers[height] {
+            return Err(BlockchainError::Storage(format!(
+                "stored block at height {} doesn't match its header",
+                height
+            )));
+        }
+        Ok(block)
     }
 
     /// If the block is correct, add it to the chain.
-    pub fn add_block(&mut self, block: Block) -> Result<(), Error> {
+    pub fn add_block(&mut self, block: Block) -> Result<(), BlockchainError> {
+        let _span = tracing::debug_span!("add_block", height = self.headers.len()).entered();
+
+        let result = Self::check_block(block)
+            .and_then(|block| self.check_not_in_future(block))
+            .and_then(|block| self.apply_block(block));
+        if let Err(err) = &result {
+            tracing::warn!(error = %err, "block rejected");
+            self.metrics.blocks_rejected.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /**
+    Add several blocks to the chain, in order.
+
+    Stateless validation runs on a pool of worker threads, a batch of blocks
+    at a time, while the blocks of the previous batch are being applied to the
+    state, so syncing many blocks is faster than calling `add_block` for each
+    of them.
+    Stops at the first invalid block: blocks before it are kept.
+    */
+    pub fn add_blocks(&mut self, blocks: Vec<Block>) -> Result<(), BlockchainError> {
+        let _span = tracing::debug_span!("add_blocks", count = blocks.len()).entered();
+        let (sender, receiver) = std::sync::mpsc::sync_channel(PIPELINE_DEPTH);
+        let workers = std::thread::available_parallelism().map_or(1, |workers| workers.get());
+
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                let mut blocks = blocks.into_iter();
+                loop {
+                    let batch: Vec<Block> =
+                        blocks.by_ref().take(workers * BLOCKS_PER_WORKER).collect();
+                    if batch.is_empty() {
+                        return;
+                    }
+
+                    for block in Self::check_blocks(batch, workers) {
+                        // the receiver hung up: a previous block was rejected
+                        if sender.send(block).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+
+            for (i, block) in receiver.into_iter().enumerate() {
+                let _span =
+                    tracing::debug_span!("add_block", height = self.headers.len()).entered();
+                let result = block
+                    .and_then(|block| self.check_not_in_future(block))
+                    .and_then(|block| self.apply_block(block));
+                if let Err(err) = result {
+                    tracing::warn!(error = %err, "block rejected");
+                    self.metrics.blocks_rejected.fetch_add(1, Ordering::Relaxed);
+                    return Err(BlockchainError::InvalidBlock {
+                        index: i,
+                        error: Box::new(err),
+                    });
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /**
+    Rebuild the state and indexes of the chain by validating and applying
+    every block again, from genesis. Use it to recover from a corrupted
+    index, or to fill an index that was added after the blocks.
+    The chain is left untouched if a block turns out to be invalid.
+    Pending transactions, subscribers and watched accounts are kept.
+    */
+    pub fn reindex(&mut self) -> Result<(), BlockchainError> {
+        let _span = tracing::info_span!("reindex", blocks = self.headers.len()).entered();
+
+        let mut rebuilt: Blockchain = match &self.genesis {
+            Some(genesis) => Blockchain::from_genesis(genesis)?,
+            None => Blockchain::new(),
+        };
+        rebuilt.difficulty_adjuster = self.difficulty_adjuster.clone();
+        let skip = rebuilt.headers.len();
+        if skip > 0 && rebuilt.get_last_block_hash() != self.hashes.first() {
+            return Err(BlockchainError::GenesisMismatch);
+        }
+
+        for height in skip..self.headers.len() {
+            let block = self.load_block(height)?;
+            Self::check_block(block)
+                .and_then(|block| rebuilt.apply_block(block))
+                .map_err(|err| BlockchainError::InvalidBlock {
+                    index: height,
+                    error: Box::new(err),
+                })?;
+            // this chain keeps the bodies already
+            rebuilt.unflushed.clear();
+        }
+
+        self.accounts = rebuilt.accounts;
+        self.names = rebuilt.names;
+        self.transactions_index = rebuilt.transactions_index;
+        self.memo_index = rebuilt.memo_index;
+        self.events = rebuilt.events;
+        self.history_from = 0;
+        self.loaded_balances.clear();
+        self.receipts = rebuilt.receipts;
+        self.minted = rebuilt.minted;
+        tracing::info!("chain reindexed");
+        Ok(())
+    }
+
+    /// Validate everything in the block that doesn't depend on the chain state.
+    fn check_block(block: Block) -> Result<Block, BlockchainError> {
         if !block.is_hash_valid() {
-            return Err("invalid hash".to_string());
+            return Err(BlockchainError::InvalidHash);
+        }
+
+        if !block.meets_difficulty() {
+            return Err(BlockchainError::InsufficientProofOfWork);
+        }
+
+        if !block.is_merkle_root_valid() {
+            return Err(BlockchainError::InvalidMerkleRoot);
+        }
+
+        if !block.is_bloom_valid() {
+            return Err(BlockchainError::InvalidBloom);
         }
 
+        Ok(block)
+    }
+
+    /// Reject a new block from further in the future than the clock drift
+    /// allows: the difficulty adjustment trusts the timestamps of blocks.
+    /// Blocks already in the chain are not checked again when reindexing.
+    fn check_not_in_future(&self, block: Block) -> Result<Block, BlockchainError> {
+        if block.header.timestamp > self.clock.now() + MAX_FUTURE_DRIFT {
+            return Err(BlockchainError::BlockInFuture);
+        }
+        Ok(block)
+    }
+
+    /// Check blocks concurrently, split between up to `workers` threads.
+    /// Results are in the same order as the blocks.
+    fn check_blocks(blocks: Vec<Block>, workers: usize) -> Vec<Result<Block, BlockchainError>> {
+        let chunk_size = blocks.len().div_ceil(workers.max(1));
+        let mut blocks = blocks.into_iter();
+        let chunks: Vec<Vec<Block>> = std::iter::from_fn(|| {
+            let chunk: Vec<Block> = blocks.by_ref().take(chunk_size).collect();
+            Some(chunk).filter(|chunk| !chunk.is_empty())
+        })
+        .collect();
+
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = chunks
+                .into_iter()
+                .map(|chunk| {
+                    scope.spawn(|| chunk.into_iter().map(Self::check_block).collect::<Vec<_>>())
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("block check panicked"))
+                .collect()
+        })
+    }
+
+    /// Apply an already checked block to the chain state.
+    fn apply_block(&mut self, block: Block) -> Result<(), BlockchainError> {
         if self.is_genesis() {
-            self.blocks.push(block);
+            self.push_block(block, vec![], vec![]);
             return Ok(());
         }
 
-        if block.previous_hash.as_ref() != self.get_last_block_hash() {
-            return Err("invalid previous hash".to_string());
+        if block.header.previous_hash.as_ref() != self.get_last_block_hash() {
+            return Err(BlockchainError::InvalidPreviousHash {
+                expected: self.get_last_block_hash().cloned(),
+                got: block.header.previous_hash,
+            });
         }
 
-        let previous_state = self.accounts.clone();
+        if block.header.height != self.height() {
+            return Err(BlockchainError::InvalidHeight {
+                expected: self.height(),
+                got: block.header.height,
+            });
+        }
+
+        if self
+            .headers
+            .last()
+            .is_some_and(|parent| block.header.timestamp < parent.timestamp)
+        {
+            return Err(BlockchainError::BlockBeforeParent);
+        }
+
+        let difficulty = self.current_difficulty();
+        if block.header.difficulty != difficulty {
+            return Err(BlockchainError::WrongDifficulty {
+                expected: difficulty,
+                got: block.header.difficulty,
+            });
+        }
+
+        let previous_state = (
+            self.accounts.clone(),
+            self.names.clone(),
+            self.params.clone(),
+        );
+        self.pending_events.clear();
+        self.beneficiary = block.header.beneficiary.clone();
+        let mut notifications = vec![];
+        let mut receipts = Vec::with_capacity(block.transactions.len());
         for (i, transaction) in block.transactions.iter().enumerate() {
-            if let Err(err) = transaction.apply(self) {
+            let first_event = self.pending_events.len();
+            let started_at = Instant::now();
+            let result = self
+                .check_signature(transaction)
+                .and_then(|()| Self::apply_in_order(self, transaction));
+            self.metrics
+                .transaction_validation
+                .observe(started_at.elapsed());
+
+            if let Err(err) = result {
+                tracing::debug!(transaction = i, error = %err, "transaction failed");
                 // roll back (this is super bad)
-                self.accounts = previous_state;
-                return Err(format! {"err {:?} on transaction {:?}", err, i});
+                (self.accounts, self.names, self.params) = previous_state;
+                self.pending_events.clear();
+                self.beneficiary = None;
+                return Err(BlockchainError::InvalidTransaction {
+                    index: i,
+                    error: Box::new(err),
+                });
             };
+
+            notifications.extend(self.watch_list.notifications(
+                self.headers.len(),
+                transaction,
+                &self.pending_events[first_event..],
+            ));
+            receipts.push(self.receipt(transaction, first_event));
         }
 
-        self.blocks.push(block);
+        self.beneficiary = None;
+        self.metrics
+            .transactions_applied
+            .fetch_add(block.transactions.len() as u64, Ordering::Relaxed);
+        let events = std::mem::take(&mut self.pending_events);
+        self.push_block(block, events, receipts);
+        self.watch_list.notify(notifications);
         Ok(())
     }
 
-    /// Constructor
-    pub fn new() -> Self {
-        Blockchain {
-            blocks: vec![],
-            accounts: HashMap::new(),
-            pending_transactions: vec![],
+    /// Apply a transaction if it uses the next nonce of its sender, and
+    /// advance that nonce.
+    fn apply_in_order(
+        world_state: &mut impl WorldState,
+        transaction: &Transaction,
+    ) -> Result<(), BlockchainError> {
+        let from_id = match &transaction.from_account_id {
+            Some(from_id) => from_id,
+            None => return transaction.apply(world_state),
+        };
+        let expected = world_state.get_account_by_id(from_id)?.nonce;
+        if transaction.nonce != expected {
+            return Err(BlockchainError::InvalidNonce {
+                expected,
+                got: transaction.nonce,
+            });
         }
+        let next = expected.checked_add(1).ok_or(BlockchainError::Overflow)?;
+
+        transaction.apply(world_state)?;
+        world_state.get_account_by_id_mut(from_id)?.nonce = next;
+        Ok(())
     }
-}
 
-impl Default for Blockchain {
-    fn default() -> Self {
-        Self::new()
+    /// Transactions sent from an account must be signed with its key. An
+    /// account without a key cannot send anything.
+    fn check_signature(&self, transaction: &Transaction) -> Result<(), BlockchainError> {
+        let from_id = match &transaction.from_account_id {
+            Some(from_id) => from_id,
+            None => return Ok(()),
+        };
+        let public_key = self
+            .get_account_by_id(from_id)?
+            .public_key
+            .as_ref()
+            .ok_or(BlockchainError::MissingPublicKey)?;
+        transaction.verify_signature(public_key)
     }
-}
 
-impl WorldState for Blockchain {
-    fn is_genesis(&self) -> bool {
-        self.blocks.is_empty()
+    /// Receipt of a transaction that was just applied, whose events start at
+    /// `first_event` in the pending events.
+    fn receipt(&self, transaction: &Transaction, first_event: usize) -> Receipt {
+        let mut accounts = transaction.touched_accounts();
+        accounts.dedup();
+        Receipt {
+            error: None,
+            events: self.pending_events[first_event..].to_vec(),
+            balances: accounts
+                .into_iter()
+                .filter_map(|id| Some((id.clone(), self.accounts.get(id)?.tokens)))
+                .collect(),
+        }
     }
 
-    fn get_account_by_id(&self, id: &Id) -> Result<&Account, Error> {
-        self.accounts
-            .get(id)
-            .ok_or_else(|| "account doesn't exist".to_string())
+    /// Keep the receipt of a pending transaction dropped because it failed,
+    /// forgetting the oldest ones past `MAX_FAILED_RECEIPTS`.
+    fn record_failure(&mut self, hash: Hash, receipt: Receipt) {
+        if self.failed_receipts.len() == MAX_FAILED_RECEIPTS {
+            self.failed_receipts.pop_front();
+        }
+        self.failed_receipts.push_back((hash, receipt));
+    }
+
+    /// Append an applied block to the chain, and index its transactions.
+    fn push_block(&mut self, block: Block, events: Vec<TransactionEvent>, receipts: Vec<Receipt>) {
+        let block_height = self.headers.len();
+        for (index, transaction) in block.transactions.iter().enumerate() {
+            let hash = transaction.calculate_hash();
+            self.event_bus.publish(ChainEvent::TxIncluded {
+                hash: hash.clone(),
+                block_height,
+            });
+            if let Some(memo) = &transaction.memo {
+                self.memo_index
+                    .entry(memo.clone())
+                    .or_default()
+                    .push(hash.clone());
+            }
+            self.transactions_index.insert(
+                hash,
+                TransactionLocation {
+                    block_hash: block.hash.clone().unwrap_or_default(),
+                    block_height,
+                    index,
+                },
+            );
+        }
+
+        tracing::info!(
+            height = block_height,
+            transactions = block.transactions.len(),
+            "block added"
+        );
+        self.event_bus.publish(ChainEvent::BlockAdded {
+            height: block_height,
+            hash: block.hash.clone().unwrap_or_default(),
+        });
+        self.headers.push(block.header.clone());
+        self.hashes.push(block.hash.clone().unwrap_or_default());
+        self.chain_size += block.size();
+        self.unflushed.push(block);
+        self.events.push(events);
+        self.receipts.push(receipts);
+        self.metrics.blocks_applied.fetch_add(1, Ordering::Relaxed);
+        self.publish_confirmations();
+    }
+
+    /// Tell subscribers about the blocks the new tip made safe or final.
+    fn publish_confirmations(&self) {
+        let tip = self.headers.len() - 1;
+        if let Some(height) = tip.checked_sub(SAFE_DEPTH) {
+            self.event_bus.publish(ChainEvent::BlockSafe {
+                height,
+                hash: self.hashes[height].clone(),
+            });
+        }
+        if let Some(height) = tip.checked_sub(FINALIZED_DEPTH) {
+            self.event_bus.publish(ChainEvent::BlockFinalized {
+                height,
+                hash: self.hashes[height].clone(),
+            });
+        }
     }
 
-    fn get_account_by_id_mut(&mut self, id: &Id) -> Result<&mut Account, Error> {
+    /// Events emitted by the transactions of the block at that height.
+    pub fn get_block_events(&self, height: usize) -> Option<&[TransactionEvent]> {
+        self.events.get(height).map(Vec::as_slice)
+    }
+
+    /// All the events matching the filter, along with the height of the block
+    /// they were emitted in, oldest first.
+    pub fn find_events<'a>(
+        &'a self,
+        filter: &'a EventFilter,
+    ) -> impl Iterator<Item = (usize, &'a TransactionEvent)> + 'a {
+        self.events
+            .iter()
+            .enumerate()
+            .flat_map(|(height, events)| events.iter().map(move |event| (height, event)))
+            .filter(move |(_, event)| filter.matches(event))
+    }
+
+    /// Find a transaction included in the chain by its hash, along with
+    /// where it was included.
+    pub fn get_transaction(
+        &self,
+        hash: &[u8],
+    ) -> Result<Option<(Transaction, &TransactionLocation)>, BlockchainError> {
+        let location = match self.transactions_index.get(hash) {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+        let transaction = self
+            .load_block(location.block_height)?
+            .transactions
+            .into_iter()
+            .nth(location.index);
+        Ok(transaction.map(|transaction| (transaction, location)))
+    }
+
+    /// All the transactions carrying exactly this memo, oldest first.
+    pub fn transactions_with_memo<'a>(
+        &'a self,
+        memo: &str,
+    ) -> impl Iterator<Item = Result<(Transaction, &'a TransactionLocation), BlockchainError>> + 'a
+    {
+        self.memo_index
+            .get(memo)
+            .into_iter()
+            .flatten()
+            .filter_map(move |hash| self.get_transaction(hash).transpose())
+    }
+
+    /// All the transactions whose memo starts with the prefix, grouped by
+    /// memo in lexicographic order, oldest first within a memo.
+    pub fn transactions_with_memo_prefix<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = Result<(Transaction, &'a TransactionLocation), BlockchainError>> + 'a
+    {
+        self.memo_index
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(move |(memo, _)| memo.starts_with(prefix))
+            .flat_map(|(_, hashes)| hashes)
+            .filter_map(move |hash| self.get_transaction(hash).transpose())
+    }
+
+    /**
+    Prove that a transaction is included in the chain, by its hash: the
+    proof can be checked against the Merkle root of the block including it,
+    without the rest of the chain.
+
+    ```
+    # use crate::blockchain::testing::ChainBuilder;
+    let chain = ChainBuilder::new()
+        .with_account("alice", 10)
+        .with_account("bob", 0)
+        .block(|block| block.transfer("alice", "bob", 3).transfer("alice", "bob", 4))
+        .build()
+        .unwrap();
+
+    let block = chain.iter_blocks_from_tip().next().unwrap().unwrap();
+    let hash = block.transactions[1].calculate_hash();
+    let proof = chain.prove_transaction(&hash).unwrap().unwrap();
+
+    assert!(proof.verify(block.merkle_root(), &hash));
+    ```
+    */
+    pub fn prove_transaction(&self, hash: &[u8]) -> Result<Option<MerkleProof>, BlockchainError> {
+        let location = match self.transactions_index.get(hash) {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+        Ok(self
+            .load_block(location.block_height)?
+            .merkle_tree()
+            .proof(hash))
+    }
+
+    /// The receipt of a transaction included in the chain, or of a pending
+    /// transaction recently dropped because it failed, by its hash.
+    pub fn get_receipt(&self, hash: &[u8]) -> Option<&Receipt> {
+        match self.transactions_index.get(hash) {
+            Some(location) => self
+                .receipts
+                .get(location.block_height)?
+                .get(location.index),
+            None => self
+                .failed_receipts
+                .iter()
+                .rev()
+                .find(|(failed, _)| failed.as_slice() == hash)
+                .map(|(_, receipt)| receipt),
+        }
+    }
+
+    /// Iterate over the blocks whose heights are in the range, oldest first,
+    /// loading them one at a time. Heights past the tip are ignored.
+    pub fn iter_blocks<R: RangeBounds<usize>>(
+        &self,
+        range: R,
+    ) -> impl DoubleEndedIterator<Item = Result<Block, BlockchainError>> + '_ {
+        let start = match range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => end.saturating_add(1),
+            Bound::Excluded(end) => *end,
+            Bound::Unbounded => self.headers.len(),
+        };
+
+        let end = end.min(self.headers.len());
+        (start.min(end)..end).map(move |height| self.load_block(height))
+    }
+
+    /// Iterate over all the blocks, starting from the tip of the chain.
+    pub fn iter_blocks_from_tip(
+        &self,
+    ) -> impl Iterator<Item = Result<Block, BlockchainError>> + '_ {
+        self.iter_blocks(..).rev()
+    }
+
+    /// A page of blocks, oldest first. The cursor is a block height.
+    pub fn list_blocks(
+        &self,
+        cursor: Option<usize>,
+        limit: usize,
+    ) -> Result<Page<Block, usize>, BlockchainError> {
+        let start = cursor.unwrap_or(0);
+        Page::try_collect(
+            self.iter_blocks(start..)
+                .zip(start..)
+                .map(|(block, height)| Ok((height, block?))),
+            limit,
+        )
+    }
+
+    /// A page of the transactions involving the account, oldest first, along
+    /// with the height of their block. Only the blocks whose header may
+    /// involve the account are loaded.
+    /// The cursor is the (block height, position in the block) of a transaction.
+    pub fn account_history(
+        &self,
+        id: &Id,
+        cursor: Option<(usize, usize)>,
+        limit: usize,
+    ) -> Result<HistoryPage, BlockchainError> {
+        let cursor = cursor.unwrap_or((0, 0));
+        let transactions = self
+            .headers
+            .iter()
+            .enumerate()
+            .skip(cursor.0)
+            .filter(|(_, header)| header.bloom.contains(id))
+            .flat_map(|(height, _)| match self.load_block(height) {
+                Ok(block) => block
+                    .transactions
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, transaction)| Ok(((height, index), (height, transaction))))
+                    .collect(),
+                Err(err) => vec![Err(err)],
+            })
+            .filter(|item| {
+                item.as_ref().map_or(true, |(position, (_, transaction))| {
+                    *position >= cursor && transaction.touched_accounts().contains(&id)
+                })
+            });
+
+        Page::try_collect(transactions, limit)
+    }
+
+    /**
+    Number of tokens the account held once the block at that height was
+    applied, replayed from the events of the chain.
+    None if the account didn't exist yet, or the height is past the tip.
+
+    The events of blocks loaded from a store are not restored: for a loaded
+    chain, balances are only known from the last loaded block on, and None
+    before it, until the chain is reindexed.
+    */
+    pub fn balance_at(&self, id: &Id, height: usize) -> Option<Amount> {
+        let mut balance = None;
+        if self.history_from > 0 {
+            if height < self.history_from - 1 {
+                return None;
+            }
+            balance = self.loaded_balances.get(id).copied();
+        }
+        for event in self
+            .events
+            .get(self.history_from..=height)?
+            .iter()
+            .flatten()
+        {
+            match event {
+                TransactionEvent::AccountCreated { id: created } if created == id => {
+                    balance = Some(0)
+                }
+                TransactionEvent::Minted { to, amount }
+                | TransactionEvent::FeesCollected { to, amount }
+                    if to == id =>
+                {
+                    balance = balance.map(|tokens: Amount| tokens.saturating_add(*amount))
+                }
+                TransactionEvent::FeePaid { from, amount } if from == id => {
+                    balance = balance.map(|tokens| tokens.saturating_sub(*amount))
+                }
+                TransactionEvent::Transfer { from, to, amount } if from != to => {
+                    if from == id {
+                        balance = balance.map(|tokens| tokens.saturating_sub(*amount));
+                    } else if to == id {
+                        balance = balance.map(|tokens| tokens.saturating_add(*amount));
+                    }
+                }
+                _ => {}
+            }
+        }
+        balance
+    }
+
+    /// All the blocks involving the account, oldest first. Only the blocks
+    /// whose header may involve the account are loaded.
+    pub fn blocks_involving<'a>(
+        &'a self,
+        id: &'a Id,
+    ) -> impl Iterator<Item = Result<Block, BlockchainError>> + 'a {
+        self.headers
+            .iter()
+            .enumerate()
+            .filter(move |(_, header)| header.bloom.contains(id))
+            .map(move |(height, _)| self.load_block(height))
+            .filter(move |block| block.as_ref().map_or(true, |block| block.involves(id)))
+    }
+
+    /// Number of tokens held by all the accounts.
+    pub fn total_supply(&self) -> Amount {
         self.accounts
-            .get_mut(id)
-            .ok_or_else(|| "account doesn't exist".to_string())
+            .values()
+            .fold(0, |supply, account| supply.saturating_add(account.tokens))
     }
 
-    fn add_account(&mut self, id: Id) -> Result<(), Error> {
-        if let std::collections::hash_map::Entry::Vacant(accounts) = self.accounts.entry(id) {
-            accounts.insert(Account::new());
-            Ok(())
-        } else {
-            Err("account already exists".to_string())
+    /// Number of tokens created since genesis.
+    pub fn minted_supply(&self) -> Amount {
+        self.minted
+    }
+
+    /// Maximum number of tokens that can ever exist, if limited.
+    pub fn max_supply(&self) -> Option<Amount> {
+        self.max_supply
+    }
+
+    /// Execute a transaction as if it was included in the next block, mined
+    /// by `beneficiary`, without changing the chain, e.g. for wallets to check
+    /// a transaction before sending it. Returns the events it would emit.
+    pub fn simulate(
+        &self,
+        transaction: &Transaction,
+        beneficiary: &Id,
+    ) -> Result<Vec<TransactionEvent>, BlockchainError> {
+        let mut overlay = Overlay::new(self).with_beneficiary(beneficiary.clone());
+        transaction.apply(&mut overlay)?;
+        Ok(overlay.into_events())
+    }
+
+    /// The account a name currently points to, if it is registered.
+    pub fn resolve_name(&self, name: &str) -> Option<&Id> {
+        self.names
+            .get(name)
+            .filter(|record| record.is_active(self.headers.len()))
+            .map(|record| &record.owner)
+    }
+
+    /// Economic rules in effect for the block at that height.
+    pub fn params_at(&self, height: usize) -> &ChainParams {
+        self.params.at(height)
+    }
+
+    /// Mine a block at the difficulty the chain expects next, counting the
+    /// hashes computed in the metrics.
+    pub fn mine_block(&self, block: &mut Block) {
+        let hashes = block.mine(self.current_difficulty());
+        self.metrics
+            .mining_hashes
+            .fetch_add(hashes, Ordering::Relaxed);
+    }
+
+    /// Difficulty the next block must be mined at: adjusted to the observed
+    /// block times, but never below the minimum difficulty of the chain.
+    pub fn current_difficulty(&self) -> u32 {
+        self.difficulty_adjuster
+            .next_difficulty(&self.headers)
+            .max(self.params_at(self.headers.len()).difficulty)
+    }
+
+    /// Change how the difficulty adjusts to the observed block times.
+    pub fn set_difficulty_adjuster(&mut self, adjuster: DifficultyAdjuster) {
+        self.difficulty_adjuster = adjuster;
+    }
+
+    /// Create new tokens, without exceeding the maximum supply.
+    fn mint(&mut self, to: &Id, amount: Amount) -> Result<(), BlockchainError> {
+        let minted = self
+            .minted
+            .checked_add(amount)
+            .filter(|minted| {
+                self.max_supply
+                    .is_none_or(|max_supply| *minted <= max_supply)
+            })
+            .ok_or(BlockchainError::MaxSupplyExceeded)?;
+
+        let account = self.get_account_by_id_mut(to)?;
+        account.tokens = account
+            .tokens
+            .checked_add(amount)
+            .ok_or(BlockchainError::Overflow)?;
+        self.minted = minted;
+        Ok(())
+    }
+
+    /// Check the invariants of the whole chain: blocks are valid and linked
+    /// to each other, and accounts hold exactly the tokens that were minted,
+    /// within the maximum supply.
+    pub fn verify_chain(&self) -> Result<(), BlockchainError> {
+        let mut previous: Option<Block> = None;
+        for (height, block) in self.iter_blocks(..).enumerate() {
+            let block = block?;
+            let previous_hash = previous
+                .as_ref()
+                .and_then(|previous| previous.hash.as_ref());
+            let invalid = |error| BlockchainError::InvalidBlock {
+                index: height,
+                error: Box::new(error),
+            };
+            if !block.is_hash_valid() {
+                return Err(invalid(BlockchainError::InvalidHash));
+            }
+            if !block.meets_difficulty() {
+                return Err(invalid(BlockchainError::InsufficientProofOfWork));
+            }
+            if !block.is_merkle_root_valid() {
+                return Err(invalid(BlockchainError::InvalidMerkleRoot));
+            }
+            if !block.is_bloom_valid() {
+                return Err(invalid(BlockchainError::InvalidBloom));
+            }
+            if height > 0 && block.header.previous_hash.as_ref() != previous_hash {
+                return Err(invalid(BlockchainError::InvalidPreviousHash {
+                    expected: previous_hash.cloned(),
+                    got: block.header.previous_hash.clone(),
+                }));
+            }
+            if height > 0 && block.header.height != height {
+                return Err(invalid(BlockchainError::InvalidHeight {
+                    expected: height,
+                    got: block.header.height,
+                }));
+            }
+            if previous
+                .as_ref()
+                .is_some_and(|previous| block.header.timestamp < previous.header.timestamp)
+            {
+                return Err(invalid(BlockchainError::BlockBeforeParent));
+            }
+            previous = Some(block);
+        }
+
+        let supply = self
+            .accounts
+            .values()
+            .try_fold(0 as Amount, |supply, account| {
+                supply.checked_add(account.tokens)
+            })
+            .ok_or(BlockchainError::Overflow)?;
+        if supply != self.minted {
+            return Err(BlockchainError::SupplyMismatch {
+                held: supply,
+                minted: self.minted,
+            });
         }
+        if self
+            .max_supply
+            .is_some_and(|max_supply| self.minted > max_supply)
+        {
+            return Err(BlockchainError::MaxSupplyExceeded);
+        }
+        Ok(())
     }
-}
 
-#[test]
-fn test_add_block() {
-    use crate::transaction::TransactionRecord;
-    use std::time::SystemTime;
+    /// Number of tokens that can be freely transferred.
+    /// Tokens cannot be burned nor locked yet, so it is the total supply.
+    pub fn circulating_supply(&self) -> Amount {
+        self.total_supply()
+    }
 
-    let mut chain = Blockchain::new();
-    let mut block = Block::new();
+    /// The `n` accounts holding the most tokens, richest first.
+    pub fn top_accounts(&self, n: usize) -> Vec<(&Id, &Account)> {
+        let mut accounts: Vec<(&Id, &Account)> = self.accounts.iter().collect();
+        accounts.sort_by(|(a_id, a), (b_id, b)| {
+            b.tokens
+                .cmp(&a.tokens)
+                .then_with(|| a_id.as_ref().cmp(b_id.as_ref()))
+        });
+        accounts.truncate(n);
+        accounts
+    }
 
-    block.transactions.push(Transaction {
-        nonce: 0,
-        from_account_id: Some("hello".into()),
-        record: TransactionRecord::CreateUserAccount("world".into()),
-        signature: Some("signature".to_string()),
-        created_at: SystemTime::now(),
-    });
-    block.hash = Some(block.calculate_hash());
+    /// Statistics over the last `window` blocks of the chain.
+    pub fn stats(&self, window: usize) -> Result<ChainStats, BlockchainError> {
+        let recent: Vec<Block> = self
+            .iter_blocks(self.headers.len().saturating_sub(window)..)
+            .collect::<Result<_, _>>()?;
 
-    assert_eq!(Ok(()), chain.add_block(block))
-}
+        let average_block_time = match (recent.first(), recent.last()) {
+            (Some(first), Some(last)) if recent.len() > 1 => last
+                .header
+                .timestamp
+                .duration_since(first.header.timestamp)
+                .ok()
+                .map(|elapsed| elapsed / (recent.len() - 1) as u32),
+            _ => None,
+        };
 
-#[test]
-fn test_cannot_create_duplicate_accounts() {
-    let mut chain = Blockchain::new();
+        let transactions: usize = recent.iter().map(|block| block.transactions.len()).sum();
+        let total_fees = recent
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .fold(0 as Amount, |fees, transaction| {
+                fees.saturating_add(transaction.fee)
+            });
+        let active_accounts: HashSet<&Id> = recent
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .flat_map(Transaction::touched_accounts)
+            .collect();
 
-    chain.add_account("someone".into()).unwrap();
+        Ok(ChainStats {
+            blocks: recent.len(),
+            average_block_time,
+            transactions_per_block: if recent.is_empty() {
+                0.0
+            } else {
+                transactions as f64 / recent.len() as f64
+            },
+            active_accounts: active_accounts.len(),
+            chain_size: self.chain_size,
+            total_fees,
+            average_fee: if transactions == 0 {
+                0.0
+            } else {
+                total_fees as f64 / transactions as f64
+            },
+        })
+    }
+
+    /// The chain as a GraphViz graph: one node per block, linked to its
+    /// previous block. The chain has no forks yet, so it is a single line.
+    pub fn to_dot(&self) -> Result<String, BlockchainError> {
+        let mut dot = String::from("digraph chain {\n    rankdir=RL;\n");
+        for (height, block) in self.iter_blocks(..).enumerate() {
+            let block = block?;
+            let hash: String = block
+                .hash
+                .iter()
+                .flat_map(|hash| hash.iter().take(4))
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            dot += &format!(
+                "    {} [label=\"#{}\\n{}\\n{} transactions\"];\n",
+                height,
+                height,
+                hash,
+                block.transactions.len()
+            );
+            if height > 0 {
+                dot += &format!("    {} -> {};\n", height, height - 1);
+            }
+        }
+        Ok(dot + "}\n")
+    }
+
+    /// Tokens sent between accounts by the blocks in `range`, as a GraphViz
+    /// graph: one edge per pair of accounts, labelled with the total amount.
+    pub fn flows_to_dot<R: RangeBounds<usize>>(&self, range: R) -> Result<String, BlockchainError> {
+        let mut flows: BTreeMap<(String, String), Amount> = BTreeMap::new();
+        for block in self.iter_blocks(range) {
+            for transaction in block?.transactions {
+                if let (Some(from), TransactionRecord::SendTokens { to, amount }) =
+                    (&transaction.from_account_id, &transaction.record)
+                {
+                    let flow = flows.entry((from.to_string(), to.to_string())).or_insert(0);
+                    *flow = flow.saturating_add(*amount);
+                }
+            }
+        }
+
+        let quote = |id: &str| format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut dot = String::from("digraph flows {\n");
+        for ((from, to), amount) in flows {
+            dot += &format!(
+                "    {} -> {} [label=\"{}\"];\n",
+                quote(&from),
+                quote(&to),
+                amount
+            );
+        }
+        Ok(dot + "}\n")
+    }
+
+    /// Transactions waiting to be included in a block.
+    pub fn mempool(&self) -> &Mempool {
+        &self.pending_transactions
+    }
+
+    /// Mutable access to the transactions waiting to be included in a block.
+    pub fn mempool_mut(&mut self) -> &mut Mempool {
+        &mut self.pending_transactions
+    }
+
+    /**
+    Queue a transaction in the mempool, if it is valid against the current
+    state: signed by its sender, not reusing a nonce of the sender's pending
+    transactions nor replaying an included transaction, and applicable after
+    the sender's pending transactions, e.g. affordable and using the nonce
+    that follows theirs.
+
+    ```
+    # use crate::blockchain::error::BlockchainError;
+    # use crate::blockchain::testing::{account_id, keypair, ChainBuilder};
+    # use crate::blockchain::transaction::{Transaction, TransactionRecord};
+    let mut chain = ChainBuilder::new()
+        .with_account("alice", 10)
+        .with_account("bob", 0)
+        .build()
+        .unwrap();
+    let transfer = |nonce, amount| {
+        let mut transaction = Transaction::new(
+            nonce,
+            TransactionRecord::SendTokens { to: account_id("bob"), amount },
+            Some(account_id("alice")),
+        );
+        transaction.sign(&keypair("alice"));
+        transaction
+    };
+
+    chain.submit_transaction(transfer(1, 6)).unwrap();
     assert_eq!(
-        Err("account already exists".to_string()),
-        chain.add_account("someone".into())
-    )
+        Err(BlockchainError::NonceAlreadyUsed),
+        chain.submit_transaction(transfer(1, 1))
+    );
+    // alice only has 4 tokens left once her pending transfer is applied
+    assert!(chain.submit_transaction(transfer(2, 6)).is_err());
+    assert_eq!(1, chain.pending_count());
+    ```
+    */
+    pub fn submit_transaction(&mut self, transaction: Transaction) -> Result<(), BlockchainError> {
+        self.check_signature(&transaction)?;
+        if self
+            .transactions_index
+            .contains_key(&transaction.calculate_hash())
+        {
+            return Err(BlockchainError::AlreadyIncluded);
+        }
+
+        let mut overlay = Overlay::new(&*self);
+        if let Some(sender) = &transaction.from_account_id {
+            for pending in self.pending_transactions.iter() {
+                if pending.from_account_id.as_ref() != Some(sender) {
+                    continue;
+                }
+                if pending.nonce == transaction.nonce {
+                    return Err(BlockchainError::NonceAlreadyUsed);
+                }
+                // a pending transaction that fails will not spend anything
+                let _ = Self::apply_in_order(&mut overlay, &pending);
+            }
+        }
+        Self::apply_in_order(&mut overlay, &transaction)?;
+
+        let lane = transaction.lane(&*self);
+        self.pending_transactions
+            .add_to_lane(transaction, lane)
+            .map_err(BlockchainError::MempoolRejected)
+    }
+
+    /// Nonce the next transaction of an account must use: the one following
+    /// its pending transactions that still apply.
+    pub fn next_nonce(&self, account: &Id) -> Result<Nonce, BlockchainError> {
+        let mut overlay = Overlay::new(self);
+        for pending in self.pending_transactions.iter() {
+            if pending.from_account_id.as_ref() == Some(account) {
+                // a pending transaction that fails will not use its nonce
+                let _ = Self::apply_in_order(&mut overlay, &pending);
+            }
+        }
+        Ok(overlay.get_account_by_id(account)?.nonce)
+    }
+
+    /// Number of transactions waiting to be included in a block.
+    pub fn pending_count(&self) -> usize {
+        self.pending_transactions.len()
+    }
+
+    /**
+    Remove and return up to `max` pending transactions that can be included
+    together in the next block, in the order of `Mempool::drain`. Pending
+    transactions that no longer apply, e.g. because the sender spent their
+    tokens in the meantime, are dropped: their receipt tells why.
+    */
+    pub fn drain_valid(&mut self, max: usize) -> Vec<Transaction> {
+        // the mempool is set aside while the chain is borrowed by the overlay
+        let mut pending = std::mem::take(&mut self.pending_transactions);
+        let mut valid = vec![];
+        let mut failed = vec![];
+        let mut overlay = Overlay::new(&*self);
+        // only drain again to replace the dropped transactions, within what
+        // the included ones left of the lane quotas
+        let mut quotas = pending.lane_quotas();
+        let mut wanted = max;
+        while wanted > 0 {
+            let candidates = pending.drain_within(wanted, &mut quotas);
+            if candidates.is_empty() {
+                break;
+            }
+            wanted = 0;
+            for transaction in candidates {
+                let result = self
+                    .check_signature(&transaction)
+                    .and_then(|()| Self::apply_in_order(&mut overlay, &transaction));
+                match result {
+                    Ok(()) => valid.push(transaction),
+                    Err(err) => {
+                        tracing::debug!(error = %err, "pending transaction dropped");
+                        quotas.release(transaction.lane(&*self));
+                        wanted += 1;
+                        let mut accounts = transaction.touched_accounts();
+                        accounts.dedup();
+                        let balances = accounts
+                            .into_iter()
+                            .filter_map(|id| {
+                                Some((id.clone(), overlay.get_account_by_id(id).ok()?.tokens))
+                            })
+                            .collect();
+                        failed.push((
+                            transaction.calculate_hash(),
+                            Receipt {
+                                error: Some(err),
+                                events: vec![],
+                                balances,
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+        self.pending_transactions = pending;
+        for (hash, receipt) in failed {
+            self.record_failure(hash, receipt);
+        }
+        valid
+    }
+
+    /// Accounts to notify subscribers about.
+    pub fn watch_list(&self) -> &WatchList {
+        &self.watch_list
+    }
+
+    /// Mutable access to the accounts to notify subscribers about.
+    pub fn watch_list_mut(&mut self) -> &mut WatchList {
+        &mut self.watch_list
+    }
+
+    /// Counters and histograms about the activity of the chain, e.g. to
+    /// serve them with `metrics::serve`.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// Receive everything that happens to the chain from now on.
+    pub fn subscribe(&self) -> Receiver<ChainEvent> {
+        self.event_bus.subscribe()
+    }
+
+    /// Store the blocks added since the last flush, and a snapshot of the
+    /// state after them, then make them durable. Their bodies are then only
+    /// kept by the store.
+    pub fn flush(&mut self) -> Result<(), BlockchainError> {
+        // blocks stored by a flush that failed afterwards are not stored again
+        let flushed = self.headers.len() - self.unflushed.len();
+        let stored = self.store.height().saturating_sub(flushed);
+        for block in self.unflushed.iter().skip(stored) {
+            self.store.put_block(block)?;
+        }
+        self.store.put_state(&Snapshot {
+            height: self.headers.len(),
+            accounts: self.accounts.clone(),
+            names: self.names.clone(),
+            minted: self.minted,
+            max_supply: self.max_supply,
+            genesis: self.genesis.clone(),
+            params: self.params.clone(),
+            difficulty_adjuster: self.difficulty_adjuster.clone(),
+        })?;
+        self.store.flush()?;
+        self.unflushed.clear();
+        Ok(())
+    }
+
+    /// Store the chain is kept in.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Give the store back, e.g. to load the chain again. Blocks that were
+    /// not flushed are lost.
+    pub fn into_store(self) -> S {
+        self.store
+    }
+
+    /// The clock telling the time.
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// Tell the time with another clock, e.g. a `MockClock` in tests.
+    pub fn with_clock<T: Clock>(self, clock: T) -> Blockchain<S, T> {
+        Blockchain {
+            headers: self.headers,
+            hashes: self.hashes,
+            unflushed: self.unflushed,
+            chain_size: self.chain_size,
+            accounts: self.accounts,
+            names: self.names,
+            pending_transactions: self.pending_transactions,
+            transactions_index: self.transactions_index,
+            memo_index: self.memo_index,
+            events: self.events,
+            history_from: self.history_from,
+            loaded_balances: self.loaded_balances,
+            receipts: self.receipts,
+            failed_receipts: self.failed_receipts,
+            pending_events: self.pending_events,
+            beneficiary: self.beneficiary,
+            watch_list: self.watch_list,
+            metrics: self.metrics,
+            event_bus: self.event_bus,
+            genesis: self.genesis,
+            max_supply: self.max_supply,
+            minted: self.minted,
+            params: self.params,
+            difficulty_adjuster: self.difficulty_adjuster,
+            store: self.store,
+            clock,
+        }
+    }
+}
+
+impl<S: ChainStore> Blockchain<S> {
+    /// A chain without blocks, keeping them in a store.
+    fn empty(store: S) -> Self {
+        let metrics = Arc::new(Metrics::default());
+        let event_bus = EventBus::new();
+        Blockchain {
+            headers: vec![],
+            hashes: vec![],
+            unflushed: vec![],
+            chain_size: 0,
+            accounts: HashMap::new(),
+            names: HashMap::new(),
+            pending_transactions: Mempool::with_reporting(
+                DEFAULT_MEMPOOL_SIZE,
+                metrics.clone(),
+                event_bus.clone(),
+            ),
+            transactions_index: HashMap::new(),
+            memo_index: BTreeMap::new(),
+            events: vec![],
+            history_from: 0,
+            loaded_balances: HashMap::new(),
+            receipts: vec![],
+            failed_receipts: VecDeque::new(),
+            pending_events: vec![],
+            beneficiary: None,
+            watch_list: WatchList::new(),
+            metrics,
+            event_bus,
+            genesis: None,
+            max_supply: None,
+            minted: 0,
+            params: ParamsSchedule::default(),
+            difficulty_adjuster: DifficultyAdjuster::default(),
+            store,
+            clock: SystemClock,
+        }
+    }
+
+    /**
+    Load the chain kept in a store, or start an empty chain there if it is
+    empty. The stored blocks are checked again, and the state is restored
+    from the stored snapshot rather than by applying every block. Blocks
+    stored after the snapshot, e.g. by an interrupted flush, are dropped.
+    The genesis, the scheduled parameters and the difficulty adjustment are
+    restored along with the state. Events and receipts of the stored blocks
+    are not restored, so neither is the history of balances before the last
+    stored block: `reindex` the chain to rebuild them.
+
+    ```
+    # use crate::blockchain::blockchain::Blockchain;
+    # use crate::blockchain::block::Block;
+    # use crate::blockchain::storage::MemoryStore;
+    let mut chain = Blockchain::with_store(MemoryStore::new()).unwrap();
+    let mut genesis = Block::new();
+    genesis.seal();
+    chain.add_block(genesis).unwrap();
+    chain.flush().unwrap();
+
+    let chain = Blockchain::with_store(chain.into_store()).unwrap();
+    assert_eq!(1, chain.iter_blocks(..).count());
+    ```
+    */
+    pub fn with_store(mut store: S) -> Result<Self, BlockchainError> {
+        let snapshot = store.get_state()?;
+        let height = snapshot.as_ref().map_or(0, |snapshot| snapshot.height);
+        if store.height() < height {
+            return Err(BlockchainError::Storage(
+                "fewer blocks are stored than the snapshot covers".to_string(),
+            ));
+        }
+        store.truncate(height)?;
+
+        let mut chain = Self::empty(store);
+        for index in 0..height {
+            let block = chain.store.get_block_by_height(index)?.ok_or_else(|| {
+                BlockchainError::Storage(format!("missing block at height {}", index))
+            })?;
+            let block = Self::check_block(block).map_err(|err| BlockchainError::InvalidBlock {
+                index,
+                error: Box::new(err),
+            })?;
+            chain.push_block(block, vec![], vec![]);
+            // the block is stored already
+            chain.unflushed.clear();
+        }
+        if let Some(snapshot) = snapshot {
+            chain.accounts = snapshot.accounts;
+            chain.names = snapshot.names;
+            chain.minted = snapshot.minted;
+            chain.max_supply = snapshot.max_supply;
+            chain.genesis = snapshot.genesis;
+            chain.params = snapshot.params;
+            chain.difficulty_adjuster = snapshot.difficulty_adjuster;
+            chain.history_from = chain.headers.len();
+            chain.loaded_balances = chain
+                .accounts
+                .iter()
+                .map(|(id, account)| (id.clone(), account.tokens))
+                .collect();
+        }
+        chain.verify_chain()?;
+        Ok(chain)
+    }
+
+    /**
+    Load the chain kept in a store like `with_store`, or start it there from
+    its genesis block if the store is empty. A stored chain started from
+    another genesis is rejected.
+
+    ```
+    # use crate::blockchain::blockchain::Blockchain;
+    # use crate::blockchain::genesis::GenesisConfig;
+    # use crate::blockchain::storage::MemoryStore;
+    let genesis = GenesisConfig::default().with_allocation(&[1; 32], 1000);
+    let mut chain = Blockchain::with_genesis(MemoryStore::new(), &genesis).unwrap();
+    chain.flush().unwrap();
+
+    let chain = Blockchain::with_genesis(chain.into_store(), &genesis).unwrap();
+    assert_eq!(1000, chain.total_supply());
+    ```
+    */
+    pub fn with_genesis(store: S, genesis: &GenesisConfig) -> Result<Self, BlockchainError> {
+        genesis
+            .validate()
+            .map_err(BlockchainError::InvalidGenesis)?;
+
+        let mut chain = Self::with_store(store)?;
+        if !chain.is_genesis() {
+            if chain.genesis.as_ref() != Some(genesis) {
+                return Err(BlockchainError::GenesisMismatch);
+            }
+            return Ok(chain);
+        }
+
+        chain.genesis = Some(genesis.clone());
+        chain.max_supply = genesis.max_supply;
+        chain.params = ParamsSchedule::new(genesis.params.clone());
+        let mut events = vec![];
+        for allocation in &genesis.allocations {
+            chain.add_account(allocation.account.clone())?;
+            let account = chain.get_account_by_id_mut(&allocation.account)?;
+            account.account_type = allocation.account_type;
+            account.public_key = Some(allocation.public_key.clone());
+            chain.mint(&allocation.account, allocation.tokens)?;
+            events.push(TransactionEvent::AccountCreated {
+                id: allocation.account.clone(),
+            });
+            if allocation.tokens > 0 {
+                events.push(TransactionEvent::Minted {
+                    to: allocation.account.clone(),
+                    amount: allocation.tokens,
+                });
+            }
+        }
+
+        chain.push_block(genesis.block(), events, vec![]);
+        Ok(chain)
+    }
+}
+
+impl Blockchain {
+    /// Constructor
+    pub fn new() -> Self {
+        Self::empty(MemoryStore::new())
+    }
+
+    /// Start a chain from its genesis block, creating the allocated
+    /// accounts and their tokens.
+    pub fn from_genesis(genesis: &GenesisConfig) -> Result<Self, BlockchainError> {
+        Self::with_genesis(MemoryStore::new(), genesis)
+    }
+}
+
+impl Blockchain<FileStore> {
+    /**
+    Open the chain stored in a directory, or start an empty chain there if
+    there is none.
+
+    ```
+    # use crate::blockchain::blockchain::Blockchain;
+    # use crate::blockchain::block::Block;
+    # use crate::blockchain::testing::TempDir;
+    let dir = TempDir::new("doc-open");
+    let mut chain = Blockchain::open(dir.path()).unwrap();
+
+    let mut genesis = Block::new();
+    genesis.seal();
+    chain.add_block(genesis).unwrap();
+    chain.flush().unwrap();
+
+    let chain = Blockchain::open(dir.path()).unwrap();
+    assert_eq!(1, chain.iter_blocks(..).count());
+    ```
+    */
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, BlockchainError> {
+        Self::with_store(FileStore::open(path.as_ref())?)
+    }
+}
+
+impl Default for Blockchain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: ChainStore, C: Clock> WorldState for Blockchain<S, C> {
+    fn get_account_by_id(&self, id: &Id) -> Result<&Account, BlockchainError> {
+        self.accounts
+            .get(id)
+            .ok_or_else(|| BlockchainError::AccountNotFound(id.clone()))
+    }
+
+    fn get_account_by_id_mut(&mut self, id: &Id) -> Result<&mut Account, BlockchainError> {
+        self.accounts
+            .get_mut(id)
+            .ok_or_else(|| BlockchainError::AccountNotFound(id.clone()))
+    }
+
+    fn add_account(&mut self, id: Id) -> Result<(), BlockchainError> {
+        match self.accounts.entry(id) {
+            std::collections::hash_map::Entry::Vacant(accounts) => {
+                accounts.insert(Account::new());
+                Ok(())
+            }
+            std::collections::hash_map::Entry::Occupied(account) => {
+                Err(BlockchainError::AccountAlreadyExists(account.key().clone()))
+            }
+        }
+    }
+
+    fn height(&self) -> usize {
+        self.headers.len()
+    }
+
+    fn get_name(&self, name: &str) -> Option<&NameRecord> {
+        self.names.get(name)
+    }
+
+    fn set_name(&mut self, name: String, record: NameRecord) {
+        self.names.insert(name, record);
+    }
+
+    fn emit(&mut self, event: TransactionEvent) {
+        self.pending_events.push(event);
+    }
+
+    fn params(&self) -> ChainParams {
+        // rules of the block being applied
+        self.params.at(self.headers.len()).clone()
+    }
+
+    fn schedule_params(&mut self, height: usize, params: ChainParams) {
+        self.params.schedule(height, params);
+    }
+
+    fn collect_fee(&mut self, amount: Amount) -> Result<(), BlockchainError> {
+        let beneficiary = self
+            .beneficiary
+            .clone()
+            .ok_or(BlockchainError::MissingBeneficiary)?;
+        let account = self.get_account_by_id_mut(&beneficiary)?;
+        account.tokens = account
+            .tokens
+            .checked_add(amount)
+            .ok_or(BlockchainError::Overflow)?;
+        self.emit(TransactionEvent::FeesCollected {
+            to: beneficiary,
+            amount,
+        });
+        Ok(())
+    }
+}
+
+#[test]
+fn test_add_block() {
+    use crate::transaction::TransactionRecord;
+    use std::time::SystemTime;
+
+    let mut chain = Blockchain::new();
+    let mut block = Block::new();
+
+    block.transactions.push(Transaction {
+        nonce: 0,
+        from_account_id: Some("hello".into()),
+        record: TransactionRecord::create_user_account(b"world"),
+        signature: Some(crate::transaction::Signature::from_bytes(&[0; 64])),
+        created_at: SystemTime::now(),
+        memo: None,
+        fee: 0,
+    });
+    block.seal();
+
+    assert_eq!(Ok(()), chain.add_block(block))
+}
+
+#[test]
+fn test_from_genesis() {
+    use crate::testing::{account_id, keypair};
+
+    let someone = keypair("someone").verifying_key().to_bytes();
+    let genesis = GenesisConfig::default()
+        .with_allocation(&someone, 300)
+        .with_allocation(keypair("someone else").verifying_key().as_bytes(), 0);
+    let chain = Blockchain::from_genesis(&genesis).unwrap();
+
+    assert_eq!(1, chain.iter_blocks(..).count());
+    assert_eq!(genesis.block().hash.as_ref(), chain.get_last_block_hash());
+    assert_eq!(300, chain.total_supply());
+    assert_eq!(
+        0,
+        chain
+            .get_account_by_id(&account_id("someone else"))
+            .unwrap()
+            .tokens
+    );
+    assert_eq!(
+        Some(someone.to_vec()),
+        chain
+            .get_account_by_id(&account_id("someone"))
+            .unwrap()
+            .public_key
+    );
+    assert_eq!(
+        Some(
+            &[
+                TransactionEvent::AccountCreated {
+                    id: account_id("someone")
+                },
+                TransactionEvent::Minted {
+                    to: account_id("someone"),
+                    amount: 300
+                },
+                TransactionEvent::AccountCreated {
+                    id: account_id("someone else")
+                },
+            ][..]
+        ),
+        chain.get_block_events(0)
+    );
+
+    let invalid = genesis.with_allocation(&someone, 1);
+    assert!(Blockchain::from_genesis(&invalid).is_err());
+}
+
+#[test]
+fn test_max_supply() {
+    let genesis = GenesisConfig {
+        max_supply: Some(1000),
+        ..GenesisConfig::default()
+    }
+    .with_allocation(b"someone", 600);
+    let mut chain = Blockchain::from_genesis(&genesis).unwrap();
+    assert_eq!(Some(1000), chain.max_supply());
+    assert_eq!(600, chain.minted_supply());
+
+    chain.add_account("someone else".into()).unwrap();
+    assert_eq!(Ok(()), chain.mint(&"someone else".into(), 400));
+    assert_eq!(
+        Err(BlockchainError::MaxSupplyExceeded),
+        chain.mint(&"someone else".into(), 1)
+    );
+    assert_eq!(1000, chain.total_supply());
+    assert_eq!(Ok(()), chain.verify_chain());
+}
+
+#[test]
+fn test_schedule_params() {
+    use crate::account::AccountType;
+    use crate::testing::{account_id, keypair};
+    use crate::transaction::{TransactionEvent, TransactionRecord};
+
+    let mut genesis = GenesisConfig::default()
+        .with_allocation(keypair("authority").verifying_key().as_bytes(), 0)
+        .with_allocation(keypair("someone").verifying_key().as_bytes(), 100)
+        .with_allocation(keypair("someone else").verifying_key().as_bytes(), 0);
+    genesis.allocations[0].account_type = AccountType::System;
+    let mut chain = Blockchain::from_genesis(&genesis).unwrap();
+    let stricter = ChainParams {
+        min_transfer: 10,
+        ..ChainParams::default()
+    };
+
+    let signed = |name: &str, nonce, record| {
+        let mut transaction = Transaction::new(nonce, record, Some(account_id(name)));
+        transaction.sign(&keypair(name));
+        transaction
+    };
+    let schedule = |from: &str, height| {
+        signed(
+            from,
+            1,
+            TransactionRecord::ScheduleParams {
+                height,
+                params: stricter.clone(),
+            },
+        )
+    };
+    let transfer = |nonce, amount| {
+        signed(
+            "someone",
+            nonce,
+            TransactionRecord::SendTokens {
+                to: account_id("someone else"),
+                amount,
+            },
+        )
+    };
+    let add_block = |chain: &mut Blockchain, transactions: Vec<Transaction>| {
+        let mut block = Block::new();
+        block.header.height = chain.height();
+        block.header.previous_hash = chain.get_last_block_hash().cloned();
+        block.transactions = transactions;
+        block.seal();
+        chain.add_block(block)
+    };
+    let invalid = |index, error| {
+        Err(BlockchainError::InvalidTransaction {
+            index,
+            error: Box::new(error),
+        })
+    };
+
+    // only the authority can change the rules, and only for later blocks
+    assert_eq!(
+        invalid(0, BlockchainError::NotAuthority),
+        add_block(&mut chain, vec![schedule("someone", 2)])
+    );
+    assert_eq!(
+        invalid(0, BlockchainError::ActivationHeightInPast),
+        add_block(&mut chain, vec![schedule("authority", 1)])
+    );
+    // nor does a rejected block
+    assert_eq!(
+        invalid(
+            1,
+            BlockchainError::InvalidNonce {
+                expected: 1,
+                got: 5
+            }
+        ),
+        add_block(&mut chain, vec![schedule("authority", 2), transfer(5, 5)])
+    );
+    assert_eq!(&ChainParams::default(), chain.params_at(2));
+
+    // height 1: any amount can be sent
+    assert_eq!(
+        Ok(()),
+        add_block(&mut chain, vec![schedule("authority", 2), transfer(1, 5)])
+    );
+    assert_eq!(&ChainParams::default(), chain.params_at(1));
+    assert_eq!(&stricter, chain.params_at(2));
+    assert_eq!(
+        TransactionEvent::ParamsScheduled {
+            height: 2,
+            params: stricter.clone()
+        },
+        chain.events[1][0]
+    );
+    // height 2: the new rules are in effect
+    assert_eq!(
+        invalid(0, BlockchainError::BelowMinimumTransfer),
+        add_block(&mut chain, vec![transfer(2, 5)])
+    );
+    assert_eq!(Ok(()), add_block(&mut chain, vec![transfer(2, 10)]));
+
+    // the rules come from the blocks
+    assert_eq!(Ok(()), chain.reindex());
+    assert_eq!(&stricter, chain.params_at(2));
+}
+
+#[test]
+fn test_resolve_name() {
+    use crate::testing::{account_id, keypair};
+    use crate::transaction::TransactionRecord;
+
+    let mut chain = crate::testing::ChainBuilder::new()
+        .with_account("someone", 0)
+        .build()
+        .unwrap();
+    assert_eq!(None, chain.resolve_name("someone"));
+
+    let mut block = Block::new();
+    block.header.height = chain.height();
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
+    let mut transaction = Transaction::new(
+        1,
+        TransactionRecord::RegisterName {
+            name: "someone".to_string(),
+        },
+        Some(account_id("someone")),
+    );
+    transaction.sign(&keypair("someone"));
+    block.transactions.push(transaction);
+    block.seal();
+    chain.add_block(block).unwrap();
+
+    assert_eq!(Some(&account_id("someone")), chain.resolve_name("someone"));
+}
+
+#[test]
+fn test_verify_chain() {
+    use crate::testing::{account_id, ChainBuilder};
+
+    let mut chain = ChainBuilder::new()
+        .with_account("someone", 100)
+        .with_account("someone else", 0)
+        .block(|b| b.transfer("someone", "someone else", 30))
+        .build()
+        .unwrap();
+    assert_eq!(Ok(()), chain.verify_chain());
+
+    chain
+        .get_account_by_id_mut(&account_id("someone else"))
+        .unwrap()
+        .tokens += 1;
+    assert_eq!(
+        Err(BlockchainError::SupplyMismatch {
+            held: 101,
+            minted: 100
+        }),
+        chain.verify_chain()
+    );
+}
+
+#[test]
+fn test_blocks_involving() {
+    use crate::transaction::TransactionRecord;
+
+    let mut chain = Blockchain::new();
+
+    let mut genesis = Block::new();
+    genesis.transactions.push(Transaction::new(
+        0,
+        TransactionRecord::create_user_account(b"someone"),
+        None,
+    ));
+    genesis.seal();
+    chain.add_block(genesis).unwrap();
+
+    let mut block = Block::new();
+    block.header.height = chain.height();
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
+    block.transactions.push(Transaction::new(
+        0,
+        TransactionRecord::create_user_account(b"someone else"),
+        None,
+    ));
+    block.seal();
+    chain.add_block(block).unwrap();
+
+    assert_eq!(
+        1,
+        chain
+            .blocks_involving(&Id::from_public_key(b"someone"))
+            .count()
+    );
+    assert_eq!(
+        1,
+        chain
+            .blocks_involving(&Id::from_public_key(b"someone else"))
+            .count()
+    );
+    assert_eq!(
+        0,
+        chain
+            .blocks_involving(&Id::from_public_key(b"nobody"))
+            .count()
+    );
+}
+
+#[test]
+fn test_add_blocks() {
+    use crate::transaction::TransactionRecord;
+
+    let mut blocks = vec![];
+    let mut previous_hash = None;
+    for (height, name) in ["someone", "someone else", "another one"]
+        .iter()
+        .enumerate()
+    {
+        let mut block = Block::new();
+        block.header.height = height;
+        block.header.previous_hash = previous_hash;
+        block.transactions.push(Transaction::new(
+            0,
+            TransactionRecord::create_user_account(name.as_bytes()),
+            None,
+        ));
+        block.seal();
+        previous_hash = block.hash.clone();
+        blocks.push(block);
+    }
+
+    let mut chain = Blockchain::new();
+    assert_eq!(Ok(()), chain.add_blocks(blocks));
+    assert_eq!(3, chain.height());
+    assert!(chain
+        .get_account_by_id(&Id::from_public_key(b"another one"))
+        .is_ok());
+}
+
+#[test]
+fn test_add_blocks_stops_at_invalid_block() {
+    let mut first = Block::new();
+    first.seal();
+
+    let mut second = Block::new();
+    second.header.height = 1;
+    second.header.previous_hash = first.hash.clone();
+    second.hash = Some(vec![]);
+
+    let mut third = Block::new();
+    third.header.height = 1;
+    third.header.previous_hash = first.hash.clone();
+    third.seal();
+
+    let mut chain = Blockchain::new();
+    assert_eq!(
+        Err(BlockchainError::InvalidBlock {
+            index: 1,
+            error: Box::new(BlockchainError::InvalidHash)
+        }),
+        chain.add_blocks(vec![first, second, third])
+    );
+    assert_eq!(1, chain.height());
+}
+
+#[test]
+fn test_check_blocks_keeps_order() {
+    let blocks: Vec<Block> = (0..10)
+        .map(|i| {
+            let mut block = Block::new();
+            block.hash = Some(if i == 7 {
+                vec![]
+            } else {
+                block.calculate_hash()
+            });
+            block
+        })
+        .collect();
+    let hashes: Vec<Option<Hash>> = blocks.iter().map(|block| block.hash.clone()).collect();
+
+    let checked = <Blockchain>::check_blocks(blocks, 3);
+    assert_eq!(10, checked.len());
+    for (i, (block, hash)) in checked.into_iter().zip(hashes).enumerate() {
+        match block {
+            Ok(block) => {
+                assert_ne!(7, i);
+                assert_eq!(hash, block.hash);
+            }
+            Err(err) => {
+                assert_eq!(7, i);
+                assert_eq!(BlockchainError::InvalidHash, err);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_get_transaction() {
+    use crate::transaction::TransactionRecord;
+
+    let mut chain = Blockchain::new();
+    let mut block = Block::new();
+    block.transactions.push(Transaction::new(
+        0,
+        TransactionRecord::create_user_account(b"someone"),
+        None,
+    ));
+    block.transactions.push(Transaction::new(
+        0,
+        TransactionRecord::create_user_account(b"someone else"),
+        None,
+    ));
+    block.seal();
+    let block_hash = block.hash.clone().unwrap();
+    let transaction_hash = block.transactions[1].calculate_hash();
+    chain.add_block(block).unwrap();
+
+    let (transaction, location) = chain.get_transaction(&transaction_hash).unwrap().unwrap();
+    assert_eq!(transaction_hash, transaction.calculate_hash());
+    assert_eq!(
+        &TransactionLocation {
+            block_hash,
+            block_height: 0,
+            index: 1,
+        },
+        location
+    );
+
+    assert!(chain.get_transaction(&[0; 32]).unwrap().is_none());
+}
+
+#[test]
+fn test_iter_blocks() {
+    let mut blocks = vec![];
+    let mut previous_hash = None;
+    for height in 0..4 {
+        let mut block = Block::new();
+        block.header.height = height;
+        block.header.previous_hash = previous_hash;
+        block.seal();
+        previous_hash = block.hash.clone();
+        blocks.push(block);
+    }
+
+    let mut chain = Blockchain::new();
+    chain.add_blocks(blocks).unwrap();
+
+    assert_eq!(4, chain.iter_blocks(..).count());
+    assert_eq!(2, chain.iter_blocks(1..3).count());
+    assert_eq!(3, chain.iter_blocks(1..=10).count());
+    assert_eq!(0, chain.iter_blocks(5..).count());
+    assert_eq!(
+        chain.get_last_block_hash(),
+        chain
+            .iter_blocks_from_tip()
+            .next()
+            .unwrap()
+            .unwrap()
+            .hash
+            .as_ref()
+    );
+    assert_eq!(
+        chain.get_last_block_hash(),
+        chain
+            .iter_blocks(2..)
+            .next_back()
+            .unwrap()
+            .unwrap()
+            .hash
+            .as_ref()
+    );
+}
+
+#[test]
+fn test_supply_and_top_accounts() {
+    let mut chain = Blockchain::new();
+    for (id, tokens) in &[("someone", 50), ("someone else", 300), ("another one", 50)] {
+        chain.add_account((*id).into()).unwrap();
+        chain.get_account_by_id_mut(&(*id).into()).unwrap().tokens = *tokens;
+    }
+
+    assert_eq!(400, chain.total_supply());
+    assert_eq!(400, chain.circulating_supply());
+
+    let top: Vec<(String, Amount)> = chain
+        .top_accounts(2)
+        .into_iter()
+        .map(|(id, account)| (id.to_string(), account.tokens))
+        .collect();
+    assert_eq!(
+        vec![
+            ("someone else".to_string(), 300),
+            ("another one".to_string(), 50)
+        ],
+        top
+    );
+}
+
+#[test]
+fn test_block_events() {
+    use crate::transaction::TransactionRecord;
+
+    let mut chain = Blockchain::new();
+    let mut genesis = Block::new();
+    genesis.seal();
+    chain.add_block(genesis).unwrap();
+
+    let mut block = Block::new();
+    block.header.height = chain.height();
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
+    for id in &["someone", "someone else"] {
+        block.transactions.push(Transaction::new(
+            0,
+            TransactionRecord::create_user_account(id.as_bytes()),
+            None,
+        ));
+    }
+    block.seal();
+    chain.add_block(block).unwrap();
+
+    assert_eq!(Some(&[][..]), chain.get_block_events(0));
+    assert_eq!(
+        Some(
+            &[
+                TransactionEvent::AccountCreated {
+                    id: Id::from_public_key(b"someone")
+                },
+                TransactionEvent::AccountCreated {
+                    id: Id::from_public_key(b"someone else")
+                },
+            ][..]
+        ),
+        chain.get_block_events(1)
+    );
+    assert_eq!(None, chain.get_block_events(2));
+
+    let filter = EventFilter {
+        account: Some(Id::from_public_key(b"someone else")),
+        topic: Some("account_created".to_string()),
+    };
+    assert_eq!(1, chain.find_events(&filter).count());
+
+    let filter = EventFilter {
+        account: None,
+        topic: Some("transfer".to_string()),
+    };
+    assert_eq!(0, chain.find_events(&filter).count());
+}
+
+#[test]
+fn test_list_blocks() {
+    let mut blocks = vec![];
+    let mut previous_hash = None;
+    for height in 0..3 {
+        let mut block = Block::new();
+        block.header.height = height;
+        block.header.previous_hash = previous_hash;
+        block.seal();
+        previous_hash = block.hash.clone();
+        blocks.push(block);
+    }
+
+    let mut chain = Blockchain::new();
+    chain.add_blocks(blocks).unwrap();
+
+    let page = chain.list_blocks(None, 2).unwrap();
+    assert_eq!(2, page.items.len());
+    assert_eq!(Some(2), page.next);
+
+    let page = chain.list_blocks(page.next, 2).unwrap();
+    assert_eq!(1, page.items.len());
+    assert_eq!(None, page.next);
+}
+
+#[test]
+fn test_account_history() {
+    use crate::testing::{account_id, ChainBuilder};
+
+    let chain = ChainBuilder::new()
+        .with_account("someone", 10)
+        .with_account("someone else", 0)
+        .block(|b| {
+            b.transfer("someone", "someone else", 0)
+                .transfer("someone else", "someone else", 0)
+                .transfer("someone", "someone else", 0)
+        })
+        .build()
+        .unwrap();
+
+    let someone = account_id("someone");
+    let page = chain.account_history(&someone, None, 1).unwrap();
+    assert_eq!(1, page.items.len());
+    assert_eq!(Some((1, 2)), page.next);
+
+    let page = chain.account_history(&someone, page.next, 1).unwrap();
+    assert_eq!(1, page.items.len());
+    assert_eq!(1, page.items[0].0);
+    assert_eq!(None, page.next);
+}
+
+#[test]
+fn test_watched_accounts_notifications() {
+    use crate::testing::{account_id, keypair, ChainBuilder};
+    use crate::transaction::TransactionRecord;
+
+    let mut chain = ChainBuilder::new()
+        .with_account("sender", 100)
+        .with_account("receiver", 0)
+        .build()
+        .unwrap();
+    chain.watch_list_mut().watch(account_id("receiver"));
+    let notifications = chain.watch_list_mut().subscribe();
+
+    let mut block = Block::new();
+    block.header.height = chain.height();
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
+    let mut transaction = Transaction::new(
+        1,
+        TransactionRecord::SendTokens {
+            to: account_id("receiver"),
+            amount: 30,
+        },
+        Some(account_id("sender")),
+    );
+    transaction.sign(&keypair("sender"));
+    block.transactions.push(transaction);
+    block.seal();
+    let transaction_hash = block.transactions[0].calculate_hash();
+    chain.add_block(block).unwrap();
+
+    let notification = notifications.try_recv().unwrap();
+    assert_eq!(
+        crate::watch::WatchNotification {
+            account: account_id("receiver"),
+            block_height: 1,
+            transaction_hash,
+            balance_delta: 30,
+        },
+        notification
+    );
+    assert!(notifications.try_recv().is_err());
+}
+
+#[test]
+fn test_stats() {
+    use crate::clock::MockClock;
+    use crate::transaction::TransactionRecord;
+
+    let mut chain = Blockchain::new();
+    assert_eq!(
+        Ok(ChainStats {
+            blocks: 0,
+            average_block_time: None,
+            transactions_per_block: 0.0,
+            active_accounts: 0,
+            chain_size: 0,
+            total_fees: 0,
+            average_fee: 0.0,
+        }),
+        chain.stats(10)
+    );
+
+    let clock = MockClock::new(std::time::UNIX_EPOCH);
+    let mut blocks = vec![];
+    let mut previous_hash = None;
+    for (height, id) in ["someone", "someone else", "another one"]
+        .iter()
+        .enumerate()
+    {
+        let mut block = Block::with_clock(&clock);
+        clock.advance(Duration::from_secs(10));
+        block.header.height = height;
+        block.header.previous_hash = previous_hash;
+        block.transactions.push(Transaction::new(
+            0,
+            TransactionRecord::create_user_account(id.as_bytes()),
+            None,
+        ));
+        block.seal();
+        previous_hash = block.hash.clone();
+        blocks.push(block);
+    }
+    chain.add_blocks(blocks).unwrap();
+
+    let stats = chain.stats(2).unwrap();
+    assert_eq!(2, stats.blocks);
+    assert_eq!(Some(Duration::from_secs(10)), stats.average_block_time);
+    assert_eq!(1.0, stats.transactions_per_block);
+    assert_eq!(2, stats.active_accounts);
+    assert_eq!(
+        chain
+            .iter_blocks(..)
+            .map(|block| block.unwrap().size())
+            .sum::<usize>(),
+        stats.chain_size
+    );
+}
+
+#[test]
+fn test_metrics() {
+    use crate::transaction::TransactionRecord;
+
+    let mut chain = Blockchain::new();
+    let mut genesis = Block::new();
+    genesis.seal();
+    chain.add_block(genesis).unwrap();
+
+    let mut block = Block::new();
+    block.header.height = chain.height();
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
+    block.transactions.push(Transaction::new(
+        0,
+        TransactionRecord::create_user_account(b"someone"),
+        None,
+    ));
+    chain.mine_block(&mut block);
+    let hashes = block.header.nonce + 1;
+    chain.add_block(block).unwrap();
+    chain.add_block(Block::new()).unwrap_err();
+
+    let metrics = chain.metrics();
+    assert_eq!(hashes, metrics.mining_hashes.load(Ordering::Relaxed));
+    assert_eq!(2, metrics.blocks_applied.load(Ordering::Relaxed));
+    assert_eq!(1, metrics.blocks_rejected.load(Ordering::Relaxed));
+    assert_eq!(1, metrics.transactions_applied.load(Ordering::Relaxed));
+    assert_eq!(1, metrics.transaction_validation.count());
+}
+
+#[test]
+fn test_subscribe_to_chain_events() {
+    use crate::transaction::TransactionRecord;
+
+    let mut chain = Blockchain::new();
+    let events = chain.subscribe();
+
+    let transaction = Transaction::new(0, TransactionRecord::create_user_account(b"someone"), None);
+    let transaction_hash = transaction.calculate_hash();
+    let mut block = Block::new();
+    block.transactions.push(transaction);
+    block.seal();
+    let block_hash = block.hash.clone().unwrap();
+    chain.add_block(block).unwrap();
+
+    let pending = Transaction::new(
+        0,
+        TransactionRecord::create_user_account(b"someone else"),
+        None,
+    );
+    let pending_hash = pending.calculate_hash();
+    chain.mempool_mut().add(pending).unwrap();
+
+    assert_eq!(
+        vec![
+            ChainEvent::TxIncluded {
+                hash: transaction_hash,
+                block_height: 0
+            },
+            ChainEvent::BlockAdded {
+                height: 0,
+                hash: block_hash
+            },
+            ChainEvent::TxAdded { hash: pending_hash },
+        ],
+        events.try_iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_cannot_create_duplicate_accounts() {
+    let mut chain = Blockchain::new();
+
+    chain.add_account("someone".into()).unwrap();
+    assert_eq!(
+        Err(BlockchainError::AccountAlreadyExists("someone".into())),
+        chain.add_account("someone".into())
+    )
+}
+
+#[test]
+fn test_to_dot() {
+    use crate::testing::{account_id, keypair, ChainBuilder};
+    use crate::transaction::TransactionRecord;
+
+    let mut chain = ChainBuilder::new()
+        .with_account("alice", 100)
+        .with_account("bob", 0)
+        .block(|b| b.transfer("alice", "bob", 10))
+        .block(|b| b.transfer("alice", "bob", 5).transfer("bob", "alice", 1))
+        .build()
+        .unwrap();
+
+    let dot = chain.to_dot().unwrap();
+    assert!(dot.starts_with("digraph chain {"));
+    assert!(dot.contains("2 [label=\"#2\\n"));
+    assert!(dot.contains("\\n2 transactions\"]"));
+    assert!(dot.contains("2 -> 1;\n"));
+    assert!(!dot.contains("0 -> "));
+
+    // IDs needing quotes are escaped
+    let builder: Id = "bob \\ \"the builder\"".into();
+    chain.add_account(builder.clone()).unwrap();
+    let mut block = Block::new();
+    block.header.height = chain.height();
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
+    let mut transaction = Transaction::new(
+        3,
+        TransactionRecord::SendTokens {
+            to: builder,
+            amount: 2,
+        },
+        Some(account_id("alice")),
+    );
+    transaction.sign(&keypair("alice"));
+    block.transactions.push(transaction);
+    block.seal();
+    chain.add_block(block).unwrap();
+
+    let (alice, bob) = (account_id("alice"), account_id("bob"));
+    let mut flows = [
+        format!("    \"{}\" -> \"{}\" [label=\"15\"];\n", alice, bob),
+        format!(
+            "    \"{}\" -> \"bob \\\\ \\\"the builder\\\"\" [label=\"2\"];\n",
+            alice
+        ),
+        format!("    \"{}\" -> \"{}\" [label=\"1\"];\n", bob, alice),
+    ];
+    flows.sort();
+    assert_eq!(
+        Ok(format!("digraph flows {{\n{}}}\n", flows.concat())),
+        chain.flows_to_dot(..)
+    );
+    assert_eq!(
+        Ok("digraph flows {\n}\n".to_string()),
+        chain.flows_to_dot(..1)
+    );
+}
+
+#[test]
+fn test_transactions_with_memo() {
+    use crate::testing::{account_id, keypair, ChainBuilder};
+
+    let mut chain = ChainBuilder::new()
+        .with_account("alice", 100)
+        .with_account("exchange", 0)
+        .build()
+        .unwrap();
+
+    let mut block = Block::new();
+    block.header.height = chain.height();
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
+    for (nonce, memo) in [
+        (1, Some("deposit-42")),
+        (2, None),
+        (3, Some("deposit-7")),
+        (4, Some("deposit-42")),
+    ] {
+        let transfer = Transaction::new(
+            nonce,
+            TransactionRecord::SendTokens {
+                to: account_id("exchange"),
+                amount: nonce,
+            },
+            Some(account_id("alice")),
+        );
+        let mut transfer = match memo {
+            Some(memo) => transfer.with_memo(memo),
+            None => transfer,
+        };
+        transfer.sign(&keypair("alice"));
+        block.transactions.push(transfer);
+    }
+    block.seal();
+    chain.add_block(block).unwrap();
+
+    let nonces =
+        |transactions: Result<Vec<(Transaction, &TransactionLocation)>, _>| -> Vec<crate::Nonce> {
+            transactions
+                .unwrap()
+                .into_iter()
+                .map(|(t, _)| t.nonce)
+                .collect()
+        };
+    assert_eq!(
+        vec![1, 4],
+        nonces(chain.transactions_with_memo("deposit-42").collect())
+    );
+    assert!(chain.transactions_with_memo("deposit").next().is_none());
+    assert_eq!(
+        vec![1, 4, 3],
+        nonces(chain.transactions_with_memo_prefix("deposit-").collect())
+    );
+    assert!(chain
+        .transactions_with_memo_prefix("withdrawal")
+        .next()
+        .is_none());
+
+    let memo = "m".repeat(crate::transaction::MAX_MEMO_LENGTH + 1);
+    let transfer = Transaction::new(
+        5,
+        TransactionRecord::SendTokens {
+            to: account_id("exchange"),
+            amount: 1,
+        },
+        Some(account_id("alice")),
+    )
+    .with_memo(&memo);
+    assert_eq!(
+        Err(BlockchainError::MemoTooLong),
+        transfer.apply(&mut chain)
+    );
+}
+
+#[test]
+fn test_subscribe_to_confirmations() {
+    let mut chain = crate::testing::ChainBuilder::new().build().unwrap();
+    let events = chain.subscribe();
+
+    for _ in 0..FINALIZED_DEPTH {
+        let mut block = Block::new();
+        block.header.height = chain.height();
+        block.header.previous_hash = chain.get_last_block_hash().cloned();
+        block.seal();
+        chain.add_block(block).unwrap();
+    }
+
+    let confirmations: Vec<ChainEvent> = events
+        .try_iter()
+        .filter(|event| !matches!(event, ChainEvent::BlockAdded { .. }))
+        .collect();
+    let hash = |height: usize| chain.hashes[height].clone();
+
+    assert_eq!(FINALIZED_DEPTH - SAFE_DEPTH + 2, confirmations.len());
+    assert_eq!(
+        ChainEvent::BlockSafe {
+            height: 0,
+            hash: hash(0)
+        },
+        confirmations[0]
+    );
+    assert_eq!(
+        Some(&ChainEvent::BlockFinalized {
+            height: 0,
+            hash: hash(0)
+        }),
+        confirmations.last()
+    );
+}
+
+#[test]
+fn test_simulate() {
+    use crate::testing::{account_id, keypair};
+    use crate::transaction::TransactionRecord;
+
+    let chain = crate::testing::ChainBuilder::new()
+        .with_account("alice", 100)
+        .with_account("bob", 0)
+        .with_account("miner", 0)
+        .build()
+        .unwrap();
+    let transfer = |amount, fee| {
+        let mut transaction = Transaction::new(
+            1,
+            TransactionRecord::SendTokens {
+                to: account_id("bob"),
+                amount,
+            },
+            Some(account_id("alice")),
+        )
+        .with_fee(fee);
+        transaction.sign(&keypair("alice"));
+        transaction
+    };
+    let miner = account_id("miner");
+
+    assert_eq!(
+        Ok(vec![
+            TransactionEvent::Transfer {
+                from: account_id("alice"),
+                to: account_id("bob"),
+                amount: 40
+            },
+            TransactionEvent::FeePaid {
+                from: account_id("alice"),
+                amount: 2
+            },
+            TransactionEvent::FeesCollected {
+                to: miner.clone(),
+                amount: 2
+            }
+        ]),
+        chain.simulate(&transfer(40, 2), &miner)
+    );
+    assert_eq!(
+        Err(BlockchainError::InsufficientFunds {
+            available: 100,
+            required: 101
+        }),
+        chain.simulate(&transfer(100, 1), &miner)
+    );
+    assert_eq!(
+        Err(BlockchainError::AccountNotFound("nobody".into())),
+        chain.simulate(&transfer(40, 2), &"nobody".into())
+    );
+    assert_eq!(
+        100,
+        chain
+            .get_account_by_id(&account_id("alice"))
+            .unwrap()
+            .tokens
+    );
+    assert_eq!(
+        0,
+        chain.get_account_by_id(&account_id("bob")).unwrap().tokens
+    );
+}
+
+#[test]
+fn test_balance_at() {
+    use crate::testing::account_id;
+
+    let carol = Id::from_public_key(b"carol");
+    let chain = crate::testing::ChainBuilder::new()
+        .with_account("alice", 100)
+        .with_account("bob", 0)
+        .block(|b| b.transfer("alice", "bob", 30))
+        .block(|b| b.create_account(b"carol").transfer_to("bob", &carol, 5))
+        .block(|b| b.transfer("alice", "alice", 10))
+        .build()
+        .unwrap();
+
+    let alice = account_id("alice");
+    let bob = account_id("bob");
+    assert_eq!(Some(100), chain.balance_at(&alice, 0));
+    assert_eq!(Some(70), chain.balance_at(&alice, 1));
+    assert_eq!(Some(70), chain.balance_at(&alice, 3));
+    assert_eq!(Some(0), chain.balance_at(&bob, 0));
+    assert_eq!(Some(30), chain.balance_at(&bob, 1));
+    assert_eq!(Some(25), chain.balance_at(&bob, 2));
+    assert_eq!(None, chain.balance_at(&carol, 1));
+    assert_eq!(Some(5), chain.balance_at(&carol, 2));
+    assert_eq!(None, chain.balance_at(&alice, 4));
+
+    assert_eq!(
+        chain.get_account_by_id(&bob).unwrap().tokens,
+        chain.balance_at(&bob, 3).unwrap()
+    );
+}
+
+#[test]
+fn test_balance_at_after_reload() {
+    use crate::testing::{account_id, keypair};
+    use crate::transaction::TransactionRecord;
+
+    let genesis = GenesisConfig::default()
+        .with_allocation(keypair("alice").verifying_key().as_bytes(), 100)
+        .with_allocation(keypair("bob").verifying_key().as_bytes(), 0);
+    let add_transfer = |chain: &mut Blockchain, nonce, amount| {
+        let mut block = Block::new();
+        block.header.height = chain.height();
+        block.header.previous_hash = chain.get_last_block_hash().cloned();
+        let mut transaction = Transaction::new(
+            nonce,
+            TransactionRecord::SendTokens {
+                to: account_id("bob"),
+                amount,
+            },
+            Some(account_id("alice")),
+        );
+        transaction.sign(&keypair("alice"));
+        block.transactions.push(transaction);
+        block.seal();
+        chain.add_block(block).unwrap();
+    };
+
+    let mut chain = Blockchain::from_genesis(&genesis).unwrap();
+    add_transfer(&mut chain, 1, 10);
+    add_transfer(&mut chain, 2, 20);
+    chain.flush().unwrap();
+
+    let mut chain = Blockchain::with_genesis(chain.into_store(), &genesis).unwrap();
+    let alice = account_id("alice");
+    // the history of the loaded blocks is unknown, but for their end state
+    assert_eq!(None, chain.balance_at(&alice, 0));
+    assert_eq!(None, chain.balance_at(&alice, 1));
+    assert_eq!(Some(70), chain.balance_at(&alice, 2));
+    assert_eq!(None, chain.balance_at(&account_id("carol"), 2));
+
+    add_transfer(&mut chain, 3, 5);
+    assert_eq!(Some(65), chain.balance_at(&alice, 3));
+    assert_eq!(Some(35), chain.balance_at(&account_id("bob"), 3));
+    assert_eq!(None, chain.balance_at(&alice, 4));
+    assert_eq!(None, chain.balance_at(&alice, usize::MAX));
+
+    chain.reindex().unwrap();
+    assert_eq!(Some(100), chain.balance_at(&alice, 0));
+    assert_eq!(Some(90), chain.balance_at(&alice, 1));
+    assert_eq!(Some(65), chain.balance_at(&alice, 3));
+}
+
+#[test]
+fn test_get_receipt() {
+    use crate::testing::account_id;
+
+    let chain = crate::testing::ChainBuilder::new()
+        .with_account("alice", 100)
+        .with_account("bob", 0)
+        .block(|b| b.transfer("alice", "bob", 30).transfer("alice", "alice", 5))
+        .build()
+        .unwrap();
+
+    let block = chain.iter_blocks(1..).next().unwrap().unwrap();
+    let receipt = chain
+        .get_receipt(&block.transactions[0].calculate_hash())
+        .unwrap();
+    assert_eq!(
+        &Receipt {
+            error: None,
+            events: vec![TransactionEvent::Transfer {
+                from: account_id("alice"),
+                to: account_id("bob"),
+                amount: 30
+            }],
+            balances: vec![(account_id("alice"), 70), (account_id("bob"), 30)],
+        },
+        receipt
+    );
+
+    let receipt = chain
+        .get_receipt(&block.transactions[1].calculate_hash())
+        .unwrap();
+    assert_eq!(vec![(account_id("alice"), 70)], receipt.balances);
+    assert_eq!(None, chain.get_receipt(b"unknown"));
+}
+
+#[test]
+fn test_reindex() {
+    use crate::testing::account_id;
+
+    let mut chain = crate::testing::ChainBuilder::new()
+        .with_account("alice", 100)
+        .with_account("bob", 0)
+        .block(|b| b.transfer("alice", "bob", 30))
+        .build()
+        .unwrap();
+    let transfer = chain.iter_blocks(1..).next().unwrap().unwrap().transactions[0].calculate_hash();
+
+    // corrupt the derived state
+    chain
+        .get_account_by_id_mut(&account_id("bob"))
+        .unwrap()
+        .tokens = 1_000_000;
+    chain.transactions_index.clear();
+
+    assert_eq!(Ok(()), chain.reindex());
+    let tokens = |id: &str| chain.get_account_by_id(&account_id(id)).unwrap().tokens;
+    assert_eq!(30, tokens("bob"));
+    assert_eq!(70, tokens("alice"));
+    assert_eq!(2, chain.height());
+    assert!(chain.get_transaction(&transfer).unwrap().is_some());
+    assert!(chain.get_receipt(&transfer).is_some());
+    assert_eq!(Ok(()), chain.verify_chain());
+}
+
+#[test]
+fn test_reindex_keeps_chain_on_invalid_block() {
+    use crate::testing::account_id;
+
+    let mut chain = crate::testing::ChainBuilder::new()
+        .with_account("alice", 100)
+        .with_account("bob", 0)
+        .block(|b| b.transfer("alice", "bob", 30))
+        .build()
+        .unwrap();
+    chain.unflushed[1].hash = Some(vec![]);
+
+    assert_eq!(
+        Err(BlockchainError::InvalidBlock {
+            index: 1,
+            error: Box::new(BlockchainError::InvalidHash)
+        }),
+        chain.reindex()
+    );
+    assert_eq!(2, chain.height());
+    assert_eq!(
+        30,
+        chain.get_account_by_id(&account_id("bob")).unwrap().tokens
+    );
+}
+
+#[test]
+fn test_add_block_checks_proof_of_work() {
+    let mut chain = Blockchain::new();
+    let mut genesis = Block::new();
+    genesis.seal();
+    chain.add_block(genesis).unwrap();
+    chain.schedule_params(
+        1,
+        ChainParams {
+            difficulty: 8,
+            ..ChainParams::default()
+        },
+    );
+
+    let new_block = |chain: &Blockchain| {
+        let mut block = Block::new();
+        block.header.height = chain.height();
+        block.header.previous_hash = chain.get_last_block_hash().cloned();
+        block
+    };
+
+    let mut block = new_block(&chain);
+    block.header.difficulty = 8;
+    block.seal();
+    while block.meets_difficulty() {
+        block.header.nonce += 1;
+        block.seal();
+    }
+    assert_eq!(
+        Err(BlockchainError::InsufficientProofOfWork),
+        chain.add_block(block)
+    );
+
+    let mut block = new_block(&chain);
+    block.mine(4);
+    assert_eq!(
+        Err(BlockchainError::WrongDifficulty {
+            expected: 8,
+            got: 4
+        }),
+        chain.add_block(block)
+    );
+
+    let mut block = new_block(&chain);
+    block.mine(8);
+    assert_eq!(Ok(()), chain.add_block(block));
+    assert_eq!(Ok(()), chain.verify_chain());
+}
+
+#[test]
+fn test_add_block_checks_height() {
+    let mut chain = Blockchain::new();
+    let mut genesis = Block::new();
+    genesis.seal();
+    chain.add_block(genesis).unwrap();
+
+    let mut block = Block::new();
+    block.header.height = 2;
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
+    block.seal();
+    assert_eq!(
+        Err(BlockchainError::InvalidHeight {
+            expected: 1,
+            got: 2
+        }),
+        chain.add_block(block.clone())
+    );
+
+    block.header.height = 1;
+    block.seal();
+    assert_eq!(Ok(()), chain.add_block(block));
+
+    chain.unflushed[1].header.height = 3;
+    chain.unflushed[1].seal();
+    assert_eq!(
+        Err(BlockchainError::InvalidBlock {
+            index: 1,
+            error: Box::new(BlockchainError::InvalidHeight {
+                expected: 1,
+                got: 3
+            })
+        }),
+        chain.verify_chain()
+    );
+}
+
+#[test]
+fn test_add_block_checks_timestamp() {
+    use crate::clock::MockClock;
+
+    let clock = MockClock::new(std::time::UNIX_EPOCH + Duration::from_secs(1_000_000));
+    let mut chain = Blockchain::new().with_clock(clock);
+    let mut genesis = Block::with_clock(chain.clock());
+    genesis.seal();
+    chain.add_block(genesis).unwrap();
+
+    let mut block = Block::with_clock(chain.clock());
+    block.header.height = 1;
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
+    block.header.timestamp -= Duration::from_secs(1);
+    block.seal();
+    assert_eq!(
+        Err(BlockchainError::BlockBeforeParent),
+        chain.add_block(block.clone())
+    );
+
+    block.header.timestamp += MAX_FUTURE_DRIFT + Duration::from_secs(2);
+    block.seal();
+    assert_eq!(
+        Err(BlockchainError::BlockInFuture),
+        chain.add_block(block.clone())
+    );
+    assert_eq!(
+        Err(BlockchainError::InvalidBlock {
+            index: 0,
+            error: Box::new(BlockchainError::BlockInFuture)
+        }),
+        chain.add_blocks(vec![block.clone()])
+    );
+
+    chain.clock().advance(Duration::from_secs(1));
+    assert_eq!(Ok(()), chain.add_block(block));
+
+    chain.unflushed[1].header.timestamp = std::time::UNIX_EPOCH;
+    chain.unflushed[1].seal();
+    assert_eq!(
+        Err(BlockchainError::InvalidBlock {
+            index: 1,
+            error: Box::new(BlockchainError::BlockBeforeParent)
+        }),
+        chain.verify_chain()
+    );
+}
+
+#[test]
+fn test_add_block_checks_merkle_root() {
+    use crate::transaction::TransactionRecord;
+
+    let mut chain = Blockchain::new();
+    let mut genesis = Block::new();
+    genesis.seal();
+    chain.add_block(genesis).unwrap();
+
+    let mut block = Block::new();
+    block.header.height = 1;
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
+    block.seal();
+    // the hash is still valid, but doesn't commit to this transaction
+    block.transactions.push(Transaction::new(
+        0,
+        TransactionRecord::create_user_account(b"someone"),
+        None,
+    ));
+    assert_eq!(
+        Err(BlockchainError::InvalidMerkleRoot),
+        chain.add_block(block.clone())
+    );
+
+    block.seal();
+    assert_eq!(Ok(()), chain.add_block(block));
+
+    chain.unflushed[1].transactions.clear();
+    assert_eq!(
+        Err(BlockchainError::InvalidBlock {
+            index: 1,
+            error: Box::new(BlockchainError::InvalidMerkleRoot)
+        }),
+        chain.verify_chain()
+    );
+}
+
+#[test]
+fn test_add_block_checks_bloom() {
+    use crate::bloom::BloomFilter;
+    use crate::transaction::TransactionRecord;
+
+    let mut chain = Blockchain::new();
+    let mut genesis = Block::new();
+    genesis.seal();
+    chain.add_block(genesis).unwrap();
+
+    let mut block = Block::new();
+    block.header.height = 1;
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
+    block.transactions.push(Transaction::new(
+        0,
+        TransactionRecord::create_user_account(b"someone"),
+        None,
+    ));
+    block.seal();
+    // hide an account from the filter, without breaking the hash
+    block.header.bloom = BloomFilter::new();
+    block.hash = Some(block.calculate_hash());
+    assert_eq!(
+        Err(BlockchainError::InvalidBloom),
+        chain.add_block(block.clone())
+    );
+
+    block.seal();
+    assert_eq!(Ok(()), chain.add_block(block));
+
+    chain.unflushed[1].header.bloom.insert("someone else");
+    chain.unflushed[1].hash = Some(chain.unflushed[1].calculate_hash());
+    assert_eq!(
+        Err(BlockchainError::InvalidBlock {
+            index: 1,
+            error: Box::new(BlockchainError::InvalidBloom)
+        }),
+        chain.verify_chain()
+    );
+}
+
+#[test]
+fn test_open_keeps_genesis_and_params() {
+    use crate::account::AccountType;
+    use crate::testing::{account_id, keypair, TempDir};
+    use crate::transaction::TransactionRecord;
+
+    let dir = TempDir::new("chain-genesis");
+    let mut genesis = GenesisConfig {
+        params: ChainParams {
+            min_transfer: 5,
+            ..ChainParams::default()
+        },
+        ..GenesisConfig::default()
+    }
+    .with_allocation(keypair("alice").verifying_key().as_bytes(), 100)
+    .with_allocation(keypair("bob").verifying_key().as_bytes(), 0)
+    .with_allocation(keypair("authority").verifying_key().as_bytes(), 0);
+    genesis.allocations[2].account_type = AccountType::System;
+    let stricter = ChainParams {
+        min_transfer: 20,
+        ..ChainParams::default()
+    };
+    let adjuster = DifficultyAdjuster::new(Duration::from_secs(60), 2);
+    let transfer = |nonce, amount| {
+        let mut transaction = Transaction::new(
+            nonce,
+            TransactionRecord::SendTokens {
+                to: account_id("bob"),
+                amount,
+            },
+            Some(account_id("alice")),
+        );
+        transaction.sign(&keypair("alice"));
+        transaction
+    };
+    let add_block = |chain: &mut Blockchain<FileStore>, transactions| {
+        let mut block = Block::new();
+        block.header.height = chain.height();
+        block.header.previous_hash = chain.get_last_block_hash().cloned();
+        block.transactions = transactions;
+        chain.mine_block(&mut block);
+        chain.add_block(block)
+    };
+    let open = |genesis: &GenesisConfig| {
+        Blockchain::with_genesis(FileStore::open(dir.path()).unwrap(), genesis)
+    };
+
+    let mut chain = open(&genesis).unwrap();
+    let mut schedule = Transaction::new(
+        1,
+        TransactionRecord::ScheduleParams {
+            height: 3,
+            params: stricter.clone(),
+        },
+        Some(account_id("authority")),
+    );
+    schedule.sign(&keypair("authority"));
+    chain.set_difficulty_adjuster(adjuster.clone());
+    assert_eq!(
+        Ok(()),
+        add_block(&mut chain, vec![schedule, transfer(1, 5)])
+    );
+    chain.flush().unwrap();
+    drop(chain);
+
+    let mut chain = open(&genesis).unwrap();
+    assert_eq!(Some(&genesis), chain.genesis.as_ref());
+    assert_eq!(&stricter, chain.params_at(3));
+    assert_eq!(adjuster, chain.difficulty_adjuster);
+    // height 2: the genesis rules are in effect
+    assert_eq!(
+        Err(BlockchainError::InvalidTransaction {
+            index: 0,
+            error: Box::new(BlockchainError::BelowMinimumTransfer)
+        }),
+        add_block(&mut chain, vec![transfer(2, 4)])
+    );
+    assert_eq!(Ok(()), add_block(&mut chain, vec![transfer(2, 5)]));
+    // height 3: the scheduled rules are
+    assert_eq!(
+        Err(BlockchainError::InvalidTransaction {
+            index: 0,
+            error: Box::new(BlockchainError::BelowMinimumTransfer)
+        }),
+        add_block(&mut chain, vec![transfer(3, 5)])
+    );
+    assert_eq!(Ok(()), add_block(&mut chain, vec![transfer(3, 20)]));
+    // blocks came fast: the difficulty goes up at height 4
+    assert_eq!(1, chain.current_difficulty());
+    assert_eq!(Ok(()), chain.reindex());
+    assert_eq!(&stricter, chain.params_at(3));
+    assert_eq!(
+        70,
+        chain
+            .get_account_by_id(&account_id("alice"))
+            .unwrap()
+            .tokens
+    );
+    chain.flush().unwrap();
+    drop(chain);
+
+    assert_eq!(
+        Err(BlockchainError::GenesisMismatch),
+        open(&GenesisConfig::default()).map(|_| ())
+    );
+}
+
+#[test]
+fn test_open_and_flush() {
+    use crate::testing::TempDir;
+    use crate::transaction::TransactionRecord;
+
+    let dir = TempDir::new("chain-open");
+    let add_account = |chain: &mut Blockchain<FileStore>, name: &str| {
+        let mut block = Block::new();
+        block.header.height = chain.height();
+        block.header.previous_hash = chain.get_last_block_hash().cloned();
+        block.transactions.push(Transaction::new(
+            0,
+            TransactionRecord::create_user_account(name.as_bytes()),
+            None,
+        ));
+        block.seal();
+        chain.add_block(block).unwrap();
+    };
+
+    let mut chain = Blockchain::open(dir.path()).unwrap();
+    assert!(chain.is_genesis());
+    let mut genesis = Block::new();
+    genesis.seal();
+    chain.add_block(genesis).unwrap();
+    add_account(&mut chain, "someone");
+    add_account(&mut chain, "someone else");
+    let chain_size = chain.stats(0).unwrap().chain_size;
+    chain.flush().unwrap();
+    // only the bodies of blocks added since the flush stay loaded
+    assert!(chain.unflushed.is_empty());
+    assert_eq!(3, chain.iter_blocks(..).count());
+    // not flushed
+    add_account(&mut chain, "another one");
+    assert_eq!(1, chain.unflushed.len());
+
+    let mut chain = Blockchain::open(dir.path()).unwrap();
+    assert_eq!(3, chain.height());
+    assert_eq!(2, chain.accounts.len());
+    assert!(chain.unflushed.is_empty());
+    assert_eq!(chain_size, chain.stats(0).unwrap().chain_size);
+    assert!(chain
+        .get_account_by_id(&Id::from_public_key(b"someone else"))
+        .is_ok());
+    let block = chain.get_block(2).unwrap().unwrap();
+    assert_eq!(chain.get_last_block_hash(), block.hash.as_ref());
+    let hash = block.transactions[0].calculate_hash();
+    assert!(chain.get_transaction(&hash).unwrap().is_some());
+    assert!(chain.get_block(3).unwrap().is_none());
+
+    add_account(&mut chain, "another one");
+    chain.flush().unwrap();
+    let chain = Blockchain::open(dir.path()).unwrap();
+    assert_eq!(4, chain.height());
+    assert_eq!(3, chain.accounts.len());
+    assert_eq!(Ok(()), chain.verify_chain());
+}
+
+#[test]
+fn test_stored_blocks_must_match_their_headers() {
+    let mut chain = Blockchain::new();
+    let mut genesis = Block::new();
+    genesis.seal();
+    chain.add_block(genesis).unwrap();
+    let mut block = Block::new();
+    block.header.height = 1;
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
+    block.seal();
+    chain.add_block(block.clone()).unwrap();
+    chain.flush().unwrap();
+
+    block.header.nonce += 1;
+    block.seal();
+    chain.store.truncate(1).unwrap();
+    chain.store.put_block(&block).unwrap();
+    assert_eq!(
+        Err(BlockchainError::Storage(
+            "stored block at height 1 doesn't match its header".to_string()
+        )),
+        chain.get_block(1).map(|_| ())
+    );
+    assert_eq!(
+        Err(BlockchainError::Storage(
+            "stored block at height 1 doesn't match its header".to_string()
+        )),
+        chain.verify_chain()
+    );
+}
+
+#[test]
+fn test_prove_transaction() {
+    use crate::testing::ChainBuilder;
+
+    let chain = ChainBuilder::new()
+        .with_account("alice", 100)
+        .with_account("bob", 0)
+        .block(|block| block.transfer("alice", "bob", 1))
+        .block(|block| {
+            block
+                .transfer("alice", "bob", 2)
+                .transfer("alice", "bob", 3)
+                .transfer("alice", "bob", 4)
+        })
+        .build()
+        .unwrap();
+
+    let genesis = chain.get_block(0).unwrap().unwrap();
+    for block in chain.iter_blocks(1..) {
+        let block = block.unwrap();
+        for transaction in &block.transactions {
+            let hash = transaction.calculate_hash();
+            let proof = chain.prove_transaction(&hash).unwrap().unwrap();
+            assert!(proof.verify(block.merkle_root(), &hash));
+            assert!(!proof.verify(genesis.merkle_root(), &hash));
+        }
+    }
+    assert_eq!(Ok(None), chain.prove_transaction(&[0; 32]));
+}
+
+#[test]
+fn test_current_difficulty_adjusts_to_block_times() {
+    let mut chain = Blockchain::new();
+    chain.set_difficulty_adjuster(DifficultyAdjuster::new(Duration::from_secs(60), 2));
+
+    let mut difficulties = vec![];
+    for _ in 0..7 {
+        difficulties.push(chain.current_difficulty());
+        let mut block = Block::new();
+        block.header.height = chain.height();
+        block.header.previous_hash = chain.get_last_block_hash().cloned();
+        chain.mine_block(&mut block);
+        chain.add_block(block).unwrap();
+    }
+
+    // blocks come much faster than one a minute
+    assert_eq!(vec![0, 0, 0, 0, 1, 1, 2], difficulties);
+
+    let mut block = Block::new();
+    block.header.height = chain.height();
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
+    block.mine(1);
+    assert_eq!(
+        Err(BlockchainError::WrongDifficulty {
+            expected: 2,
+            got: 1
+        }),
+        chain.add_block(block)
+    );
+}
+
+#[test]
+fn test_add_block_checks_signatures() {
+    use crate::account::generate_keypair;
+    use crate::rng::SeededRng;
+    use crate::testing::account_id;
+
+    let keypair = generate_keypair(&SeededRng::new(0));
+    let public_key = keypair.verifying_key().to_bytes();
+    let alice = Id::from_public_key(&public_key);
+    let mut chain = crate::testing::ChainBuilder::new()
+        .with_account("bob", 0)
+        .block(|b| b.create_account(&public_key))
+        .build()
+        .unwrap();
+    chain.get_account_by_id_mut(&alice).unwrap().tokens = 100;
+
+    let transfer = |signer: Option<&crate::account::Keypair>| {
+        let mut transaction = Transaction::new(
+            1,
+            TransactionRecord::SendTokens {
+                to: account_id("bob"),
+                amount: 10,
+            },
+            Some(alice.clone()),
+        );
+        if let Some(signer) = signer {
+            transaction.sign(signer);
+        }
+        let mut block = Block::new();
+        block.header.height = chain.height();
+        block.header.previous_hash = chain.get_last_block_hash().cloned();
+        block.transactions.push(transaction);
+        block.seal();
+        block
+    };
+
+    let unsigned = transfer(None);
+    let forged = transfer(Some(&generate_keypair(&SeededRng::new(1))));
+    let signed = transfer(Some(&keypair));
+    assert_eq!(
+        Err(BlockchainError::InvalidTransaction {
+            index: 0,
+            error: Box::new(BlockchainError::MissingSignature)
+        }),
+        chain.add_block(unsigned)
+    );
+    assert_eq!(
+        Err(BlockchainError::InvalidTransaction {
+            index: 0,
+            error: Box::new(BlockchainError::InvalidSignature)
+        }),
+        chain.add_block(forged)
+    );
+    assert_eq!(Ok(()), chain.add_block(signed));
+    assert_eq!(
+        10,
+        chain.get_account_by_id(&account_id("bob")).unwrap().tokens
+    );
+}
+
+#[test]
+fn test_submit_transaction() {
+    use crate::account::generate_keypair;
+    use crate::rng::SeededRng;
+    use crate::testing::account_id;
+
+    let keypair = generate_keypair(&SeededRng::new(0));
+    let public_key = keypair.verifying_key().to_bytes();
+    let alice = Id::from_public_key(&public_key);
+    let mut chain = crate::testing::ChainBuilder::new()
+        .with_account("bob", 0)
+        .block(|b| b.create_account(&public_key))
+        .build()
+        .unwrap();
+    chain.get_account_by_id_mut(&alice).unwrap().tokens = 100;
+
+    let transfer = |nonce, amount, signer: Option<&crate::account::Keypair>| {
+        let mut transaction = Transaction::new(
+            nonce,
+            TransactionRecord::SendTokens {
+                to: account_id("bob"),
+                amount,
+            },
+            Some(alice.clone()),
+        );
+        if let Some(signer) = signer {
+            transaction.sign(signer);
+        }
+        transaction
+    };
+
+    assert_eq!(
+        Err(BlockchainError::MissingSignature),
+        chain.submit_transaction(transfer(1, 10, None))
+    );
+    assert_eq!(
+        Err(BlockchainError::InvalidSignature),
+        chain.submit_transaction(transfer(1, 10, Some(&generate_keypair(&SeededRng::new(1)))))
+    );
+    let included = transfer(1, 60, Some(&keypair));
+    assert_eq!(Ok(()), chain.submit_transaction(included.clone()));
+    assert_eq!(
+        Err(BlockchainError::NonceAlreadyUsed),
+        chain.submit_transaction(transfer(1, 10, Some(&keypair)))
+    );
+    assert_eq!(
+        Err(BlockchainError::InvalidNonce {
+            expected: 2,
+            got: 3
+        }),
+        chain.submit_transaction(transfer(3, 10, Some(&keypair)))
+    );
+    assert_eq!(
+        Err(BlockchainError::InsufficientFunds {
+            available: 40,
+            required: 50
+        }),
+        chain.submit_transaction(transfer(2, 50, Some(&keypair)))
+    );
+    assert_eq!(
+        Err(BlockchainError::AccountNotFound("nobody".into())),
+        chain.submit_transaction(Transaction::new(
+            1,
+            TransactionRecord::SendTokens {
+                to: account_id("bob"),
+                amount: 1
+            },
+            Some("nobody".into())
+        ))
+    );
+    assert_eq!(1, chain.pending_count());
+
+    let mut block = Block::new();
+    block.header.height = chain.height();
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
+    block.transactions = chain.drain_valid(10);
+    block.seal();
+    chain.add_block(block).unwrap();
+    assert_eq!(
+        Err(BlockchainError::AlreadyIncluded),
+        chain.submit_transaction(included.clone())
+    );
+    assert_eq!(
+        Err(BlockchainError::InvalidNonce {
+            expected: 2,
+            got: 1
+        }),
+        chain.submit_transaction(transfer(1, 10, Some(&keypair)))
+    );
+
+    // the included transaction cannot be replayed in another block either
+    let mut block = Block::new();
+    block.header.height = chain.height();
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
+    block.transactions.push(included);
+    block.seal();
+    assert_eq!(
+        Err(BlockchainError::InvalidTransaction {
+            index: 0,
+            error: Box::new(BlockchainError::InvalidNonce {
+                expected: 2,
+                got: 1
+            })
+        }),
+        chain.add_block(block)
+    );
+    assert_eq!(40, chain.get_account_by_id(&alice).unwrap().tokens);
+}
+
+#[test]
+fn test_drain_valid() {
+    use crate::testing::{account_id, keypair};
+
+    let mut chain = crate::testing::ChainBuilder::new()
+        .with_account("alice", 10)
+        .with_account("bob", 0)
+        .build()
+        .unwrap();
+    let transfer = |nonce, amount| {
+        let mut transaction = Transaction::new(
+            nonce,
+            TransactionRecord::SendTokens {
+                to: account_id("bob"),
+                amount,
+            },
+            Some(account_id("alice")),
+        );
+        transaction.sign(&keypair("alice"));
+        transaction
+    };
+    let transfers: Vec<Transaction> = (1..=3).map(|nonce| transfer(nonce, 3)).collect();
+    for transaction in &transfers {
+        chain.submit_transaction(transaction.clone()).unwrap();
+    }
+    assert_eq!(3, chain.pending_count());
+    // alice spends her tokens before the pending transactions are included
+    chain
+        .get_account_by_id_mut(&account_id("alice"))
+        .unwrap()
+        .tokens = 5;
+
+    let drained = chain.drain_valid(2);
+    assert_eq!(vec![1], drained.iter().map(|t| t.nonce).collect::<Vec<_>>());
+    assert_eq!(0, chain.pending_count());
+    assert!(chain.drain_valid(2).is_empty());
+
+    // the dropped transactions can be looked up, with the reason they failed
+    let receipt = chain.get_receipt(&transfers[1].calculate_hash()).unwrap();
+    assert!(!receipt.is_success());
+    assert_eq!(
+        Some(BlockchainError::InsufficientFunds {
+            available: 2,
+            required: 3
+        }),
+        receipt.error
+    );
+    assert!(receipt.events.is_empty());
+    assert_eq!(
+        vec![(account_id("alice"), 2), (account_id("bob"), 3)],
+        receipt.balances
+    );
+    assert_eq!(
+        Some(BlockchainError::InvalidNonce {
+            expected: 2,
+            got: 3
+        }),
+        chain
+            .get_receipt(&transfers[2].calculate_hash())
+            .unwrap()
+            .error
+    );
+}
+
+#[test]
+fn test_drain_valid_keeps_lane_quotas() {
+    use crate::account::AccountType;
+    use crate::mempool::LaneQuotas;
+    use crate::testing::{account_id, keypair};
+    use crate::transaction::Lane;
+
+    let mut genesis = GenesisConfig::default()
+        .with_allocation(keypair("authority").verifying_key().as_bytes(), 0)
+        .with_allocation(keypair("alice").verifying_key().as_bytes(), 10)
+        .with_allocation(keypair("bob").verifying_key().as_bytes(), 10);
+    genesis.allocations[0].account_type = AccountType::System;
+    let mut chain = Blockchain::from_genesis(&genesis).unwrap();
+    chain
+        .mempool_mut()
+        .set_lane_quotas(LaneQuotas { system: 1, user: 1 });
+
+    let signed = |name: &str, nonce, record| {
+        let mut transaction = Transaction::new(nonce, record, Some(account_id(name)));
+        transaction.sign(&keypair(name));
+        transaction
+    };
+    let freeze = signed(
+        "authority",
+        1,
+        TransactionRecord::FreezeAccount(account_id("alice")),
+    );
+    // not a freeze, but sent by the authority all the same
+    let metadata = signed(
+        "authority",
+        2,
+        TransactionRecord::SetMetadata {
+            key: "url".to_string(),
+            value: Some("https://example.com".to_string()),
+        },
+    );
+    assert_eq!(Lane::System, metadata.lane(&chain));
+    let transfer = |from: &str, to: &str| {
+        signed(
+            from,
+            1,
+            TransactionRecord::SendTokens {
+                to: account_id(to),
+                amount: 5,
+            },
+        )
+    };
+    let from_bob = transfer("bob", "alice");
+    assert_eq!(Lane::User, from_bob.lane(&chain));
+
+    for transaction in [
+        freeze.clone(),
+        metadata,
+        transfer("alice", "bob"),
+        from_bob.clone(),
+    ] {
+        chain.submit_transaction(transaction).unwrap();
+    }
+
+    // once alice is frozen, her transfer is dropped: the refill must take
+    // bob's, and not the second system transaction
+    let drained = chain.drain_valid(2);
+    assert_eq!(
+        vec![freeze.calculate_hash(), from_bob.calculate_hash()],
+        drained
+            .iter()
+            .map(Transaction::calculate_hash)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(1, chain.pending_count());
+}
+
+#[test]
+fn test_add_block_collects_fees() {
+    use crate::testing::{account_id, keypair};
+
+    let mut chain = crate::testing::ChainBuilder::new()
+        .with_account("alice", 100)
+        .with_account("bob", 0)
+        .with_account("miner", 0)
+        .build()
+        .unwrap();
+    let block = |chain: &Blockchain, beneficiary: Option<&Id>| {
+        let mut block = Block::new();
+        block.header.height = chain.height();
+        block.header.previous_hash = chain.get_last_block_hash().cloned();
+        block.header.beneficiary = beneficiary.cloned();
+        for (nonce, fee) in [(1, 2), (2, 3)] {
+            let mut transaction = Transaction::new(
+                nonce,
+                TransactionRecord::SendTokens {
+                    to: account_id("bob"),
+                    amount: 10,
+                },
+                Some(account_id("alice")),
+            )
+            .with_fee(fee);
+            transaction.sign(&keypair("alice"));
+            block.transactions.push(transaction);
+        }
+        block.seal();
+        block
+    };
+
+    assert_eq!(
+        Err(BlockchainError::InvalidTransaction {
+            index: 0,
+            error: Box::new(BlockchainError::MissingBeneficiary)
+        }),
+        chain.add_block(block(&chain, None))
+    );
+    assert_eq!(
+        Err(BlockchainError::InvalidTransaction {
+            index: 0,
+            error: Box::new(BlockchainError::AccountNotFound("nobody".into()))
+        }),
+        chain.add_block(block(&chain, Some(&"nobody".into())))
+    );
+    let tokens =
+        |chain: &Blockchain, name| chain.get_account_by_id(&account_id(name)).unwrap().tokens;
+    assert_eq!(100, tokens(&chain, "alice"));
+
+    let miner = account_id("miner");
+    chain.add_block(block(&chain, Some(&miner))).unwrap();
+    assert_eq!(75, tokens(&chain, "alice"));
+    assert_eq!(20, tokens(&chain, "bob"));
+    assert_eq!(5, tokens(&chain, "miner"));
+    assert_eq!(Some(5), chain.balance_at(&miner, chain.height() - 1));
+    assert_eq!(Ok(()), chain.verify_chain());
+
+    let stats = chain.stats(2).unwrap();
+    assert_eq!(5, stats.total_fees);
+    assert_eq!(2.5, stats.average_fee);
+}
+
+#[test]
+fn test_fees_cannot_leave_dust() {
+    use crate::testing::{account_id, keypair};
+    use crate::transaction::TransactionRecord;
+
+    let genesis = GenesisConfig {
+        params: ChainParams {
+            min_balance: 10,
+            ..ChainParams::default()
+        },
+        ..GenesisConfig::default()
+    }
+    .with_allocation(keypair("alice").verifying_key().as_bytes(), 15)
+    .with_allocation(keypair("miner").verifying_key().as_bytes(), 10);
+    let chain = Blockchain::from_genesis(&genesis).unwrap();
+    let miner = account_id("miner");
+
+    let mut register = Transaction::new(
+        1,
+        TransactionRecord::RegisterName {
+            name: "alice".to_string(),
+        },
+        Some(account_id("alice")),
+    )
+    .with_fee(6);
+    register.sign(&keypair("alice"));
+    assert_eq!(
+        Err(BlockchainError::DustBalance),
+        chain.simulate(&register, &miner)
+    );
+
+    let mut register = register.with_fee(5);
+    register.sign(&keypair("alice"));
+    assert!(chain.simulate(&register, &miner).is_ok());
 }
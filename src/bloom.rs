@@ -0,0 +1,123 @@
+use crate::{Error, Hash};
+
+/// Size of a bloom filter, in bytes.
+pub const BLOOM_SIZE: usize = 256;
+
+/// Number of bits set for each inserted item.
+const BLOOM_HASHES: usize = 3;
+
+/**
+A fixed-size bloom filter, used to know quickly whether a block might involve
+an account without scanning all of its transactions.
+
+A bloom filter can return false positives, but never false negatives.
+
+```
+# use crate::blockchain::bloom::BloomFilter;
+let mut bloom = BloomFilter::new();
+bloom.insert("someone");
+
+assert!(bloom.contains("someone"));
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+    bits: Vec<u8>,
+}
+
+impl BloomFilter {
+    /// Constructor
+    pub fn new() -> Self {
+        BloomFilter {
+            bits: vec![0; BLOOM_SIZE],
+        }
+    }
+
+    /// Add an item to the filter.
+    pub fn insert<T: AsRef<[u8]>>(&mut self, item: T) {
+        for bit in Self::bit_positions(item.as_ref()) {
+            self.bits[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    /// Might the item have been added to the filter?
+    pub fn contains<T: AsRef<[u8]>>(&self, item: T) -> bool {
+        Self::bit_positions(item.as_ref())
+            .iter()
+            .all(|bit| self.bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    /// Decode a filter from its bits, as returned by `as_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != BLOOM_SIZE {
+            return Err(format!(
+                "bloom filter of {} bytes, expected {}",
+                bytes.len(),
+                BLOOM_SIZE
+            ));
+        }
+        Ok(BloomFilter {
+            bits: bytes.to_vec(),
+        })
+    }
+
+    /// Bits of the filter.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bits
+    }
+
+    /// Is the filter empty, i.e. no item has been inserted?
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|byte| *byte == 0)
+    }
+
+    /// Derive the bits to set for an item from its hash.
+    fn bit_positions(item: &[u8]) -> [usize; BLOOM_HASHES] {
+        let hash: Hash = blake3::hash(item).as_bytes().to_vec();
+        let mut positions = [0; BLOOM_HASHES];
+        for (i, position) in positions.iter_mut().enumerate() {
+            let bytes = [hash[2 * i], hash[2 * i + 1]];
+            *position = u16::from_be_bytes(bytes) as usize % (BLOOM_SIZE * 8);
+        }
+        positions
+    }
+}
+
+impl Default for BloomFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod bloom_tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_filter_contains_nothing() {
+        let bloom = BloomFilter::new();
+        assert!(bloom.is_empty());
+        assert!(!bloom.contains("someone"));
+    }
+
+    #[test]
+    fn test_contains_inserted_items() {
+        let mut bloom = BloomFilter::new();
+        bloom.insert("someone");
+        bloom.insert("someone else");
+
+        assert!(!bloom.is_empty());
+        assert!(bloom.contains("someone"));
+        assert!(bloom.contains("someone else"));
+        assert!(!bloom.contains("nobody"));
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let mut bloom = BloomFilter::new();
+        bloom.insert("someone");
+
+        assert_eq!(Ok(bloom.clone()), BloomFilter::from_bytes(bloom.as_bytes()));
+        assert!(BloomFilter::from_bytes(&[0; BLOOM_SIZE - 1]).is_err());
+    }
+}
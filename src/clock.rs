@@ -0,0 +1,80 @@
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// Tells the time, to timestamp transactions and blocks.
+pub trait Clock {
+    /// The current time.
+    fn now(&self) -> SystemTime;
+}
+
+/// The local time of the system.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/**
+A clock that only moves when told to, for deterministic tests and
+simulations.
+
+```
+# use crate::blockchain::clock::{Clock, MockClock};
+# use std::time::{Duration, UNIX_EPOCH};
+let clock = MockClock::new(UNIX_EPOCH);
+clock.advance(Duration::from_secs(10));
+
+assert_eq!(UNIX_EPOCH + Duration::from_secs(10), clock.now());
+```
+*/
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<SystemTime>,
+}
+
+impl MockClock {
+    /// Constructor
+    pub fn new(now: SystemTime) -> Self {
+        MockClock {
+            now: Mutex::new(now),
+        }
+    }
+
+    /// Move the clock forward.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+
+    /// Set the current time.
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().unwrap() = now;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod clock_tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::new(UNIX_EPOCH);
+        assert_eq!(UNIX_EPOCH, clock.now());
+        assert_eq!(UNIX_EPOCH, clock.now());
+
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(UNIX_EPOCH + Duration::from_secs(3), clock.now());
+
+        clock.set(UNIX_EPOCH + Duration::from_secs(1));
+        assert_eq!(UNIX_EPOCH + Duration::from_secs(1), clock.now());
+    }
+}
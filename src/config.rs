@@ -0,0 +1,272 @@
+use crate::presets::Network;
+use crate::Error;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+/// Prefix of the environment variables overriding configuration settings.
+pub const ENV_PREFIX: &str = "BLOCKCHAIN_";
+
+/**
+Settings of a node, loaded from a TOML file. Missing settings take their
+default value.
+
+```
+# use crate::blockchain::config::Config;
+let config = Config::from_toml(r#"
+data_dir = "/var/lib/blockchain"
+mining = true
+"#).unwrap();
+
+assert!(config.mining);
+assert_eq!(Config::default().rpc_bind, config.rpc_bind);
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Directory where the node stores its data.
+    pub data_dir: PathBuf,
+
+    /// Addresses to listen to peers on.
+    pub listen_addresses: Vec<SocketAddr>,
+
+    /// Peers to connect to on startup, as `host:port`.
+    pub peers: Vec<String>,
+
+    /// Should the node mine blocks?
+    pub mining: bool,
+
+    /// Address the RPC server binds to.
+    pub rpc_bind: SocketAddr,
+
+    /// Well-known network to join: `mainnet`, `testnet` or `devnet`.
+    pub chain: String,
+
+    /// File describing the parameters of the chain, if not the default ones.
+    pub chain_params_file: Option<PathBuf>,
+}
+
+impl Config {
+    /// Load the configuration from a TOML file, apply the environment
+    /// overrides and validate it.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("cannot read config file {}: {}", path.display(), err))?;
+
+        let mut config = Self::from_toml(&content)?;
+        config.apply_env(std::env::vars())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parse the configuration from TOML.
+    pub fn from_toml(content: &str) -> Result<Self, Error> {
+        toml::from_str(content).map_err(|err| format!("invalid config: {}", err))
+    }
+
+    /// Override settings from environment variables, e.g. `BLOCKCHAIN_MINING`
+    /// overrides `mining`. Lists are comma-separated.
+    pub fn apply_env<I: IntoIterator<Item = (String, String)>>(
+        &mut self,
+        vars: I,
+    ) -> Result<(), Error> {
+        for (name, value) in vars {
+            let setting = match name.strip_prefix(ENV_PREFIX) {
+                Some(setting) => setting,
+                None => continue,
+            };
+            let invalid = |err: &dyn std::fmt::Display| format!("invalid {}: {}", name, err);
+
+            match setting {
+                "DATA_DIR" => self.data_dir = value.into(),
+                "LISTEN_ADDRESSES" => {
+                    self.listen_addresses = split_list(&value)
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()
+                        .map_err(|err| invalid(&err))?
+                }
+                "PEERS" => self.peers = split_list(&value).map(str::to_string).collect(),
+                "MINING" => self.mining = value.parse().map_err(|err| invalid(&err))?,
+                "RPC_BIND" => self.rpc_bind = value.parse().map_err(|err| invalid(&err))?,
+                "CHAIN" => self.chain = value,
+                "CHAIN_PARAMS_FILE" => self.chain_params_file = Some(value.into()),
+                _ => return Err(format!("unknown setting {}", name)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the settings make sense together.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.data_dir.as_os_str().is_empty() {
+            return Err("invalid config: data_dir cannot be empty".to_string());
+        }
+
+        if self.listen_addresses.is_empty() {
+            return Err("invalid config: listen_addresses cannot be empty".to_string());
+        }
+
+        if self.listen_addresses.contains(&self.rpc_bind) {
+            return Err(format!(
+                "invalid config: rpc_bind {} is also a listen address",
+                self.rpc_bind
+            ));
+        }
+
+        for peer in &self.peers {
+            match peer.rsplit_once(':') {
+                Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {}
+                _ => {
+                    return Err(format!(
+                        "invalid config: peer {:?} should be formatted as host:port",
+                        peer
+                    ))
+                }
+            }
+        }
+
+        Network::from_name(&self.chain).map_err(|err| format!("invalid config: {}", err))?;
+
+        if let Some(file) = &self.chain_params_file {
+            if !file.is_file() {
+                return Err(format!(
+                    "invalid config: chain_params_file {} does not exist",
+                    file.display()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            data_dir: PathBuf::from("data"),
+            listen_addresses: vec![SocketAddr::from(([0, 0, 0, 0], 7878))],
+            peers: vec![],
+            mining: false,
+            rpc_bind: SocketAddr::from(([127, 0, 0, 1], 7879)),
+            chain: Network::Mainnet.name().to_string(),
+            chain_params_file: None,
+        }
+    }
+}
+
+/// Split a comma-separated list, ignoring empty items.
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let config = Config::from_toml(
+            r#"
+data_dir = "/tmp/chain"
+listen_addresses = ["127.0.0.1:9000"]
+peers = ["example.com:9000"]
+mining = true
+rpc_bind = "127.0.0.1:9001"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Config {
+                data_dir: "/tmp/chain".into(),
+                listen_addresses: vec!["127.0.0.1:9000".parse().unwrap()],
+                peers: vec!["example.com:9000".to_string()],
+                mining: true,
+                rpc_bind: "127.0.0.1:9001".parse().unwrap(),
+                chain: "mainnet".to_string(),
+                chain_params_file: None,
+            },
+            config
+        );
+        assert_eq!(Ok(()), config.validate());
+    }
+
+    #[test]
+    fn test_from_toml_rejects_unknown_settings() {
+        let err = Config::from_toml("minning = true").unwrap_err();
+        assert!(err.contains("unknown field `minning`"), "{}", err);
+    }
+
+    #[test]
+    fn test_apply_env() {
+        let mut config = Config::default();
+        config
+            .apply_env(vars(&[
+                ("HOME", "/root"),
+                ("BLOCKCHAIN_MINING", "true"),
+                ("BLOCKCHAIN_PEERS", "a:1, b:2,"),
+            ]))
+            .unwrap();
+
+        assert!(config.mining);
+        assert_eq!(vec!["a:1".to_string(), "b:2".to_string()], config.peers);
+
+        assert_eq!(
+            Err("invalid BLOCKCHAIN_MINING: provided string was not `true` or `false`".to_string()),
+            config.apply_env(vars(&[("BLOCKCHAIN_MINING", "yes")]))
+        );
+        assert_eq!(
+            Err("unknown setting BLOCKCHAIN_MINNING".to_string()),
+            config.apply_env(vars(&[("BLOCKCHAIN_MINNING", "true")]))
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(Ok(()), Config::default().validate());
+
+        let mut config = Config::default();
+        config.peers.push("no-port".to_string());
+        assert_eq!(
+            Err("invalid config: peer \"no-port\" should be formatted as host:port".to_string()),
+            config.validate()
+        );
+
+        let mut config = Config::default();
+        config.rpc_bind = config.listen_addresses[0];
+        assert_eq!(
+            Err("invalid config: rpc_bind 0.0.0.0:7878 is also a listen address".to_string()),
+            config.validate()
+        );
+
+        let config = Config {
+            chain: "moonnet".to_string(),
+            ..Config::default()
+        };
+        assert_eq!(
+            Err("invalid config: unknown network moonnet".to_string()),
+            config.validate()
+        );
+
+        let config = Config {
+            chain_params_file: Some("/does/not/exist.toml".into()),
+            ..Config::default()
+        };
+        assert_eq!(
+            Err(
+                "invalid config: chain_params_file /does/not/exist.toml does not exist".to_string()
+            ),
+            config.validate()
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use crate::block::{BlockHeader, MAX_DIFFICULTY};
+use std::time::Duration;
+
+/// Time between two blocks the difficulty aims for, by default.
+pub const DEFAULT_TARGET_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Number of blocks between two difficulty adjustments, by default: about a
+/// day, with a block every minute.
+pub const DEFAULT_RETARGET_WINDOW: usize = 1440;
+
+/**
+Adjusts the difficulty of mining to the observed block times: every
+`window` blocks, the difficulty goes up by one bit if the last `window`
+blocks came more than twice as fast as targeted, and down by one bit if
+they came more than twice as slow.
+The genesis block is never part of a window: its timestamp is fixed.
+
+```
+# use crate::blockchain::difficulty::DifficultyAdjuster;
+# use std::time::Duration;
+let adjuster = DifficultyAdjuster::new(Duration::from_secs(60), 10);
+
+// 10 blocks in a minute, instead of 10 minutes
+assert_eq!(9, adjuster.retarget(8, Duration::from_secs(60)));
+assert_eq!(8, adjuster.retarget(8, Duration::from_secs(600)));
+assert_eq!(7, adjuster.retarget(8, Duration::from_secs(6000)));
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DifficultyAdjuster {
+    /// Time between two blocks the difficulty aims for.
+    pub target_interval: Duration,
+
+    /// Number of blocks between two adjustments, 0 to never adjust.
+    pub window: usize,
+}
+
+impl DifficultyAdjuster {
+    /// Constructor
+    pub fn new(target_interval: Duration, window: usize) -> Self {
+        DifficultyAdjuster {
+            target_interval,
+            window,
+        }
+    }
+
+    /// Is the difficulty adjusted for the block at that height?
+    pub fn is_retarget_height(&self, height: usize) -> bool {
+        height > self.window && height.is_multiple_of(self.window)
+    }
+
+    /// New difficulty, after `window` blocks were mined in `timespan` at the
+    /// current difficulty.
+    pub fn retarget(&self, difficulty: u32, timespan: Duration) -> u32 {
+        let expected = self.target_interval * self.window as u32;
+        if timespan < expected / 2 {
+            (difficulty + 1).min(MAX_DIFFICULTY)
+        } else if timespan > expected * 2 {
+            difficulty.saturating_sub(1)
+        } else {
+            difficulty
+        }
+    }
+
+    /// Difficulty of the block following these headers, before the minimum
+    /// difficulty of the chain is applied.
+    pub fn next_difficulty(&self, headers: &[BlockHeader]) -> u32 {
+        match headers.last() {
+            Some(last) => self.difficulty_at(headers.len(), last, |height| headers.get(height)),
+            None => 0,
+        }
+    }
+
+    /// Difficulty of the block at `height`, following `last`, before the
+    /// minimum difficulty of the chain is applied. `ancestor` finds the
+    /// header at a lower height of the same chain, e.g. for light clients
+    /// that only follow headers.
+    pub fn difficulty_at<'a, F>(&self, height: usize, last: &BlockHeader, ancestor: F) -> u32
+    where
+        F: FnOnce(usize) -> Option<&'a BlockHeader>,
+    {
+        if !self.is_retarget_height(height) {
+            return last.difficulty;
+        }
+        let first = match ancestor(height - 1 - self.window) {
+            Some(first) => first,
+            None => return last.difficulty,
+        };
+
+        // a clock going backwards counts as blocks coming instantly
+        let timespan = last
+            .timestamp
+            .duration_since(first.timestamp)
+            .unwrap_or_default();
+        self.retarget(last.difficulty, timespan)
+    }
+}
+
+impl Default for DifficultyAdjuster {
+    fn default() -> Self {
+        Self::new(DEFAULT_TARGET_INTERVAL, DEFAULT_RETARGET_WINDOW)
+    }
+}
+
+#[cfg(test)]
+mod difficulty_tests {
+    use super::*;
+    use crate::block::Block;
+    use std::time::UNIX_EPOCH;
+
+    /// Headers of blocks mined at the given difficulty, `interval` apart.
+    fn headers(count: usize, difficulty: u32, interval: Duration) -> Vec<BlockHeader> {
+        (0..count)
+            .map(|i| {
+                let mut block = Block::new();
+                block.header.timestamp = UNIX_EPOCH + interval * i as u32;
+                block.header.difficulty = difficulty;
+                block.header
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_next_difficulty() {
+        let adjuster = DifficultyAdjuster::new(Duration::from_secs(60), 4);
+        assert_eq!(0, adjuster.next_difficulty(&[]));
+
+        // only retarget once a full window follows genesis
+        let fast = headers(4, 3, Duration::from_secs(1));
+        assert_eq!(3, adjuster.next_difficulty(&fast));
+        let fast = headers(8, 3, Duration::from_secs(1));
+        assert_eq!(4, adjuster.next_difficulty(&fast));
+        assert_eq!(3, adjuster.next_difficulty(&fast[..7]));
+
+        let on_time = headers(8, 3, Duration::from_secs(60));
+        assert_eq!(3, adjuster.next_difficulty(&on_time));
+
+        let slow = headers(8, 3, Duration::from_secs(600));
+        assert_eq!(2, adjuster.next_difficulty(&slow));
+        let slow = headers(8, 0, Duration::from_secs(600));
+        assert_eq!(0, adjuster.next_difficulty(&slow));
+    }
+
+    #[test]
+    fn test_retarget_is_bounded() {
+        let adjuster = DifficultyAdjuster::default();
+        assert_eq!(
+            MAX_DIFFICULTY,
+            adjuster.retarget(MAX_DIFFICULTY, Duration::ZERO)
+        );
+        assert!(!DifficultyAdjuster::new(Duration::from_secs(1), 0).is_retarget_height(10));
+    }
+}
@@ -0,0 +1,209 @@
+use crate::Error;
+use std::convert::{TryFrom, TryInto};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Version of the canonical encoding, leading every encoded transaction.
+pub const CANONICAL_VERSION: u8 = 1;
+
+/// Append a `u32`, big endian.
+pub fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+/// Append a `u64`, big endian.
+pub fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+/// Append bytes, prefixed with their length as a `u64`.
+pub fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    put_u64(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// Append a `0` when there are no bytes, or a `1` followed by the
+/// length-prefixed bytes.
+pub fn put_optional_bytes<B: AsRef<[u8]>>(out: &mut Vec<u8>, bytes: Option<B>) {
+    match bytes {
+        None => out.push(0),
+        Some(bytes) => {
+            out.push(1);
+            put_bytes(out, bytes.as_ref());
+        }
+    }
+}
+
+/// Append a point in time: seconds since the UNIX epoch as a `u64`, then
+/// nanoseconds as a `u32`. Times before the epoch are encoded as the epoch.
+pub fn put_time(out: &mut Vec<u8>, time: SystemTime) {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    put_u64(out, since_epoch.as_secs());
+    put_u32(out, since_epoch.subsec_nanos());
+}
+
+/**
+Reads back, in order, the values appended by the `put_*` functions.
+
+```
+# use crate::blockchain::encoding::{put_bytes, put_u32, Reader};
+let mut out = vec![];
+put_u32(&mut out, 7);
+put_bytes(&mut out, b"hi");
+
+let mut reader = Reader::new(&out);
+assert_eq!(Ok(7), reader.get_u32());
+assert_eq!(Ok(&b"hi"[..]), reader.get_bytes());
+assert_eq!(Ok(()), reader.finish());
+```
+*/
+#[derive(Debug, Clone)]
+pub struct Reader<'a> {
+    /// Bytes not read yet.
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Constructor
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes }
+    }
+
+    /// Read the next `len` bytes, as is.
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < len {
+            return Err("unexpected end of data".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    /// Read a byte.
+    pub fn get_u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Read a `u32`, big endian.
+    pub fn get_u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.take(std::mem::size_of::<u32>())?;
+        Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Read a `u64`, big endian.
+    pub fn get_u64(&mut self) -> Result<u64, Error> {
+        let bytes = self.take(std::mem::size_of::<u64>())?;
+        Ok(u64::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Read bytes prefixed with their length.
+    pub fn get_bytes(&mut self) -> Result<&'a [u8], Error> {
+        let len = usize::try_from(self.get_u64()?).map_err(|_| "invalid length")?;
+        self.take(len)
+    }
+
+    /// Read bytes written by `put_optional_bytes`.
+    pub fn get_optional_bytes(&mut self) -> Result<Option<&'a [u8]>, Error> {
+        match self.get_u8()? {
+            0 => Ok(None),
+            1 => self.get_bytes().map(Some),
+            tag => Err(format!("invalid option tag {}", tag)),
+        }
+    }
+
+    /// Read UTF-8 text prefixed with its length.
+    pub fn get_string(&mut self) -> Result<String, Error> {
+        let bytes = self.get_bytes()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| "invalid UTF-8".to_string())
+    }
+
+    /// Read UTF-8 text written by `put_optional_bytes`.
+    pub fn get_optional_string(&mut self) -> Result<Option<String>, Error> {
+        match self.get_optional_bytes()? {
+            Some(bytes) => String::from_utf8(bytes.to_vec())
+                .map(Some)
+                .map_err(|_| "invalid UTF-8".to_string()),
+            None => Ok(None),
+        }
+    }
+
+    /// Read a point in time.
+    pub fn get_time(&mut self) -> Result<SystemTime, Error> {
+        let secs = self.get_u64()?;
+        let nanos = self.get_u32()?;
+        if nanos >= 1_000_000_000 {
+            return Err("invalid nanoseconds".to_string());
+        }
+        UNIX_EPOCH
+            .checked_add(Duration::new(secs, nanos))
+            .ok_or_else(|| "time out of range".to_string())
+    }
+
+    /// Check that everything was read.
+    pub fn finish(self) -> Result<(), Error> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err("trailing bytes".to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod encoding_tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_encoding() {
+        let mut out = vec![];
+        put_u32(&mut out, 0x0102_0304);
+        put_bytes(&mut out, b"hi");
+        put_optional_bytes::<&[u8]>(&mut out, None);
+        put_optional_bytes(&mut out, Some(b"!"));
+        put_time(&mut out, UNIX_EPOCH + Duration::new(1, 2));
+
+        assert_eq!(
+            vec![
+                1, 2, 3, 4, //
+                0, 0, 0, 0, 0, 0, 0, 2, b'h', b'i', //
+                0,    //
+                1, 0, 0, 0, 0, 0, 0, 0, 1, b'!', //
+                0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 2,
+            ],
+            out
+        );
+
+        let mut before_epoch = vec![];
+        put_time(&mut before_epoch, UNIX_EPOCH - Duration::from_secs(1));
+        assert_eq!(vec![0; 12], before_epoch);
+    }
+
+    #[test]
+    fn test_reader() {
+        let mut out = vec![];
+        put_u64(&mut out, u64::MAX);
+        put_optional_bytes::<&[u8]>(&mut out, None);
+        put_optional_bytes(&mut out, Some("é"));
+        put_time(&mut out, UNIX_EPOCH + Duration::new(1, 2));
+
+        let mut reader = Reader::new(&out);
+        assert_eq!(Ok(u64::MAX), reader.get_u64());
+        assert_eq!(Ok(None), reader.get_optional_bytes());
+        assert_eq!(Ok(Some("é".to_string())), reader.get_optional_string());
+        assert_eq!(Ok(UNIX_EPOCH + Duration::new(1, 2)), reader.get_time());
+        assert_eq!(Ok(()), reader.finish());
+
+        assert!(Reader::new(&out[..3]).get_u64().is_err());
+        assert_eq!(
+            Err("invalid option tag 2".to_string()),
+            Reader::new(&[2]).get_optional_bytes()
+        );
+        assert_eq!(
+            Err("trailing bytes".to_string()),
+            Reader::new(&[0]).finish()
+        );
+        let mut huge = vec![];
+        put_u64(&mut huge, u64::MAX);
+        assert!(Reader::new(&huge).get_bytes().is_err());
+    }
+}
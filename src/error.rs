@@ -0,0 +1,344 @@
+use crate::account::Amount;
+use crate::id::Id;
+use crate::transaction::MAX_MEMO_LENGTH;
+use crate::{Hash, Nonce};
+use std::fmt;
+
+/**
+Why a transaction, a block or the chain was rejected, to be matched on.
+
+```
+# use crate::blockchain::blockchain::Blockchain;
+# use crate::blockchain::error::BlockchainError;
+# use crate::blockchain::world::WorldState;
+let chain = Blockchain::new();
+
+match chain.get_account_by_id(&"nobody".into()) {
+    Err(BlockchainError::AccountNotFound(id)) => assert_eq!("nobody", id.to_string()),
+    _ => unreachable!(),
+}
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockchainError {
+    /// No account has that ID.
+    AccountNotFound(Id),
+
+    /// An account with that ID already exists.
+    AccountAlreadyExists(Id),
+
+    /// The ID of a new account is not the hash of its public key.
+    AccountIdMismatch,
+
+    /// The transaction needs a sender, but has none.
+    MissingSender,
+
+    /// The sender is not allowed to send tokens, e.g. a contract account.
+    CannotSend,
+
+    /// The sender is frozen.
+    AccountFrozen,
+
+    /// Only the system authority can send this transaction.
+    NotAuthority,
+
+    /// The sender doesn't hold enough tokens.
+    InsufficientFunds {
+        /// Tokens the sender holds.
+        available: Amount,
+        /// Tokens the transaction needs.
+        required: Amount,
+    },
+
+    /// An amount of tokens doesn't fit in an `Amount`.
+    Overflow,
+
+    /// The amount sent is below the minimum transfer.
+    BelowMinimumTransfer,
+
+    /// The transfer would leave an account with a dust balance.
+    DustBalance,
+
+    /// The memo is longer than `MAX_MEMO_LENGTH`.
+    MemoTooLong,
+
+    /// The metadata entry breaks the limits on metadata.
+    InvalidMetadata(String),
+
+    /// The name cannot be registered.
+    InvalidName(String),
+
+    /// The name is registered by another account.
+    NameAlreadyRegistered,
+
+    /// The sender doesn't own the name.
+    NameNotOwned,
+
+    /// The registration would expire beyond the highest height.
+    RegistrationTooLong,
+
+    /// The transaction has no signature, but its sender owns a key.
+    MissingSignature,
+
+    /// The public key is not a valid ed25519 key.
+    InvalidPublicKey,
+
+    /// The signature doesn't match the transaction and the public key.
+    InvalidSignature,
+
+    /// The hash of the block doesn't match its content.
+    InvalidHash,
+
+    /// The hash of the block doesn't meet its difficulty.
+    InsufficientProofOfWork,
+
+    /// The Merkle root of the block doesn't match its transactions.
+    InvalidMerkleRoot,
+
+    /// The bloom filter of the block doesn't match its transactions.
+    InvalidBloom,
+
+    /// The block doesn't follow the tip of the chain.
+    InvalidPreviousHash {
+        /// Hash of the tip of the chain.
+        expected: Option<Hash>,
+        /// Previous hash of the block.
+        got: Option<Hash>,
+    },
+
+    /// The block is not at the height it claims.
+    InvalidHeight {
+        /// Height of the block in the chain.
+        expected: usize,
+        /// Height in the header of the block.
+        got: usize,
+    },
+
+    /// The block is older than the block it follows.
+    BlockBeforeParent,
+
+    /// The block is further in the future than the clock drift allows.
+    BlockInFuture,
+
+    /// The block was mined at another difficulty than the chain's.
+    WrongDifficulty {
+        /// Difficulty of the chain.
+        expected: u32,
+        /// Difficulty of the block.
+        got: u32,
+    },
+
+    /// A transaction of the block was rejected.
+    InvalidTransaction {
+        /// Position of the transaction in the block.
+        index: usize,
+        /// Why it was rejected.
+        error: Box<BlockchainError>,
+    },
+
+    /// A block was rejected.
+    InvalidBlock {
+        /// Position of the block, in the blocks being added or in the chain.
+        index: usize,
+        /// Why it was rejected.
+        error: Box<BlockchainError>,
+    },
+
+    /// Creating tokens would exceed the maximum supply.
+    MaxSupplyExceeded,
+
+    /// Accounts don't hold exactly the tokens that were minted.
+    SupplyMismatch {
+        /// Tokens held by all the accounts.
+        held: Amount,
+        /// Tokens minted since genesis.
+        minted: Amount,
+    },
+
+    /// Rules can only change from a future height on.
+    ActivationHeightInPast,
+
+    /// The genesis configuration is invalid.
+    InvalidGenesis(String),
+
+    /// The genesis configuration doesn't match the first block of the chain.
+    GenesisMismatch,
+
+    /// The world state or the chain could not be read or written.
+    Storage(String),
+
+    /// The transaction is already in the chain.
+    AlreadyIncluded,
+
+    /// The sender already has a pending transaction with this nonce.
+    NonceAlreadyUsed,
+
+    /// The transaction doesn't use the next nonce of its sender.
+    InvalidNonce {
+        /// Next nonce of the sender.
+        expected: Nonce,
+        /// Nonce of the transaction.
+        got: Nonce,
+    },
+
+    /// The mempool refused the transaction, e.g. because it is full.
+    MempoolRejected(String),
+
+    /// The transactions of the block pay fees, but nobody is to receive them.
+    MissingBeneficiary,
+
+    /// The sender has no key to check the signature of its transactions.
+    MissingPublicKey,
+}
+
+impl fmt::Display for BlockchainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hex = |hash: &Option<Hash>| match hash {
+            Some(hash) => hash.iter().map(|byte| format!("{:02x}", byte)).collect(),
+            None => "none".to_string(),
+        };
+
+        match self {
+            BlockchainError::AccountNotFound(id) => write!(f, "account {} doesn't exist", id),
+            BlockchainError::AccountAlreadyExists(id) => {
+                write!(f, "account {} already exists", id)
+            }
+            BlockchainError::AccountIdMismatch => {
+                write!(f, "account ID is not the hash of its public key")
+            }
+            BlockchainError::MissingSender => write!(f, "missing from account"),
+            BlockchainError::CannotSend => write!(f, "account cannot send tokens"),
+            BlockchainError::AccountFrozen => write!(f, "account is frozen"),
+            BlockchainError::NotAuthority => {
+                write!(f, "only the system authority can freeze accounts")
+            }
+            BlockchainError::InsufficientFunds {
+                available,
+                required,
+            } => write!(
+                f,
+                "not enough tokens: {} available, {} required",
+                available, required
+            ),
+            BlockchainError::Overflow => write!(f, "too many tokens"),
+            BlockchainError::BelowMinimumTransfer => {
+                write!(f, "amount is below the minimum transfer")
+            }
+            BlockchainError::DustBalance => write!(f, "would leave a dust balance"),
+            BlockchainError::MemoTooLong => {
+                write!(f, "memo must be at most {} bytes long", MAX_MEMO_LENGTH)
+            }
+            BlockchainError::InvalidMetadata(err) | BlockchainError::InvalidName(err) => {
+                write!(f, "{}", err)
+            }
+            BlockchainError::NameAlreadyRegistered => write!(f, "name already registered"),
+            BlockchainError::NameNotOwned => write!(f, "name is not owned by the sender"),
+            BlockchainError::RegistrationTooLong => write!(f, "registration is too long"),
+            BlockchainError::MissingSignature => write!(f, "missing signature"),
+            BlockchainError::InvalidPublicKey => write!(f, "invalid public key"),
+            BlockchainError::InvalidSignature => write!(f, "invalid signature"),
+            BlockchainError::InvalidHash => write!(f, "invalid hash"),
+            BlockchainError::InsufficientProofOfWork => {
+                write!(f, "hash doesn't meet the difficulty")
+            }
+            BlockchainError::InvalidMerkleRoot => write!(f, "invalid merkle root"),
+            BlockchainError::InvalidBloom => write!(f, "invalid bloom filter"),
+            BlockchainError::InvalidPreviousHash { expected, got } => write!(
+                f,
+                "invalid previous hash: expected {}, got {}",
+                hex(expected),
+                hex(got)
+            ),
+            BlockchainError::InvalidHeight { expected, got } => {
+                write!(f, "invalid height: expected {}, got {}", expected, got)
+            }
+            BlockchainError::BlockBeforeParent => write!(f, "block is older than its parent"),
+            BlockchainError::BlockInFuture => write!(f, "block is too far in the future"),
+            BlockchainError::WrongDifficulty { expected, got } => {
+                write!(f, "wrong difficulty: expected {}, got {}", expected, got)
+            }
+            BlockchainError::InvalidTransaction { index, error } => {
+                write!(f, "invalid transaction {}: {}", index, error)
+            }
+            BlockchainError::InvalidBlock { index, error } => {
+                write!(f, "invalid block {}: {}", index, error)
+            }
+            BlockchainError::MaxSupplyExceeded => write!(f, "maximum supply exceeded"),
+            BlockchainError::SupplyMismatch { held, minted } => write!(
+                f,
+                "accounts hold {} tokens, but {} were minted",
+                held, minted
+            ),
+            BlockchainError::ActivationHeightInPast => {
+                write!(f, "activation height must be in the future")
+            }
+            BlockchainError::InvalidGenesis(err) => write!(f, "invalid genesis: {}", err),
+            BlockchainError::GenesisMismatch => write!(f, "genesis doesn't match the chain"),
+            BlockchainError::Storage(err) => write!(f, "storage error: {}", err),
+            BlockchainError::AlreadyIncluded => write!(f, "transaction already included"),
+            BlockchainError::NonceAlreadyUsed => {
+                write!(f, "nonce already used by a pending transaction")
+            }
+            BlockchainError::InvalidNonce { expected, got } => {
+                write!(f, "invalid nonce: expected {}, got {}", expected, got)
+            }
+            BlockchainError::MempoolRejected(err) => write!(f, "rejected by the mempool: {}", err),
+            BlockchainError::MissingBeneficiary => {
+                write!(f, "block collects fees but has no beneficiary")
+            }
+            BlockchainError::MissingPublicKey => write!(f, "sender has no public key"),
+        }
+    }
+}
+
+impl std::error::Error for BlockchainError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BlockchainError::InvalidTransaction { error, .. }
+            | BlockchainError::InvalidBlock { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Modules reporting errors as messages can propagate a `BlockchainError`
+/// with `?`.
+impl From<BlockchainError> for String {
+    fn from(err: BlockchainError) -> Self {
+        err.to_string()
+    }
+}
+
+#[cfg(test)]
+mod error_tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn test_display() {
+        let err = BlockchainError::InvalidBlock {
+            index: 2,
+            error: Box::new(BlockchainError::InvalidTransaction {
+                index: 0,
+                error: Box::new(BlockchainError::InsufficientFunds {
+                    available: 5,
+                    required: 10,
+                }),
+            }),
+        };
+        assert_eq!(
+            "invalid block 2: invalid transaction 0: not enough tokens: 5 available, 10 required",
+            err.to_string()
+        );
+        assert!(err.source().unwrap().source().is_some());
+
+        let err = BlockchainError::InvalidPreviousHash {
+            expected: Some(vec![0xab, 0x01]),
+            got: None,
+        };
+        assert_eq!(
+            "invalid previous hash: expected ab01, got none",
+            String::from(err)
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use crate::Hash;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+/// Number of blocks on top of a block for it to be considered safe: it is
+/// unlikely to be reverted.
+pub const SAFE_DEPTH: usize = 6;
+/// Number of blocks on top of a block for it to be considered final.
+pub const FINALIZED_DEPTH: usize = 32;
+
+/// Something that happened to the chain, that other components may react to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainEvent {
+    /// A block has been added to the chain.
+    BlockAdded {
+        /// Height of the block.
+        height: usize,
+        /// Hash of the block.
+        hash: Hash,
+    },
+
+    /// A block has reached `SAFE_DEPTH` blocks of confirmations.
+    BlockSafe {
+        /// Height of the block.
+        height: usize,
+        /// Hash of the block.
+        hash: Hash,
+    },
+
+    /// A block has reached `FINALIZED_DEPTH` blocks of confirmations.
+    BlockFinalized {
+        /// Height of the block.
+        height: usize,
+        /// Hash of the block.
+        hash: Hash,
+    },
+
+    /// A transaction has been added to the mempool.
+    TxAdded {
+        /// Hash of the transaction.
+        hash: Hash,
+    },
+
+    /// A transaction has been included in a block.
+    TxIncluded {
+        /// Hash of the transaction.
+        hash: Hash,
+        /// Height of the block including the transaction.
+        block_height: usize,
+    },
+}
+
+/**
+Broadcasts events to every subscriber. Cloning the bus gives another handle
+to the same subscribers, so several components can publish to it.
+
+```
+# use crate::blockchain::events::EventBus;
+let bus = EventBus::new();
+let receiver = bus.subscribe();
+
+bus.publish("hello");
+assert_eq!(Ok("hello"), receiver.try_recv());
+```
+*/
+#[derive(Debug)]
+pub struct EventBus<T> {
+    subscribers: Arc<Mutex<Vec<Sender<T>>>>,
+}
+
+impl<T: Clone> EventBus<T> {
+    /// Constructor
+    pub fn new() -> Self {
+        EventBus {
+            subscribers: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// Receive every event published from now on.
+    pub fn subscribe(&self) -> Receiver<T> {
+        let (sender, receiver) = channel();
+        self.lock().push(sender);
+        receiver
+    }
+
+    /// Send an event to all the subscribers still listening.
+    pub fn publish(&self, event: T) {
+        self.lock()
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    /// Number of subscribers still listening, as of the last publication.
+    pub fn subscriber_count(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Sender<T>>> {
+        // subscribers stay consistent even if a thread panicked while publishing
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<T> Clone for EventBus<T> {
+    fn clone(&self) -> Self {
+        EventBus {
+            subscribers: self.subscribers.clone(),
+        }
+    }
+}
+
+impl<T: Clone> Default for EventBus<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod events_tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_to_all_subscribers() {
+        let bus = EventBus::new();
+        let first = bus.subscribe();
+        let second = bus.clone().subscribe();
+
+        bus.publish(1);
+        assert_eq!(Ok(1), first.try_recv());
+        assert_eq!(Ok(1), second.try_recv());
+    }
+
+    #[test]
+    fn test_publish_drops_gone_subscribers() {
+        let bus = EventBus::new();
+        let receiver = bus.subscribe();
+        drop(bus.subscribe());
+
+        bus.publish(1);
+        assert_eq!(1, bus.subscriber_count());
+        assert_eq!(Ok(1), receiver.try_recv());
+    }
+}
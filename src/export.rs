@@ -0,0 +1,216 @@
+use crate::blockchain::Blockchain;
+use crate::transaction::TransactionRecord;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/**
+Write all the blocks of the chain as CSV, one row per block.
+
+```
+# use crate::blockchain::blockchain::Blockchain;
+# use crate::blockchain::export::export_blocks;
+let chain = Blockchain::new();
+let mut csv = vec![];
+export_blocks(&chain, &mut csv).unwrap();
+
+assert_eq!("height,hash,previous_hash,created_at,transactions\n", String::from_utf8(csv).unwrap());
+```
+*/
+pub fn export_blocks<W: Write>(chain: &Blockchain, writer: &mut W) -> std::io::Result<()> {
+    writeln!(writer, "height,hash,previous_hash,created_at,transactions")?;
+    for (height, block) in chain.iter_blocks(..).enumerate() {
+        let block = block.map_err(std::io::Error::other)?;
+        write_row(
+            writer,
+            &[
+                height.to_string(),
+                block.hash.as_deref().map(to_hex).unwrap_or_default(),
+                block
+                    .header
+                    .previous_hash
+                    .as_deref()
+                    .map(to_hex)
+                    .unwrap_or_default(),
+                format_time(block.header.timestamp),
+                block.transactions.len().to_string(),
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+/// Write all the transactions of the chain as CSV, one row per transaction.
+pub fn export_transactions<W: Write>(chain: &Blockchain, writer: &mut W) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "block_height,index,hash,nonce,from,record,to,amount,created_at"
+    )?;
+    for (height, block) in chain.iter_blocks(..).enumerate() {
+        let block = block.map_err(std::io::Error::other)?;
+        for (index, transaction) in block.iter_transactions().enumerate() {
+            let (record, to, amount) = match &transaction.record {
+                TransactionRecord::CreateUserAccount { id, .. } => {
+                    ("create_user_account", id.to_string(), None)
+                }
+                TransactionRecord::SendTokens { to, amount } => {
+                    ("send_tokens", to.to_string(), Some(amount))
+                }
+                TransactionRecord::FreezeAccount(id) => ("freeze_account", id.to_string(), None),
+                TransactionRecord::UnfreezeAccount(id) => {
+                    ("unfreeze_account", id.to_string(), None)
+                }
+                TransactionRecord::SetMetadata { key, .. } => ("set_metadata", key.clone(), None),
+                TransactionRecord::RegisterName { name } => ("register_name", name.clone(), None),
+                TransactionRecord::TransferName { to, .. } => {
+                    ("transfer_name", to.to_string(), None)
+                }
+                TransactionRecord::ScheduleParams { height, .. } => {
+                    ("schedule_params", height.to_string(), None)
+                }
+            };
+
+            write_row(
+                writer,
+                &[
+                    height.to_string(),
+                    index.to_string(),
+                    to_hex(&transaction.calculate_hash()),
+                    transaction.nonce.to_string(),
+                    transaction
+                        .from_account_id
+                        .as_ref()
+                        .map(ToString::to_string)
+                        .unwrap_or_default(),
+                    record.to_string(),
+                    to,
+                    amount.map(ToString::to_string).unwrap_or_default(),
+                    format_time(transaction.created_at),
+                ],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a snapshot of the balance of every account as CSV, richest first.
+pub fn export_balances<W: Write>(chain: &Blockchain, writer: &mut W) -> std::io::Result<()> {
+    writeln!(writer, "account,tokens")?;
+    for (id, account) in chain.top_accounts(usize::MAX) {
+        write_row(writer, &[id.to_string(), account.tokens.to_string()])?;
+    }
+    Ok(())
+}
+
+/// Write a CSV row, quoting fields when needed.
+fn write_row<W: Write>(writer: &mut W, fields: &[String]) -> std::io::Result<()> {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            if field.contains(&[',', '"', '\n', '\r'][..]) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_owned()
+            }
+        })
+        .collect();
+    writeln!(writer, "{}", fields.join(","))
+}
+
+/// Lowercase hexadecimal representation of some bytes.
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Seconds since the UNIX epoch, with nanosecond precision.
+fn format_time(time: SystemTime) -> String {
+    let elapsed = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("{}.{:09}", elapsed.as_secs(), elapsed.subsec_nanos())
+}
+
+#[cfg(test)]
+mod export_tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::clock::MockClock;
+    use crate::id::Id;
+    use crate::transaction::Transaction;
+    use crate::world::WorldState;
+    use std::time::Duration;
+
+    fn chain() -> Blockchain {
+        let mut chain = Blockchain::new();
+        chain.add_account("someone, with a comma".into()).unwrap();
+        chain
+            .get_account_by_id_mut(&"someone, with a comma".into())
+            .unwrap()
+            .tokens = 50;
+
+        let clock = MockClock::new(UNIX_EPOCH);
+        let transaction = Transaction::with_clock(
+            3,
+            TransactionRecord::create_user_account(b"someone"),
+            None,
+            &clock,
+        );
+        clock.advance(Duration::from_millis(1500));
+        let mut block = Block::with_clock(&clock);
+        block.transactions.push(transaction);
+        block.seal();
+        chain.add_block(block).unwrap();
+        chain
+    }
+
+    #[test]
+    fn test_export_blocks() {
+        let chain = chain();
+        let hash = to_hex(
+            chain
+                .iter_blocks(..)
+                .next()
+                .unwrap()
+                .unwrap()
+                .hash
+                .as_ref()
+                .unwrap(),
+        );
+
+        let mut csv = vec![];
+        export_blocks(&chain, &mut csv).unwrap();
+        assert_eq!(
+            format!(
+                "height,hash,previous_hash,created_at,transactions\n0,{},,1.500000000,1\n",
+                hash
+            ),
+            String::from_utf8(csv).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_export_transactions() {
+        let chain = chain();
+        let block = chain.iter_blocks(..).next().unwrap().unwrap();
+        let hash = to_hex(&block.transactions[0].calculate_hash());
+
+        let mut csv = vec![];
+        export_transactions(&chain, &mut csv).unwrap();
+        assert_eq!(
+            format!(
+                "block_height,index,hash,nonce,from,record,to,amount,created_at\n\
+                 0,0,{},3,,create_user_account,{},,0.000000000\n",
+                hash,
+                Id::from_public_key(b"someone")
+            ),
+            String::from_utf8(csv).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_export_balances_quotes_fields() {
+        let mut csv = vec![];
+        export_balances(&chain(), &mut csv).unwrap();
+        assert_eq!(
+            "account,tokens\n\"someone, with a comma\",50\n",
+            String::from_utf8(csv).unwrap()
+        );
+    }
+}
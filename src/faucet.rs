@@ -0,0 +1,200 @@
+use crate::account::{Amount, Keypair};
+use crate::blockchain::Blockchain;
+use crate::clock::{Clock, SystemClock};
+use crate::id::Id;
+use crate::storage::ChainStore;
+use crate::transaction::{Transaction, TransactionRecord};
+use crate::world::WorldState;
+use crate::{Error, Hash};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/**
+Hands out tokens from a funded account, on chains whose parameters allow a
+faucet. Each address can only be funded once per cooldown, and only existing
+accounts can be funded.
+
+```
+# use crate::blockchain::account::generate_keypair;
+# use crate::blockchain::blockchain::Blockchain;
+# use crate::blockchain::faucet::Faucet;
+# use crate::blockchain::genesis::GenesisConfig;
+# use crate::blockchain::id::Id;
+# use crate::blockchain::params::ChainParams;
+# use crate::blockchain::rng::OsRng;
+# use std::time::Duration;
+let keypair = generate_keypair(&OsRng);
+let genesis = GenesisConfig {
+    params: ChainParams { faucet: true, ..ChainParams::default() },
+    ..GenesisConfig::default()
+}
+.with_allocation(keypair.verifying_key().as_bytes(), 1_000_000)
+.with_allocation(b"someone", 0);
+let mut chain = Blockchain::from_genesis(&genesis).unwrap();
+let someone = Id::from_public_key(b"someone");
+
+let mut faucet = Faucet::new(keypair, 100, Duration::from_secs(3600));
+faucet.drip(&mut chain, &someone).unwrap();
+assert!(faucet.drip(&mut chain, &someone).is_err());
+```
+*/
+#[derive(Debug)]
+pub struct Faucet {
+    /// Key of the account the tokens are sent from, signing the transfers.
+    keypair: Keypair,
+
+    /// Account the tokens are sent from.
+    account: Id,
+
+    /// Number of tokens sent by each drip.
+    amount: Amount,
+
+    /// Time before the same address can be funded again.
+    cooldown: Duration,
+
+    /// When each address was last funded.
+    last_drips: HashMap<Id, SystemTime>,
+}
+
+impl Faucet {
+    /// Constructor, sending tokens from the account owned by the keypair.
+    pub fn new(keypair: Keypair, amount: Amount, cooldown: Duration) -> Self {
+        Faucet {
+            account: Id::from_public_key(keypair.verifying_key().as_bytes()),
+            keypair,
+            amount,
+            cooldown,
+            last_drips: HashMap::new(),
+        }
+    }
+
+    /// Submit a transfer funding the address to the chain, and return the
+    /// hash of the transaction. The transfer uses the nonce following the
+    /// pending transactions of the faucet account, so a faucet created again
+    /// after a restart carries on where the previous one stopped.
+    pub fn drip<S: ChainStore, K: Clock>(
+        &mut self,
+        chain: &mut Blockchain<S, K>,
+        to: &Id,
+    ) -> Result<Hash, Error> {
+        self.drip_with_clock(chain, to, &SystemClock)
+    }
+
+    /// Same as `drip`, telling the time with the given clock.
+    pub fn drip_with_clock<S: ChainStore, K: Clock, C: Clock>(
+        &mut self,
+        chain: &mut Blockchain<S, K>,
+        to: &Id,
+        clock: &C,
+    ) -> Result<Hash, Error> {
+        if !chain.params().faucet {
+            return Err("the faucet is disabled on this chain".to_string());
+        }
+
+        let now = clock.now();
+        if let Some(last_drip) = self.last_drips.get(to) {
+            if now < *last_drip + self.cooldown {
+                return Err("address was funded recently".to_string());
+            }
+        }
+
+        let mut transaction = Transaction::with_clock(
+            chain.next_nonce(&self.account)?,
+            TransactionRecord::SendTokens {
+                to: to.clone(),
+                amount: self.amount,
+            },
+            Some(self.account.clone()),
+            clock,
+        );
+        transaction.sign(&self.keypair);
+        let hash = transaction.calculate_hash();
+        chain.submit_transaction(transaction)?;
+
+        self.last_drips.insert(to.clone(), now);
+        tracing::debug!(%to, amount = self.amount, "faucet drip queued");
+        Ok(hash)
+    }
+}
+
+#[cfg(test)]
+mod faucet_tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::genesis::GenesisConfig;
+    use crate::params::ChainParams;
+    use crate::testing::{account_id, keypair};
+    use crate::Nonce;
+    use std::time::UNIX_EPOCH;
+
+    fn chain(faucet: bool) -> Blockchain {
+        let genesis = GenesisConfig {
+            params: ChainParams {
+                faucet,
+                ..ChainParams::default()
+            },
+            ..GenesisConfig::default()
+        }
+        .with_allocation(keypair("faucet").verifying_key().as_bytes(), 1000)
+        .with_allocation(keypair("alice").verifying_key().as_bytes(), 0)
+        .with_allocation(keypair("bob").verifying_key().as_bytes(), 0);
+        Blockchain::from_genesis(&genesis).unwrap()
+    }
+
+    #[test]
+    fn test_drip_rate_limit() {
+        let mut chain = chain(true);
+        let clock = MockClock::new(UNIX_EPOCH);
+        let mut faucet = Faucet::new(keypair("faucet"), 10, Duration::from_secs(60));
+        let alice = account_id("alice");
+
+        assert!(faucet.drip_with_clock(&mut chain, &alice, &clock).is_ok());
+        assert!(faucet
+            .drip_with_clock(&mut chain, &account_id("bob"), &clock)
+            .is_ok());
+
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(
+            Err("address was funded recently".to_string()),
+            faucet.drip_with_clock(&mut chain, &alice, &clock)
+        );
+
+        clock.advance(Duration::from_secs(1));
+        assert!(faucet.drip_with_clock(&mut chain, &alice, &clock).is_ok());
+
+        let nonces: Vec<Nonce> = chain.mempool().iter().map(|t| t.nonce).collect();
+        assert_eq!(vec![1, 2, 3], nonces);
+    }
+
+    #[test]
+    fn test_drip_disabled() {
+        let mut chain = chain(false);
+        let mut faucet = Faucet::new(keypair("faucet"), 10, Duration::from_secs(60));
+
+        assert_eq!(
+            Err("the faucet is disabled on this chain".to_string()),
+            faucet.drip(&mut chain, &account_id("alice"))
+        );
+        assert!(chain.mempool().is_empty());
+    }
+
+    #[test]
+    fn test_drip_after_rejected_drip() {
+        let mut chain = chain(true);
+        let mut faucet = Faucet::new(keypair("faucet"), 10, Duration::from_secs(60));
+
+        assert!(faucet.drip(&mut chain, &account_id("alice")).is_ok());
+        assert_eq!(
+            Err("account nobody doesn't exist".to_string()),
+            faucet.drip(&mut chain, &"nobody".into())
+        );
+        let mut expensive = Faucet::new(keypair("faucet"), 10_000, Duration::from_secs(60));
+        assert!(expensive.drip(&mut chain, &account_id("bob")).is_err());
+
+        // a faucet created again carries on after the pending drip
+        let mut faucet = Faucet::new(keypair("faucet"), 10, Duration::from_secs(60));
+        assert!(faucet.drip(&mut chain, &account_id("bob")).is_ok());
+
+        assert_eq!(2, chain.drain_valid(10).len());
+    }
+}
@@ -0,0 +1,218 @@
+use crate::account::{Amount, Keypair};
+use crate::blockchain::Blockchain;
+use crate::genesis::GenesisConfig;
+use crate::id::Id;
+use crate::transaction::{Transaction, TransactionRecord};
+use crate::world::WorldState;
+use crate::{Error, Nonce};
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+
+/// Returned by functions that succeeded.
+pub const BLOCKCHAIN_OK: c_int = 0;
+/// Returned by functions that failed.
+pub const BLOCKCHAIN_ERROR: c_int = -1;
+
+/// Start an empty chain. Free it with `blockchain_free`.
+#[no_mangle]
+pub extern "C" fn blockchain_new() -> *mut Blockchain {
+    Box::into_raw(Box::new(Blockchain::new()))
+}
+
+/// Start a chain from a genesis configuration, in TOML. Free it with
+/// `blockchain_free`. Returns a null pointer if the configuration is invalid.
+///
+/// # Safety
+///
+/// `genesis` must be a valid, nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn blockchain_from_genesis(genesis: *const c_char) -> *mut Blockchain {
+    let chain = to_str(genesis)
+        .and_then(GenesisConfig::from_toml)
+        .and_then(|genesis| Blockchain::from_genesis(&genesis).map_err(String::from));
+    match chain {
+        Ok(chain) => Box::into_raw(Box::new(chain)),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Free a chain created by `blockchain_new` or `blockchain_from_genesis`.
+///
+/// # Safety
+///
+/// `chain` must be null, or a chain that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn blockchain_free(chain: *mut Blockchain) {
+    if !chain.is_null() {
+        drop(Box::from_raw(chain));
+    }
+}
+
+/// Number of blocks in the chain.
+///
+/// # Safety
+///
+/// `chain` must be a valid chain.
+#[no_mangle]
+pub unsafe extern "C" fn blockchain_height(chain: *const Blockchain) -> usize {
+    (*chain).height()
+}
+
+/// Sign a transaction sending tokens from the account owned by a secret key,
+/// and queue it in the mempool if it is valid against the current state.
+///
+/// # Safety
+///
+/// `chain` must be a valid chain, `secret_key` point to the 32 bytes of an
+/// ed25519 secret key and `to` be a valid, nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn blockchain_submit_transfer(
+    chain: *mut Blockchain,
+    nonce: Nonce,
+    secret_key: *const u8,
+    to: *const c_char,
+    amount: Amount,
+) -> c_int {
+    let submit = || -> Result<(), Error> {
+        if secret_key.is_null() {
+            return Err("null secret key".to_string());
+        }
+        let keypair = Keypair::from_bytes(&*secret_key.cast());
+        let mut transaction = Transaction::new(
+            nonce,
+            TransactionRecord::SendTokens {
+                to: to_str(to)?.into(),
+                amount,
+            },
+            Some(Id::from_public_key(keypair.verifying_key().as_bytes())),
+        );
+        transaction.sign(&keypair);
+        Ok((*chain).submit_transaction(transaction)?)
+    };
+    to_code(submit())
+}
+
+/// Write the nonce the next transaction of an account must use to `nonce`.
+///
+/// # Safety
+///
+/// `chain` must be a valid chain, `id` a valid, nul-terminated string and
+/// `nonce` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn blockchain_next_nonce(
+    chain: *const Blockchain,
+    id: *const c_char,
+    nonce: *mut Nonce,
+) -> c_int {
+    let next = to_str(id).and_then(|id| (*chain).next_nonce(&id.into()).map_err(String::from));
+    to_code(next.map(|next| *nonce = next))
+}
+
+/// Write the number of tokens held by an account to `tokens`.
+///
+/// # Safety
+///
+/// `chain` must be a valid chain, `id` a valid, nul-terminated string and
+/// `tokens` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn blockchain_balance(
+    chain: *const Blockchain,
+    id: *const c_char,
+    tokens: *mut Amount,
+) -> c_int {
+    let balance =
+        to_str(id).and_then(|id| (*chain).get_account_by_id(&id.into()).map_err(String::from));
+    to_code(balance.map(|account| *tokens = account.tokens))
+}
+
+unsafe fn to_str<'a>(s: *const c_char) -> Result<&'a str, Error> {
+    if s.is_null() {
+        return Err("null string".to_string());
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| "string is not valid UTF-8".to_string())
+}
+
+fn to_code(result: Result<(), Error>) -> c_int {
+    match result {
+        Ok(()) => BLOCKCHAIN_OK,
+        Err(_) => BLOCKCHAIN_ERROR,
+    }
+}
+
+#[cfg(test)]
+mod ffi_tests {
+    use super::*;
+    use crate::testing::{account_id, keypair};
+    use std::ffi::CString;
+
+    #[test]
+    fn test_ffi() {
+        let allocation = |name: &str, tokens| {
+            let public_key = keypair(name).verifying_key();
+            format!(
+                "[[allocations]]\naccount = \"{}\"\npublic_key = \"{}\"\ntokens = {}\n",
+                account_id(name),
+                public_key
+                    .as_bytes()
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect::<String>(),
+                tokens
+            )
+        };
+        let genesis = CString::new(allocation("alice", 1000) + &allocation("bob", 0)).unwrap();
+        let secret_key = keypair("alice").to_bytes();
+        let alice = CString::new(account_id("alice").to_string()).unwrap();
+        let bob = CString::new(account_id("bob").to_string()).unwrap();
+        let nobody = CString::new("nobody").unwrap();
+
+        unsafe {
+            let chain = blockchain_from_genesis(genesis.as_ptr());
+            assert!(!chain.is_null());
+            assert_eq!(1, blockchain_height(chain));
+
+            let mut tokens = 0;
+            assert_eq!(
+                BLOCKCHAIN_OK,
+                blockchain_balance(chain, alice.as_ptr(), &mut tokens)
+            );
+            assert_eq!(1000, tokens);
+            assert_eq!(
+                BLOCKCHAIN_ERROR,
+                blockchain_balance(chain, nobody.as_ptr(), &mut tokens)
+            );
+
+            let mut nonce = 0;
+            assert_eq!(
+                BLOCKCHAIN_OK,
+                blockchain_next_nonce(chain, alice.as_ptr(), &mut nonce)
+            );
+            assert_eq!(
+                BLOCKCHAIN_ERROR,
+                blockchain_submit_transfer(chain, nonce, secret_key.as_ptr(), bob.as_ptr(), 10_000)
+            );
+            assert_eq!(
+                BLOCKCHAIN_ERROR,
+                blockchain_submit_transfer(chain, nonce, std::ptr::null(), bob.as_ptr(), 10)
+            );
+            assert_eq!(
+                BLOCKCHAIN_OK,
+                blockchain_submit_transfer(chain, nonce, secret_key.as_ptr(), bob.as_ptr(), 10)
+            );
+            assert_eq!(1, (*chain).drain_valid(10).len());
+
+            blockchain_free(chain);
+        }
+    }
+
+    #[test]
+    fn test_ffi_invalid_genesis() {
+        let genesis = CString::new("unknown = 1").unwrap();
+        unsafe {
+            assert!(blockchain_from_genesis(genesis.as_ptr()).is_null());
+            assert!(blockchain_from_genesis(std::ptr::null()).is_null());
+        }
+    }
+}
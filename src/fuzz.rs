@@ -0,0 +1,117 @@
+use crate::block::Block;
+use crate::config::Config;
+use crate::merkle::MerkleProof;
+use crate::testing::ChainBuilder;
+use crate::transaction::{Transaction, TransactionRecord};
+use crate::world::WorldState;
+
+/// Accounts existing in the chain used by `add_block`.
+const ACCOUNTS: [&str; 4] = ["alice", "bob", "carol", "dave"];
+
+/// Decode the Merkle proof that a transaction is in a block. Anything that
+/// decodes must encode back to the same bytes.
+pub fn inclusion_proof(data: &[u8]) {
+    if let Ok(proof) = MerkleProof::from_bytes(data) {
+        assert_eq!(Ok(data.to_vec()), proof.to_bytes());
+    }
+}
+
+/// Parse and validate a configuration file.
+pub fn config(data: &[u8]) {
+    if let Ok(content) = std::str::from_utf8(data) {
+        if let Ok(config) = Config::from_toml(content) {
+            let _ = config.validate();
+        }
+    }
+}
+
+/// Add a block built from arbitrary bytes on top of a small chain.
+/// Every 4 bytes describe a transaction: its kind, sender, receiver and
+/// amount. Whether the block is accepted or not, no tokens can be created.
+pub fn add_block(data: &[u8]) {
+    let mut chain = ChainBuilder::new()
+        .with_account(ACCOUNTS[0], 1000)
+        .with_account(ACCOUNTS[1], 100)
+        .with_account(ACCOUNTS[2], 0)
+        .build()
+        .unwrap();
+    let supply = chain.total_supply();
+
+    let account = |byte: u8| ACCOUNTS[byte as usize % ACCOUNTS.len()].into();
+
+    let mut block = Block::new();
+    block.header.height = chain.height();
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
+    for (nonce, chunk) in data.chunks_exact(4).enumerate() {
+        let (from, to, amount) = (account(chunk[1]), account(chunk[2]), chunk[3].into());
+        let transaction = match chunk[0] % 4 {
+            0 => Transaction::new(
+                nonce as u64,
+                TransactionRecord::create_user_account(&chunk[1..3]),
+                None,
+            ),
+            1 => Transaction::new(
+                nonce as u64,
+                TransactionRecord::SendTokens { to, amount },
+                Some(from),
+            ),
+            2 => Transaction::new(
+                nonce as u64,
+                TransactionRecord::SendTokens { to, amount },
+                None,
+            ),
+            _ => Transaction::new(
+                nonce as u64,
+                TransactionRecord::CreateUserAccount {
+                    id: to,
+                    public_key: chunk[1..3].to_vec(),
+                },
+                Some(from),
+            ),
+        };
+        block.transactions.push(transaction);
+    }
+    // Let the fuzzer also send blocks with a wrong hash.
+    if data.len() % 4 != 1 {
+        block.seal();
+    }
+
+    let _ = chain.add_block(block);
+    assert_eq!(supply, chain.total_supply());
+}
+
+#[cfg(test)]
+mod fuzz_tests {
+    use super::*;
+
+    #[test]
+    fn test_inclusion_proof() {
+        inclusion_proof(&[]);
+        inclusion_proof(&[1, 0, 0, 0, 0]);
+        inclusion_proof(&[1, 0, 0, 0, 1, 2, 3]);
+        inclusion_proof(&[1, 255, 255, 255, 255]);
+
+        let mut sibling = vec![1, 0, 0, 0, 1, 1];
+        sibling.extend_from_slice(&[7; 32]);
+        inclusion_proof(&sibling);
+        // neither left nor right
+        sibling[5] = 2;
+        inclusion_proof(&sibling);
+    }
+
+    #[test]
+    fn test_config() {
+        config(b"");
+        config(b"mining = true");
+        config(b"rpc_bind = 42");
+        config(&[0xff, 0xfe]);
+    }
+
+    #[test]
+    fn test_add_block() {
+        add_block(&[]);
+        add_block(&[1, 0, 1, 50, 1, 1, 2, 200]);
+        add_block(&[2, 0, 0, 255, 3, 1, 1, 255]);
+        add_block(&[0, 3, 3, 0, 1]);
+    }
+}
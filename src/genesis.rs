@@ -0,0 +1,262 @@
+use crate::account::{AccountType, Amount, PublicKey};
+use crate::block::Block;
+use crate::id::Id;
+use crate::params::ChainParams;
+use crate::Error;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+/**
+Defines the first block of a chain, and the tokens that exist from the
+start. This is the only way to create tokens.
+
+```
+# use crate::blockchain::genesis::GenesisConfig;
+let genesis = GenesisConfig::from_toml(r#"
+timestamp = 1600000000
+max_supply = 21000000
+
+[params]
+min_transfer = 1
+
+[[allocations]]
+account = "9515049071ed913149a80d3bb7891fcd4c6c1e3d14ad878939a80f9b9a91e08c"
+public_key = "0101010101010101010101010101010101010101010101010101010101010101"
+tokens = 1000
+
+[[allocations]]
+account = "e4d60b4531b114100aab5f9907d1718c613e603482a15bee8ccda17e5c9bb3ea"
+public_key = "0202020202020202020202020202020202020202020202020202020202020202"
+tokens = 500
+account_type = "system"
+"#).unwrap();
+
+assert_eq!(1500, genesis.total_allocated().unwrap());
+assert_eq!(Ok(()), genesis.validate());
+```
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GenesisConfig {
+    /// Time of creation of the genesis block, in seconds since the UNIX
+    /// epoch. Fixed, so that every node builds the same genesis block.
+    pub timestamp: u64,
+
+    /// Maximum number of tokens that can ever exist, if limited.
+    pub max_supply: Option<Amount>,
+
+    /// Economic rules of the chain.
+    pub params: ChainParams,
+
+    /// Accounts existing from the start, along with their tokens.
+    pub allocations: Vec<Allocation>,
+}
+
+/// Tokens given to an account at genesis.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Allocation {
+    /// ID of the account, created at genesis: the ID owned by its public key.
+    pub account: Id,
+
+    /// Key owning the account, signing its transactions. Hexadecimal in TOML.
+    #[serde(deserialize_with = "from_hex")]
+    pub public_key: PublicKey,
+
+    /// Number of tokens held by the account from the start.
+    pub tokens: Amount,
+
+    /// What kind of entity the account belongs to, a user by default.
+    #[serde(default)]
+    pub account_type: AccountType,
+}
+
+impl GenesisConfig {
+    /// Load the genesis configuration from a TOML file and validate it.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("cannot read genesis file {}: {}", path.display(), err))?;
+
+        let genesis = Self::from_toml(&content)?;
+        genesis.validate()?;
+        Ok(genesis)
+    }
+
+    /// Parse the genesis configuration from TOML.
+    pub fn from_toml(content: &str) -> Result<Self, Error> {
+        toml::from_str(content).map_err(|err| format!("invalid genesis: {}", err))
+    }
+
+    /// Add an allocation to the user account owned by a public key.
+    pub fn with_allocation(mut self, public_key: &[u8], tokens: Amount) -> Self {
+        self.allocations.push(Allocation {
+            account: Id::from_public_key(public_key),
+            public_key: public_key.to_vec(),
+            tokens,
+            account_type: AccountType::User,
+        });
+        self
+    }
+
+    /// Total number of tokens allocated at genesis.
+    pub fn total_allocated(&self) -> Result<Amount, Error> {
+        self.allocations
+            .iter()
+            .try_fold(0 as Amount, |total, allocation| {
+                total.checked_add(allocation.tokens)
+            })
+            .ok_or_else(|| "too many tokens allocated".to_string())
+    }
+
+    /// Check that the allocations are consistent.
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut accounts = HashSet::new();
+        for allocation in &self.allocations {
+            if !accounts.insert(&allocation.account) {
+                return Err(format!("duplicate allocation to {}", allocation.account));
+            }
+            if allocation.account != Id::from_public_key(&allocation.public_key) {
+                return Err(format!(
+                    "account {} is not owned by its public key",
+                    allocation.account
+                ));
+            }
+            if self.params.is_dust(allocation.tokens) {
+                return Err(format!("dust allocation to {}", allocation.account));
+            }
+        }
+        let total = self.total_allocated()?;
+        if self.max_supply.is_some_and(|max_supply| total > max_supply) {
+            return Err("allocations exceed the maximum supply".to_string());
+        }
+        Ok(())
+    }
+
+    /// Build the genesis block.
+    pub fn block(&self) -> Block {
+        let mut block = Block::new();
+        block.header.timestamp = UNIX_EPOCH + Duration::from_secs(self.timestamp);
+        block.seal();
+        block
+    }
+}
+
+/// Parse bytes written in hexadecimal, e.g. a public key.
+fn from_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let hex = String::deserialize(deserializer)?;
+    if hex.len() % 2 != 0 {
+        return Err(serde::de::Error::custom("odd number of hexadecimal digits"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| serde::de::Error::custom("invalid hexadecimal"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod genesis_tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(Ok(()), GenesisConfig::default().validate());
+
+        let alice = Id::from_public_key(b"alice");
+        let duplicate = GenesisConfig::default()
+            .with_allocation(b"alice", 1)
+            .with_allocation(b"alice", 2);
+        assert_eq!(
+            Err(format!("duplicate allocation to {}", alice)),
+            duplicate.validate()
+        );
+
+        let mut stolen = GenesisConfig::default().with_allocation(b"alice", 1);
+        stolen.allocations[0].public_key = b"mallory".to_vec();
+        assert_eq!(
+            Err(format!("account {} is not owned by its public key", alice)),
+            stolen.validate()
+        );
+
+        let overflow = GenesisConfig::default()
+            .with_allocation(b"alice", Amount::MAX)
+            .with_allocation(b"bob", 1);
+        assert_eq!(
+            Err("too many tokens allocated".to_string()),
+            overflow.validate()
+        );
+
+        let capped = GenesisConfig {
+            max_supply: Some(10),
+            ..GenesisConfig::default()
+        };
+        assert_eq!(
+            Ok(()),
+            capped.clone().with_allocation(b"alice", 10).validate()
+        );
+        assert_eq!(
+            Err("allocations exceed the maximum supply".to_string()),
+            capped.with_allocation(b"alice", 11).validate()
+        );
+
+        let dust = GenesisConfig {
+            params: ChainParams {
+                min_balance: 10,
+                ..ChainParams::default()
+            },
+            ..GenesisConfig::default()
+        }
+        .with_allocation(b"alice", 0)
+        .with_allocation(b"bob", 9);
+        assert_eq!(
+            Err(format!(
+                "dust allocation to {}",
+                Id::from_public_key(b"bob")
+            )),
+            dust.validate()
+        );
+    }
+
+    #[test]
+    fn test_block_is_deterministic() {
+        let genesis = GenesisConfig {
+            timestamp: 1_600_000_000,
+            ..GenesisConfig::default()
+        };
+        let block = genesis.block();
+
+        assert!(block.is_hash_valid());
+        assert_eq!(
+            UNIX_EPOCH + Duration::from_secs(1_600_000_000),
+            block.header.timestamp
+        );
+        assert_eq!(block.hash, genesis.block().hash);
+    }
+
+    #[test]
+    fn test_from_toml_rejects_unknown_fields() {
+        assert!(GenesisConfig::from_toml("mint = true").is_err());
+    }
+
+    #[test]
+    fn test_from_toml_reads_hexadecimal_keys() {
+        let allocation = |public_key: &str| {
+            GenesisConfig::from_toml(&format!(
+                "[[allocations]]\naccount = \"alice\"\npublic_key = \"{}\"\ntokens = 1\n",
+                public_key
+            ))
+            .map(|genesis| genesis.allocations[0].public_key.clone())
+        };
+        assert_eq!(Ok(vec![0x01, 0xab]), allocation("01ab"));
+        assert!(allocation("01a").is_err());
+        assert!(allocation("0g").is_err());
+        assert!(
+            GenesisConfig::from_toml("[[allocations]]\naccount = \"alice\"\ntokens = 1\n").is_err()
+        );
+    }
+}
@@ -1,7 +1,36 @@
+use crate::rng::{OsRng, Rng};
+
 /// An unique identifier.
-#[derive(Debug, Clone, std::cmp::PartialEq, std::cmp::Eq, std::hash::Hash)]
+#[derive(Debug, Clone, std::cmp::PartialEq, std::cmp::Eq, std::hash::Hash, serde::Deserialize)]
 pub struct Id(String);
 
+impl Id {
+    /// The ID of the account owned by a public key: the hexadecimal hash of
+    /// the key.
+    pub fn from_public_key(public_key: &[u8]) -> Self {
+        Id::from_bytes(blake3::hash(public_key).as_bytes())
+    }
+
+    /// A new random ID, as long as a public key hash, from the operating
+    /// system's cryptographically secure generator.
+    ///
+    /// Panics if the operating system has no random source.
+    pub fn random() -> Self {
+        Self::random_with(&OsRng)
+    }
+
+    /// A new random ID, as long as a public key hash, drawn from `rng`.
+    pub fn random_with<R: Rng>(rng: &R) -> Self {
+        let mut bytes = [0; 32];
+        rng.fill(&mut bytes);
+        Id::from_bytes(&bytes)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Id(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+}
+
 impl std::fmt::Display for Id {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{}", self.0)
@@ -19,3 +48,32 @@ impl From<&str> for Id {
         Id(s.to_string())
     }
 }
+
+impl AsRef<[u8]> for Id {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+#[cfg(test)]
+mod id_tests {
+    use super::*;
+
+    #[test]
+    fn test_random() {
+        let id = Id::random();
+        assert_eq!(64, id.to_string().len());
+        assert!(id.to_string().chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(id, Id::random());
+    }
+
+    #[test]
+    fn test_random_with() {
+        use crate::rng::SeededRng;
+
+        assert_eq!(
+            Id::random_with(&SeededRng::new(7)),
+            Id::random_with(&SeededRng::new(7))
+        );
+    }
+}
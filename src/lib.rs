@@ -3,13 +3,9 @@ This is a basic Proof of Work blockchain implemented in Rust.
 I started that project to remind myself how a basic blockchain
 could work, and to learn more about Rust.
 */
-#![deny(
-    warnings,
-    missing_doc_code_examples,
-    missing_docs,
-    clippy::all,
-    clippy::cargo
-)]
+#![deny(warnings, missing_docs, clippy::all, clippy::cargo)]
+// ed25519-dalek and blake3 depend on different versions of the same crates
+#![allow(clippy::multiple_crate_versions)]
 
 /// Module account contains implementation for accounts.
 pub mod account;
@@ -23,17 +19,117 @@ pub mod block;
 */
 pub mod blockchain;
 
+/// Module bloom implements bloom filters, to quickly filter blocks by account.
+pub mod bloom;
+
+/// Module clock tells the time, so that it can be mocked in tests.
+pub mod clock;
+
+/// Module config loads and validates the settings of a node.
+pub mod config;
+
+/// Module difficulty adjusts the proof of work to the observed block times.
+pub mod difficulty;
+
+/// Module encoding writes the canonical binary encoding of chain data, which
+/// is hashed and signed.
+pub mod encoding;
+
+/// Module error lists why transactions, blocks and chains are rejected.
+pub mod error;
+
+/// Module events broadcasts what happens to the chain to other components.
+pub mod events;
+
+/// Module export dumps chain data to CSV, for offline analytics.
+pub mod export;
+
+/// Module ffi exposes the chain through a C ABI, for non-Rust applications.
+pub mod ffi;
+
+/// Module faucet hands out test tokens on development and test chains.
+pub mod faucet;
+
+/// Module fuzz exposes entry points for fuzzing decoders and validation.
+pub mod fuzz;
+
+/// Module genesis defines the first block of a chain, and its token
+/// allocations.
+pub mod genesis;
+
 /// Module id can define and generate unique identifiers.
 pub mod id;
 
+/// Module light implements a light client, following the chain from block
+/// headers only.
+pub mod light;
+
+/// Module merkle builds Merkle trees over transactions, to prove that a
+/// transaction is in a block.
+pub mod merkle;
+
+/// Module mempool holds the transactions waiting to be included in a block.
+pub mod mempool;
+
+/// Module metrics exposes counters and histograms about the node, in the
+/// Prometheus format.
+pub mod metrics;
+
+/// Module names maps human-readable names to accounts.
+pub mod names;
+
+/// Module overlay applies transactions on top of a world state, without
+/// changing it.
+pub mod overlay;
+
+/// Module page implements cursor-based pagination of list queries.
+pub mod page;
+
+/// Module params defines the economic rules of the chain.
+pub mod params;
+
+/// Module presets defines the well-known networks, and their genesis.
+pub mod presets;
+
+#[cfg(test)]
+mod proptests;
+
+/// Module python exposes the chain to Python, as an extension built with
+/// the `python` feature.
+#[cfg(feature = "python")]
+pub mod python;
+
+/// Module registry hosts several independent chains in the same node.
+pub mod registry;
+
+/// Module rng provides random bytes, so that randomness can be controlled in
+/// tests and simulations.
+pub mod rng;
+
+/// Module scheduler runs recurring maintenance tasks in the background.
+pub mod scheduler;
+
+/// Module storage defines where a chain keeps its blocks and state: in memory,
+/// in a directory, or in a sled database with the `sled` feature.
+pub mod storage;
+
+/// Module testing helps building chains and world states in tests.
+pub mod testing;
+
 /// Module transaction implements transactions: actions to apply, signature,
 /// hash...
 pub mod transaction;
 
+#[cfg(test)]
+mod vectors;
+
+/// Module watch notifies subscribers when blocks touch watched accounts.
+pub mod watch;
+
 /// Module world contains abstract definitions of the world state.
 pub mod world;
 
-/// An error message.
+/// An error message, from modules that don't report an `error::BlockchainError`.
 pub type Error = String;
 /// The hash of some data.
 pub type Hash = Vec<u8>;
@@ -0,0 +1,465 @@
+use crate::block::BlockHeader;
+use crate::clock::{Clock, SystemClock};
+use crate::difficulty::DifficultyAdjuster;
+use crate::merkle::MerkleProof;
+use crate::params::ParamsSchedule;
+use crate::{Error, Hash};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// How far in the future a header can be, to account for clock drift.
+pub const MAX_FUTURE_DRIFT: Duration = Duration::from_secs(2 * 60 * 60);
+
+/**
+A light client only stores and verifies block headers, and follows the
+best header chain: the one with the most work, i.e. the most hashes
+expected to mine it.
+
+Headers must be mined at the difficulty the full chain would require, so
+the client needs the same rules and difficulty adjustment as full nodes.
+
+```
+# use crate::blockchain::block::Block;
+# use crate::blockchain::light::LightClient;
+let block = Block::new();
+
+let mut client = LightClient::new();
+client.add_header(block.header).unwrap();
+
+assert_eq!(Some(0), client.height());
+```
+*/
+#[derive(Debug, Default)]
+pub struct LightClient<C = SystemClock> {
+    /// All known headers, by hash.
+    headers: HashMap<Hash, BlockHeader>,
+
+    /// Total work of the header chain ending at each known header, by hash.
+    work: HashMap<Hash, u128>,
+
+    /// Hash of the tip of the best header chain.
+    tip: Option<Hash>,
+
+    /// Economic rules of the chain, including its minimum difficulty.
+    params: ParamsSchedule,
+
+    /// Adjusts the difficulty to the observed block times.
+    difficulty_adjuster: DifficultyAdjuster,
+
+    /// Tells the time, to reject headers from the future.
+    clock: C,
+}
+
+impl LightClient {
+    /// Constructor
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl<C: Clock> LightClient<C> {
+    /// Constructor, telling the time with the given clock.
+    pub fn with_clock(clock: C) -> Self {
+        LightClient {
+            headers: HashMap::new(),
+            work: HashMap::new(),
+            tip: None,
+            params: ParamsSchedule::default(),
+            difficulty_adjuster: DifficultyAdjuster::default(),
+            clock,
+        }
+    }
+
+    /// Set the rules headers are validated against, as the full chain's.
+    pub fn set_params(&mut self, params: ParamsSchedule) {
+        self.params = params;
+    }
+
+    /// Change how the difficulty adjusts to the observed block times, as the
+    /// full chain's.
+    pub fn set_difficulty_adjuster(&mut self, adjuster: DifficultyAdjuster) {
+        self.difficulty_adjuster = adjuster;
+    }
+
+    /// The clock telling the time.
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// If the header is correct, add it to the known headers, and make it the
+    /// tip if its chain has more work than the best chain.
+    pub fn add_header(&mut self, header: BlockHeader) -> Result<(), Error> {
+        let hash = header.calculate_hash();
+        if self.headers.contains_key(&hash) {
+            return Err("header already known".to_string());
+        }
+
+        if !header.meets_difficulty(&hash) {
+            return Err("hash doesn't meet the difficulty".to_string());
+        }
+
+        if header.timestamp > self.clock.now() + MAX_FUTURE_DRIFT {
+            return Err("header is too far in the future".to_string());
+        }
+
+        let parent_work = match &header.previous_hash {
+            None if self.tip.is_some() => return Err("genesis already known".to_string()),
+            None if header.height != 0 => return Err("invalid header height".to_string()),
+            None => 0,
+            Some(previous_hash) => {
+                let parent = self
+                    .headers
+                    .get(previous_hash)
+                    .ok_or("unknown previous header")?;
+                if header.height != parent.height + 1 {
+                    return Err("invalid header height".to_string());
+                }
+                if header.timestamp < parent.timestamp {
+                    return Err("header is older than its parent".to_string());
+                }
+                let difficulty = self.expected_difficulty(parent);
+                if header.difficulty != difficulty {
+                    return Err(format!(
+                        "wrong difficulty: expected {}, got {}",
+                        difficulty, header.difficulty
+                    ));
+                }
+                self.work[previous_hash]
+            }
+        };
+
+        let work = parent_work.saturating_add(header.work());
+        if self.tip_work().is_none_or(|tip_work| work > tip_work) {
+            self.tip = Some(hash.clone());
+        }
+        self.work.insert(hash.clone(), work);
+        self.headers.insert(hash, header);
+        Ok(())
+    }
+
+    /// Difficulty the header following `parent` must be mined at, as
+    /// `Blockchain::current_difficulty` would require.
+    fn expected_difficulty(&self, parent: &BlockHeader) -> u32 {
+        let height = parent.height + 1;
+        self.difficulty_adjuster
+            .difficulty_at(height, parent, |ancestor_height| {
+                self.ancestor(parent, ancestor_height)
+            })
+            .max(self.params.at(height).difficulty)
+    }
+
+    /// The header at some height of the chain ending at `header`.
+    fn ancestor<'a>(
+        &'a self,
+        mut header: &'a BlockHeader,
+        height: usize,
+    ) -> Option<&'a BlockHeader> {
+        while header.height > height {
+            header = self.headers.get(header.previous_hash.as_ref()?)?;
+        }
+        Some(header).filter(|header| header.height == height)
+    }
+
+    /// Total work of the best header chain.
+    fn tip_work(&self) -> Option<u128> {
+        self.work.get(self.tip.as_ref()?).copied()
+    }
+
+    /// Verify that a transaction is included in a block of the best verified
+    /// header chain, from a proof obtained from a full node, e.g. with
+    /// `Blockchain::prove_transaction`. Blocks on a fork that lost to the best
+    /// chain don't count.
+    pub fn verify_inclusion(
+        &self,
+        header: &BlockHeader,
+        proof: &MerkleProof,
+        transaction_hash: &[u8],
+    ) -> Result<(), Error> {
+        if self.get_header(&header.calculate_hash()) != Some(header) {
+            return Err("unknown header".to_string());
+        }
+
+        if self.header_at(header.height) != Some(header) {
+            return Err("header is not on the best chain".to_string());
+        }
+
+        if !proof.verify(&header.merkle_root, transaction_hash) {
+            return Err("invalid inclusion proof".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Get a known header by its hash.
+    pub fn get_header(&self, hash: &[u8]) -> Option<&BlockHeader> {
+        self.headers.get(hash)
+    }
+
+    /// The tip of the best verified header chain.
+    pub fn tip(&self) -> Option<&BlockHeader> {
+        self.get_header(self.tip.as_ref()?)
+    }
+
+    /// The header at some height of the best verified header chain.
+    pub fn header_at(&self, height: usize) -> Option<&BlockHeader> {
+        self.ancestor(self.tip()?, height)
+    }
+
+    /// Height of the tip of the best verified header chain.
+    pub fn height(&self) -> Option<usize> {
+        self.tip().map(|header| header.height)
+    }
+}
+
+#[cfg(test)]
+mod light_tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::bloom::BloomFilter;
+    use crate::clock::MockClock;
+    use crate::params::ChainParams;
+    use std::time::UNIX_EPOCH;
+
+    /// Header of a block identified by `id`, following `parent`.
+    fn header(id: u8, parent: Option<&BlockHeader>, secs: u64) -> BlockHeader {
+        BlockHeader {
+            height: parent.map_or(0, |parent| parent.height + 1),
+            previous_hash: parent.map(BlockHeader::calculate_hash),
+            merkle_root: vec![id],
+            bloom: BloomFilter::new(),
+            beneficiary: None,
+            timestamp: UNIX_EPOCH + Duration::from_secs(secs),
+            nonce: 0,
+            difficulty: 0,
+        }
+    }
+
+    #[test]
+    fn test_follows_best_chain() {
+        let genesis = header(0, None, 0);
+        let first = header(1, Some(&genesis), 10);
+        let second = header(2, Some(&genesis), 10);
+        let third = header(3, Some(&second), 20);
+
+        let mut client = LightClient::new();
+        client.add_header(genesis).unwrap();
+        client.add_header(first.clone()).unwrap();
+        client.add_header(second).unwrap();
+        assert_eq!(Some(&first), client.tip());
+
+        client.add_header(third.clone()).unwrap();
+        assert_eq!(Some(&third), client.tip());
+        assert_eq!(Some(2), client.height());
+    }
+
+    #[test]
+    fn test_verify_inclusion() {
+        use crate::transaction::{Transaction, TransactionRecord};
+
+        let mut block = Block::new();
+        for id in &["someone", "someone else"] {
+            block.transactions.push(Transaction::new(
+                0,
+                TransactionRecord::create_user_account(id.as_bytes()),
+                None,
+            ));
+        }
+        block.seal();
+        let header = block.header.clone();
+        let transaction_hash = block.transactions[1].calculate_hash();
+        let proof = block.merkle_tree().proof(&transaction_hash).unwrap();
+
+        let mut client = LightClient::new();
+        assert_eq!(
+            Err("unknown header".to_string()),
+            client.verify_inclusion(&header, &proof, &transaction_hash)
+        );
+
+        client.add_header(header.clone()).unwrap();
+        assert_eq!(
+            Ok(()),
+            client.verify_inclusion(&header, &proof, &transaction_hash)
+        );
+        assert_eq!(
+            Err("invalid inclusion proof".to_string()),
+            client.verify_inclusion(&header, &proof, &[0; 32])
+        );
+
+        let mut forged = proof;
+        forged.siblings.pop();
+        assert_eq!(
+            Err("invalid inclusion proof".to_string()),
+            client.verify_inclusion(&header, &forged, &transaction_hash)
+        );
+    }
+
+    #[test]
+    fn test_verify_inclusion_on_best_chain() {
+        use crate::transaction::{Transaction, TransactionRecord};
+
+        let genesis = header(0, None, 0);
+        let mut block = Block::new();
+        block.header.height = 1;
+        block.header.previous_hash = Some(genesis.calculate_hash());
+        block.transactions.push(Transaction::new(
+            0,
+            TransactionRecord::create_user_account(b"someone"),
+            None,
+        ));
+        block.seal();
+        let transaction_hash = block.transactions[0].calculate_hash();
+        let proof = block.merkle_tree().proof(&transaction_hash).unwrap();
+
+        let mut client = LightClient::new();
+        client.add_header(genesis.clone()).unwrap();
+        client.add_header(block.header.clone()).unwrap();
+        assert_eq!(Some(&block.header), client.header_at(1));
+        assert_eq!(
+            Ok(()),
+            client.verify_inclusion(&block.header, &proof, &transaction_hash)
+        );
+
+        // a longer fork takes over: the block is now stale
+        let fork = header(2, Some(&genesis), 10);
+        client.add_header(fork.clone()).unwrap();
+        client.add_header(header(3, Some(&fork), 20)).unwrap();
+        assert_eq!(Some(&fork), client.header_at(1));
+        assert_eq!(
+            Err("header is not on the best chain".to_string()),
+            client.verify_inclusion(&block.header, &proof, &transaction_hash)
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_headers() {
+        let genesis = header(0, None, 10);
+        let mut client = LightClient::new();
+        client.add_header(genesis.clone()).unwrap();
+
+        assert_eq!(
+            Err("genesis already known".to_string()),
+            client.add_header(header(1, None, 10))
+        );
+        assert_eq!(
+            Err("unknown previous header".to_string()),
+            client.add_header(header(1, Some(&header(5, None, 10)), 10))
+        );
+        assert_eq!(
+            Err("header is older than its parent".to_string()),
+            client.add_header(header(1, Some(&genesis), 5))
+        );
+        assert_eq!(
+            Err("header already known".to_string()),
+            client.add_header(genesis.clone())
+        );
+
+        let mut wrong_height = header(1, Some(&genesis), 10);
+        wrong_height.height = 5;
+        assert_eq!(
+            Err("invalid header height".to_string()),
+            client.add_header(wrong_height)
+        );
+
+        let mut block = Block::new();
+        block.mine(4);
+        block.header.difficulty = 40;
+        assert_eq!(
+            Err("hash doesn't meet the difficulty".to_string()),
+            LightClient::new().add_header(block.header)
+        );
+    }
+
+    #[test]
+    fn test_rejects_headers_from_the_future() {
+        let mut client = LightClient::with_clock(MockClock::new(UNIX_EPOCH));
+        let genesis = header(0, None, 0);
+        client.add_header(genesis.clone()).unwrap();
+
+        let future = header(1, Some(&genesis), 2 * MAX_FUTURE_DRIFT.as_secs());
+        assert_eq!(
+            Err("header is too far in the future".to_string()),
+            client.add_header(future.clone())
+        );
+
+        client.clock().advance(MAX_FUTURE_DRIFT);
+        assert_eq!(Ok(()), client.add_header(future));
+    }
+
+    /// Find a nonce so that the header meets its difficulty.
+    fn mine(mut header: BlockHeader) -> BlockHeader {
+        while !header.meets_difficulty(&header.calculate_hash()) {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    #[test]
+    fn test_checks_difficulty() {
+        let mut params = ParamsSchedule::default();
+        params.schedule(
+            2,
+            ChainParams {
+                difficulty: 1,
+                ..ChainParams::default()
+            },
+        );
+        let mut client = LightClient::new();
+        client.set_params(params);
+
+        let genesis = header(0, None, 0);
+        let first = header(1, Some(&genesis), 10);
+        client.add_header(genesis).unwrap();
+        client.add_header(first.clone()).unwrap();
+
+        // below the minimum difficulty of the chain
+        assert_eq!(
+            Err("wrong difficulty: expected 1, got 0".to_string()),
+            client.add_header(header(2, Some(&first), 20))
+        );
+        let mut too_hard = header(2, Some(&first), 20);
+        too_hard.difficulty = 2;
+        assert_eq!(
+            Err("wrong difficulty: expected 1, got 2".to_string()),
+            client.add_header(mine(too_hard))
+        );
+
+        let mut second = header(2, Some(&first), 20);
+        second.difficulty = 1;
+        assert_eq!(Ok(()), client.add_header(mine(second)));
+    }
+
+    #[test]
+    fn test_follows_most_work() {
+        let mut client = LightClient::new();
+        client.set_difficulty_adjuster(DifficultyAdjuster::new(Duration::from_secs(60), 2));
+        let genesis = header(0, None, 0);
+        client.add_header(genesis.clone()).unwrap();
+
+        // blocks come fast on one branch: the difficulty goes up at height 4
+        let mut fast = vec![genesis.clone()];
+        for height in 1..=4 {
+            let mut next = header(height, fast.last(), height as u64);
+            next.difficulty = if height == 4 { 1 } else { 0 };
+            fast.push(mine(next));
+        }
+        // and slowly on the other, whose difficulty stays at 0
+        let mut slow = vec![genesis];
+        for height in 1..=6 {
+            slow.push(header(10 + height, slow.last(), 600 * height as u64));
+        }
+
+        for header in &fast[1..] {
+            client.add_header(header.clone()).unwrap();
+        }
+        assert_eq!(Some(4), client.height());
+
+        // higher, but with as much work
+        for header in &slow[1..=5] {
+            client.add_header(header.clone()).unwrap();
+        }
+        assert_eq!(Some(&fast[4]), client.tip());
+
+        client.add_header(slow[6].clone()).unwrap();
+        assert_eq!(Some(&slow[6]), client.tip());
+    }
+}
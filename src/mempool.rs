@@ -0,0 +1,615 @@
+use crate::account::Amount;
+use crate::events::{ChainEvent, EventBus};
+use crate::id::Id;
+use crate::metrics::Metrics;
+use crate::page::Page;
+use crate::transaction::{Lane, Signature, Transaction, TransactionRecord};
+use crate::Error;
+use std::ops::Range;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// Default memory budget of the mempool, in bytes.
+pub const DEFAULT_MEMPOOL_SIZE: usize = 32 * 1024 * 1024;
+
+/// How much a single account may have pending in the mempool, so that it
+/// cannot take all of its memory or all of the block space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SenderLimits {
+    /// Most pending transactions sent by the same account.
+    pub max_transactions: usize,
+
+    /// Most tokens pending transfers from the same account may send in total.
+    pub max_value: Amount,
+}
+
+impl Default for SenderLimits {
+    fn default() -> Self {
+        SenderLimits {
+            max_transactions: 64,
+            max_value: Amount::MAX,
+        }
+    }
+}
+
+/// Most transactions of each lane in a drained block, so that each lane
+/// keeps some block space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LaneQuotas {
+    /// Most system transactions in a block.
+    pub system: usize,
+
+    /// Most user transactions in a block.
+    pub user: usize,
+}
+
+impl LaneQuotas {
+    fn of(&self, lane: Lane) -> usize {
+        match lane {
+            Lane::System => self.system,
+            Lane::User => self.user,
+        }
+    }
+
+    fn of_mut(&mut self, lane: Lane) -> &mut usize {
+        match lane {
+            Lane::System => &mut self.system,
+            Lane::User => &mut self.user,
+        }
+    }
+
+    /// Give back the block space of a drained transaction that will not be
+    /// included after all.
+    pub fn release(&mut self, lane: Lane) {
+        let quota = self.of_mut(lane);
+        *quota = quota.saturating_add(1);
+    }
+}
+
+impl Default for LaneQuotas {
+    fn default() -> Self {
+        LaneQuotas {
+            system: 16,
+            user: usize::MAX,
+        }
+    }
+}
+
+/// A pending transaction, whose body is encoded in the arena of the mempool.
+#[derive(Debug, Clone)]
+struct Entry {
+    /// Order of arrival in the mempool.
+    sequence: u64,
+
+    /// Lane the transaction waits in.
+    lane: Lane,
+
+    /// Where the canonical encoding of the transaction is in the arena.
+    body: Range<usize>,
+
+    /// Signature of the transaction, which is not part of its encoding.
+    signature: Option<Signature>,
+
+    /// Sender of the transaction, to check its limits without decoding.
+    sender: Option<Id>,
+
+    /// Tokens the transaction sends, to check the limits of its sender.
+    value: Amount,
+
+    /// Size of the transaction, in bytes, as counted in the memory budget.
+    size: usize,
+}
+
+/**
+The mempool holds transactions waiting to be included in a block.
+
+It keeps track of the size of each pending transaction, and refuses new ones
+once its memory budget is exhausted. Pending transactions are kept encoded,
+one after the other in a single buffer: draining moves the remaining ones
+to its start, so that the buffer is reused rather than reallocated under
+sustained load. They are decoded when read.
+
+```
+# use crate::blockchain::mempool::Mempool;
+# use crate::blockchain::transaction::{Transaction, TransactionRecord};
+let mut mempool = Mempool::new(1024);
+
+let transaction = Transaction::new(0, TransactionRecord::create_user_account(b"hi"), None);
+mempool.add(transaction).unwrap();
+
+assert_eq!(1, mempool.len());
+```
+*/
+#[derive(Debug)]
+pub struct Mempool {
+    /// Pending transactions, oldest first.
+    entries: Vec<Entry>,
+
+    /// Canonical encodings of the pending transactions, in order.
+    arena: Vec<u8>,
+
+    /// Sequence number of the next queued transaction.
+    next_sequence: u64,
+
+    /// Total size of pending transactions, in bytes.
+    size: usize,
+
+    /// Memory budget, in bytes.
+    max_size: usize,
+
+    /// What a single account may have pending.
+    sender_limits: SenderLimits,
+
+    /// Block space of each lane.
+    lane_quotas: LaneQuotas,
+
+    /// Where to report the number and size of pending transactions.
+    metrics: Arc<Metrics>,
+
+    /// Where to announce new pending transactions.
+    event_bus: EventBus<ChainEvent>,
+}
+
+impl Mempool {
+    /// Constructor
+    pub fn new(max_size: usize) -> Self {
+        Self::with_reporting(max_size, Arc::default(), EventBus::new())
+    }
+
+    /// Constructor, reporting to shared metrics and event bus.
+    pub fn with_reporting(
+        max_size: usize,
+        metrics: Arc<Metrics>,
+        event_bus: EventBus<ChainEvent>,
+    ) -> Self {
+        Mempool {
+            entries: vec![],
+            arena: vec![],
+            next_sequence: 0,
+            size: 0,
+            max_size,
+            sender_limits: SenderLimits::default(),
+            lane_quotas: LaneQuotas::default(),
+            metrics,
+            event_bus,
+        }
+    }
+
+    /// Change what a single account may have pending. Transactions already
+    /// pending are kept.
+    pub fn set_sender_limits(&mut self, limits: SenderLimits) {
+        self.sender_limits = limits;
+    }
+
+    /// Change how much block space each lane gets.
+    pub fn set_lane_quotas(&mut self, quotas: LaneQuotas) {
+        self.lane_quotas = quotas;
+    }
+
+    /// How much block space each lane gets.
+    pub fn lane_quotas(&self) -> LaneQuotas {
+        self.lane_quotas
+    }
+
+    /// Queue a transaction in the user lane, if it fits in the memory budget
+    /// and its sender stays within its limits.
+    pub fn add(&mut self, transaction: Transaction) -> Result<(), Error> {
+        self.add_to_lane(transaction, Lane::User)
+    }
+
+    /// Queue a transaction in a lane, as `add` does. The lane is up to the
+    /// caller, who can look the sender up: see `Transaction::lane`.
+    pub fn add_to_lane(&mut self, transaction: Transaction, lane: Lane) -> Result<(), Error> {
+        self.check_sender_limits(&transaction)?;
+        let transaction_size = transaction.size();
+        let size = self
+            .size
+            .checked_add(transaction_size)
+            .filter(|size| *size <= self.max_size)
+            .ok_or_else(|| {
+                tracing::debug!(size = self.size, "mempool is full, transaction rejected");
+                "mempool is full".to_string()
+            })?;
+
+        tracing::trace!(pending = self.entries.len() + 1, "transaction queued");
+        self.event_bus.publish(ChainEvent::TxAdded {
+            hash: transaction.calculate_hash(),
+        });
+        let start = self.arena.len();
+        transaction.put_canonical_bytes(&mut self.arena);
+        self.entries.push(Entry {
+            sequence: self.next_sequence,
+            lane,
+            body: start..self.arena.len(),
+            signature: transaction.signature,
+            value: value(&transaction),
+            sender: transaction.from_account_id,
+            size: transaction_size,
+        });
+        self.next_sequence += 1;
+        self.size = size;
+        self.report_metrics();
+        Ok(())
+    }
+
+    fn check_sender_limits(&self, transaction: &Transaction) -> Result<(), Error> {
+        let sender = match &transaction.from_account_id {
+            Some(sender) => sender,
+            None => return Ok(()),
+        };
+
+        let pending: Vec<&Entry> = self
+            .entries
+            .iter()
+            .filter(|pending| pending.sender.as_ref() == Some(sender))
+            .collect();
+        if pending.len() >= self.sender_limits.max_transactions {
+            tracing::debug!(%sender, "too many pending transactions, transaction rejected");
+            return Err("too many pending transactions from this account".to_string());
+        }
+
+        let pending_value = pending
+            .into_iter()
+            .fold(value(transaction), |total, pending| {
+                total.saturating_add(pending.value)
+            });
+        if pending_value > self.sender_limits.max_value {
+            tracing::debug!(%sender, "too many pending tokens, transaction rejected");
+            return Err("too many tokens pending from this account".to_string());
+        }
+        Ok(())
+    }
+
+    /// Remove and return up to `max` pending transactions, to include in a
+    /// block: system transactions first, then user transactions, each lane
+    /// oldest first and within its quota.
+    pub fn drain(&mut self, max: usize) -> Vec<Transaction> {
+        let mut quotas = self.lane_quotas;
+        self.drain_within(max, &mut quotas)
+    }
+
+    /// Remove and return up to `max` pending transactions like `drain`, but
+    /// within what is left of some quotas, e.g. when filling the rest of a
+    /// block. The quotas are reduced by the drained transactions.
+    pub fn drain_within(&mut self, max: usize, quotas: &mut LaneQuotas) -> Vec<Transaction> {
+        let mut drained: Vec<Entry> = vec![];
+        for lane in [Lane::System, Lane::User] {
+            let mut quota = quotas.of(lane).min(max - drained.len());
+            let before = drained.len();
+            drained.extend(self.entries.extract_if(.., |entry| {
+                let selected = quota > 0 && entry.lane == lane;
+                if selected {
+                    quota -= 1;
+                }
+                selected
+            }));
+            *quotas.of_mut(lane) -= drained.len() - before;
+        }
+        self.size -= drained.iter().map(|entry| entry.size).sum::<usize>();
+        let transactions = drained.iter().map(|entry| self.decode(entry)).collect();
+        self.compact();
+        self.report_metrics();
+        transactions
+    }
+
+    /// Decode a pending transaction from the arena.
+    fn decode(&self, entry: &Entry) -> Transaction {
+        let mut transaction = Transaction::from_canonical_bytes(&self.arena[entry.body.clone()])
+            .expect("pending transactions are encoded by the mempool");
+        transaction.signature = entry.signature;
+        transaction
+    }
+
+    /// Move the bodies of the pending transactions to the start of the
+    /// arena, over the drained ones. The arena keeps its capacity.
+    fn compact(&mut self) {
+        let mut end = 0;
+        for entry in &mut self.entries {
+            let length = entry.body.len();
+            self.arena.copy_within(entry.body.clone(), end);
+            entry.body = end..end + length;
+            end += length;
+        }
+        self.arena.truncate(end);
+    }
+
+    /// Pending transactions, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = Transaction> + '_ {
+        self.entries.iter().map(move |entry| self.decode(entry))
+    }
+
+    /// A page of pending transactions, oldest first.
+    /// The cursor is a sequence number, which stays valid as transactions
+    /// leave the mempool.
+    pub fn list(&self, cursor: Option<u64>, limit: usize) -> Page<Transaction, u64> {
+        let cursor = cursor.unwrap_or(0);
+        Page::collect(
+            self.entries
+                .iter()
+                .filter(|entry| entry.sequence >= cursor)
+                .map(|entry| (entry.sequence, self.decode(entry))),
+            limit,
+        )
+    }
+
+    /// Number of pending transactions.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Is there no pending transaction?
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Total size of pending transactions, in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Update the metrics with the current state of the mempool.
+    fn report_metrics(&self) {
+        self.metrics
+            .mempool_transactions
+            .store(self.entries.len() as u64, Ordering::Relaxed);
+        self.metrics
+            .mempool_size
+            .store(self.size as u64, Ordering::Relaxed);
+    }
+
+    /// Memory budget, in bytes.
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+}
+
+/// Tokens a transaction sends, counted in the limits of its sender.
+fn value(transaction: &Transaction) -> Amount {
+    match transaction.record {
+        TransactionRecord::SendTokens { amount, .. } => amount,
+        _ => 0,
+    }
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEMPOOL_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod mempool_tests {
+    use super::*;
+    use crate::transaction::TransactionRecord;
+
+    fn create_user(id: &str) -> Transaction {
+        Transaction::new(
+            0,
+            TransactionRecord::create_user_account(id.as_bytes()),
+            None,
+        )
+    }
+
+    #[test]
+    fn test_add_tracks_size() {
+        let mut mempool = Mempool::default();
+        let transaction = create_user("someone");
+        let size = transaction.size();
+
+        mempool.add(transaction).unwrap();
+        assert_eq!(1, mempool.len());
+        assert_eq!(size, mempool.size());
+        assert_eq!(
+            size as u64,
+            mempool.metrics.mempool_size.load(Ordering::Relaxed)
+        );
+    }
+
+    #[test]
+    fn test_add_publishes_event() {
+        let mempool_bus = EventBus::new();
+        let events = mempool_bus.subscribe();
+        let mut mempool = Mempool::with_reporting(1024, Arc::default(), mempool_bus);
+
+        let transaction = create_user("someone");
+        let hash = transaction.calculate_hash();
+        mempool.add(transaction).unwrap();
+
+        assert_eq!(Ok(ChainEvent::TxAdded { hash }), events.try_recv());
+    }
+
+    #[test]
+    fn test_add_enforces_budget() {
+        let transaction = create_user("someone");
+        let mut mempool = Mempool::new(transaction.size());
+
+        mempool.add(transaction).unwrap();
+        assert_eq!(
+            Err("mempool is full".to_string()),
+            mempool.add(create_user("someone else"))
+        );
+        assert_eq!(1, mempool.len());
+    }
+
+    #[test]
+    fn test_add_enforces_sender_limits() {
+        let transfer = |from: &str, amount| {
+            Transaction::new(
+                0,
+                TransactionRecord::SendTokens {
+                    to: "someone".into(),
+                    amount,
+                },
+                Some(from.into()),
+            )
+        };
+        let mut mempool = Mempool::default();
+        mempool.set_sender_limits(SenderLimits {
+            max_transactions: 2,
+            max_value: 100,
+        });
+
+        assert_eq!(Ok(()), mempool.add(transfer("spammer", 60)));
+        assert_eq!(
+            Err("too many tokens pending from this account".to_string()),
+            mempool.add(transfer("spammer", 41))
+        );
+        assert_eq!(Ok(()), mempool.add(transfer("spammer", 40)));
+        assert_eq!(
+            Err("too many pending transactions from this account".to_string()),
+            mempool.add(transfer("spammer", 0))
+        );
+
+        // other senders have their own limits
+        assert_eq!(Ok(()), mempool.add(transfer("someone else", 100)));
+        assert_eq!(Ok(()), mempool.add(create_user("someone")));
+
+        mempool.drain(1);
+        assert_eq!(Ok(()), mempool.add(transfer("spammer", 60)));
+    }
+
+    #[test]
+    fn test_drain_prioritizes_system_lane() {
+        let freeze = |id: &str| {
+            Transaction::new(
+                0,
+                TransactionRecord::FreezeAccount(id.into()),
+                Some("authority".into()),
+            )
+        };
+        let is_freeze = |transaction: &Transaction| {
+            matches!(transaction.record, TransactionRecord::FreezeAccount(_))
+        };
+        let mut mempool = Mempool::default();
+        mempool.set_lane_quotas(LaneQuotas { system: 2, user: 2 });
+        for transaction in [
+            create_user("a"),
+            create_user("b"),
+            freeze("x"),
+            create_user("c"),
+            freeze("y"),
+            freeze("z"),
+        ] {
+            let lane = if is_freeze(&transaction) {
+                Lane::System
+            } else {
+                Lane::User
+            };
+            mempool.add_to_lane(transaction, lane).unwrap();
+        }
+
+        let freezes: Vec<bool> = mempool.drain(3).iter().map(is_freeze).collect();
+        assert_eq!(vec![true, true, false], freezes);
+        assert_eq!(3, mempool.len());
+
+        // "a" went in the first block: "b" is now the oldest user transaction
+        let drained = mempool.drain(10);
+        assert_eq!(
+            vec![true, false, false],
+            drained.iter().map(is_freeze).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![&crate::id::Id::from_public_key(b"b")],
+            drained[1].touched_accounts()
+        );
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    fn test_drain_within_remaining_quotas() {
+        let mut mempool = Mempool::default();
+        for id in &["a", "b", "c"] {
+            mempool.add(create_user(id)).unwrap();
+        }
+
+        let mut quotas = LaneQuotas { system: 1, user: 2 };
+        assert_eq!(1, mempool.drain_within(1, &mut quotas).len());
+        assert_eq!(LaneQuotas { system: 1, user: 1 }, quotas);
+        assert_eq!(1, mempool.drain_within(10, &mut quotas).len());
+        assert!(mempool.drain_within(10, &mut quotas).is_empty());
+
+        quotas.release(Lane::User);
+        assert_eq!(1, mempool.drain_within(10, &mut quotas).len());
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    fn test_drain_frees_budget() {
+        let transaction = create_user("someone");
+        let mut mempool = Mempool::new(transaction.size());
+        mempool.add(transaction).unwrap();
+
+        let drained = mempool.drain(10);
+        assert_eq!(1, drained.len());
+        assert!(mempool.is_empty());
+        assert_eq!(0, mempool.size());
+
+        assert_eq!(Ok(()), mempool.add(create_user("someone")));
+    }
+
+    #[test]
+    fn test_list_is_stable_after_drain() {
+        let mut mempool = Mempool::default();
+        for id in &["a", "b", "c", "d"] {
+            mempool.add(create_user(id)).unwrap();
+        }
+
+        let page = mempool.list(None, 2);
+        assert_eq!(2, page.items.len());
+        assert_eq!(Some(2), page.next);
+        let next = page.next;
+
+        mempool.drain(1);
+        let page = mempool.list(next, 2);
+        assert_eq!(2, page.items.len());
+        assert_eq!(None, page.next);
+        assert_eq!(
+            mempool.iter().nth(1).unwrap().calculate_hash(),
+            page.items[0].calculate_hash()
+        );
+    }
+
+    #[test]
+    fn test_drain_reuses_arena() {
+        let mut signed = Transaction::new(
+            0,
+            TransactionRecord::SendTokens {
+                to: "someone".into(),
+                amount: 10,
+            },
+            Some(crate::testing::account_id("signer")),
+        );
+        signed.sign(&crate::testing::keypair("signer"));
+
+        let mut mempool = Mempool::default();
+        let transactions = vec![create_user("a"), signed.clone(), create_user("b")];
+        for transaction in transactions.clone() {
+            mempool.add(transaction).unwrap();
+        }
+        let hashes = |transactions: Vec<Transaction>| {
+            transactions
+                .iter()
+                .map(Transaction::calculate_hash)
+                .collect::<Vec<_>>()
+        };
+        let capacity = mempool.arena.capacity();
+
+        assert_eq!(hashes(transactions[..1].to_vec()), hashes(mempool.drain(1)));
+        let pending: Vec<Transaction> = mempool.iter().collect();
+        assert_eq!(signed.signature, pending[0].signature);
+        assert_eq!(hashes(transactions[1..].to_vec()), hashes(pending));
+        assert_eq!(capacity, mempool.arena.capacity());
+        assert_eq!(
+            mempool
+                .entries
+                .iter()
+                .map(|entry| entry.body.len())
+                .sum::<usize>(),
+            mempool.arena.len()
+        );
+
+        mempool.drain(10);
+        assert!(mempool.arena.is_empty());
+        assert_eq!(capacity, mempool.arena.capacity());
+    }
+}
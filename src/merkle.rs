@@ -0,0 +1,425 @@
+use crate::{Error, Hash};
+use std::convert::TryFrom;
+
+/// Prefix of the hashed leaves, so that a leaf cannot pass for a node.
+const LEAF_PREFIX: u8 = 0;
+
+/// Prefix of the hashed nodes, so that a node cannot pass for a leaf.
+const NODE_PREFIX: u8 = 1;
+
+/// Version of the binary encoding of proofs.
+pub const PROOF_VERSION: u8 = 1;
+
+/// Hash a transaction hash into a leaf of the tree.
+pub fn hash_leaf(transaction_hash: &[u8]) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(transaction_hash);
+    hasher.finalize().as_bytes().to_vec()
+}
+
+/// Hash two sibling nodes into their parent.
+pub fn hash_node(left: &[u8], right: &[u8]) -> Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().as_bytes().to_vec()
+}
+
+/**
+A Merkle tree over the hashes of the transactions of a block, in order.
+Its root commits to all the transactions, and a proof that one of them is
+in the tree only takes a hash per level.
+
+Leaves and nodes are hashed with different prefixes. A node without a
+sibling is promoted to the next level as is, rather than paired with
+itself, so that different lists of transactions cannot share a root.
+The root of an empty tree is the hash of nothing.
+
+```
+# use crate::blockchain::merkle::{hash_leaf, hash_node, MerkleTree};
+let hashes = vec![vec![1; 32], vec![2; 32], vec![3; 32]];
+let tree = MerkleTree::new(&hashes);
+
+let left = hash_node(&hash_leaf(&hashes[0]), &hash_leaf(&hashes[1]));
+assert_eq!(hash_node(&left, &hash_leaf(&hashes[2])), tree.root());
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree {
+    /// Transaction hashes, in order.
+    transaction_hashes: Vec<Hash>,
+
+    /// Every level of the tree, from the leaves up to the root.
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    /// Constructor, from the transaction hashes in order.
+    pub fn new<I>(transaction_hashes: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let transaction_hashes: Vec<Hash> = transaction_hashes
+            .into_iter()
+            .map(|hash| hash.as_ref().to_vec())
+            .collect();
+
+        let mut levels = vec![transaction_hashes
+            .iter()
+            .map(|hash| hash_leaf(hash))
+            .collect::<Vec<_>>()];
+        while levels.last().is_some_and(|level| level.len() > 1) {
+            let level = levels.last().unwrap();
+            let parents = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_node(left, right),
+                    _ => pair[0].clone(),
+                })
+                .collect();
+            levels.push(parents);
+        }
+
+        MerkleTree {
+            transaction_hashes,
+            levels,
+        }
+    }
+
+    /// Root of the tree, committing to all the transactions.
+    pub fn root(&self) -> Hash {
+        match self.levels.last().and_then(|level| level.first()) {
+            Some(root) => root.clone(),
+            None => blake3::hash(&[]).as_bytes().to_vec(),
+        }
+    }
+
+    /// Path from a transaction to the root, if the transaction is in the
+    /// tree.
+    pub fn proof(&self, transaction_hash: &[u8]) -> Option<MerkleProof> {
+        let mut index = self
+            .transaction_hashes
+            .iter()
+            .position(|hash| hash.as_slice() == transaction_hash)?;
+
+        let mut siblings = vec![];
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if let Some(hash) = level.get(sibling) {
+                let side = if sibling < index {
+                    Side::Left
+                } else {
+                    Side::Right
+                };
+                siblings.push((side, hash.clone()));
+            }
+            index /= 2;
+        }
+        Some(MerkleProof { siblings })
+    }
+}
+
+/// Which side of the path a sibling is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// The sibling is hashed before the path.
+    Left,
+
+    /// The sibling is hashed after the path.
+    Right,
+}
+
+impl Side {
+    /// Byte encoding the side in proofs.
+    fn to_byte(self) -> u8 {
+        match self {
+            Side::Left => 0,
+            Side::Right => 1,
+        }
+    }
+
+    /// Decode a side from its byte.
+    fn from_byte(byte: u8) -> Result<Self, Error> {
+        match byte {
+            0 => Ok(Side::Left),
+            1 => Ok(Side::Right),
+            _ => Err(format!("invalid side {}", byte)),
+        }
+    }
+}
+
+/**
+The siblings on the path from a transaction to the root of a Merkle tree,
+from the leaf up. It proves that the transaction is in a block knowing only
+the block's Merkle root, e.g. from its header, without its transactions.
+
+Proofs have a compact binary encoding, to be exchanged between
+implementations, e.g. with light clients:
+
+| bytes    | content                                         |
+|----------|-------------------------------------------------|
+| 1        | version, `PROOF_VERSION`                        |
+| 4        | number of siblings, big endian                  |
+| 33 each  | side, `0` for left or `1` for right, then hash  |
+
+```
+# use crate::blockchain::merkle::{MerkleProof, MerkleTree, Side};
+let hashes = vec![vec![1; 32], vec![2; 32], vec![3; 32]];
+let tree = MerkleTree::new(&hashes);
+let proof = tree.proof(&hashes[2]).unwrap();
+
+assert_eq!(1, proof.siblings.len());
+assert_eq!(Side::Left, proof.siblings[0].0);
+assert!(proof.verify(&tree.root(), &hashes[2]));
+assert!(!proof.verify(&tree.root(), &hashes[1]));
+
+let bytes = proof.to_bytes().unwrap();
+assert_eq!(Ok(proof), MerkleProof::from_bytes(&bytes));
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleProof {
+    /// Sibling of each level of the path that has one, from the leaf up.
+    pub siblings: Vec<(Side, Hash)>,
+}
+
+impl MerkleProof {
+    /// Does this proof show that the transaction is in the tree with that
+    /// root?
+    pub fn verify(&self, root: &[u8], transaction_hash: &[u8]) -> bool {
+        let computed =
+            self.siblings
+                .iter()
+                .fold(
+                    hash_leaf(transaction_hash),
+                    |hash, (side, sibling)| match side {
+                        Side::Left => hash_node(sibling, &hash),
+                        Side::Right => hash_node(&hash, sibling),
+                    },
+                );
+        computed == root
+    }
+
+    /// Encode this proof in its binary format.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let count = u32::try_from(self.siblings.len()).map_err(|_| "too many siblings")?;
+
+        let mut bytes = Vec::with_capacity(5 + self.siblings.len() * (1 + blake3::OUT_LEN));
+        bytes.push(PROOF_VERSION);
+        bytes.extend_from_slice(&count.to_be_bytes());
+        for (side, hash) in &self.siblings {
+            if hash.len() != blake3::OUT_LEN {
+                return Err("invalid hash length".to_string());
+            }
+            bytes.push(side.to_byte());
+            bytes.extend_from_slice(hash);
+        }
+        Ok(bytes)
+    }
+
+    /// Decode a proof from its binary format.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let (version, bytes) = bytes.split_first().ok_or("missing version")?;
+        if *version != PROOF_VERSION {
+            return Err(format!("unsupported proof version {}", version));
+        }
+
+        if bytes.len() < 4 {
+            return Err("missing number of siblings".to_string());
+        }
+        let (count, siblings) = bytes.split_at(4);
+        let count = u32::from_be_bytes([count[0], count[1], count[2], count[3]]) as usize;
+
+        if Some(siblings.len()) != count.checked_mul(1 + blake3::OUT_LEN) {
+            return Err("invalid proof length".to_string());
+        }
+
+        Ok(MerkleProof {
+            siblings: siblings
+                .chunks(1 + blake3::OUT_LEN)
+                .map(|sibling| Ok((Side::from_byte(sibling[0])?, sibling[1..].to_vec())))
+                .collect::<Result<_, Error>>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod merkle_tests {
+    use super::*;
+
+    fn hashes(count: u8) -> Vec<Hash> {
+        (0..count).map(|i| vec![i; 32]).collect()
+    }
+
+    #[test]
+    fn test_root() {
+        assert_eq!(
+            blake3::hash(&[]).as_bytes().to_vec(),
+            MerkleTree::new(hashes(0)).root()
+        );
+        assert_eq!(hash_leaf(&[0; 32]), MerkleTree::new(hashes(1)).root());
+
+        let leaves: Vec<Hash> = hashes(4).iter().map(|hash| hash_leaf(hash)).collect();
+        assert_eq!(
+            hash_node(
+                &hash_node(&leaves[0], &leaves[1]),
+                &hash_node(&leaves[2], &leaves[3])
+            ),
+            MerkleTree::new(hashes(4)).root()
+        );
+    }
+
+    #[test]
+    fn test_root_depends_on_order_and_count() {
+        let mut swapped = hashes(3);
+        swapped.swap(0, 1);
+        assert_ne!(
+            MerkleTree::new(hashes(3)).root(),
+            MerkleTree::new(swapped).root()
+        );
+
+        // the last hash is not duplicated to fill the tree
+        let mut duplicated = hashes(3);
+        duplicated.push(duplicated[2].clone());
+        assert_ne!(
+            MerkleTree::new(hashes(3)).root(),
+            MerkleTree::new(duplicated).root()
+        );
+    }
+
+    #[test]
+    fn test_proof() {
+        let hashes = hashes(5);
+        let tree = MerkleTree::new(&hashes);
+        let leaves: Vec<Hash> = hashes.iter().map(|hash| hash_leaf(hash)).collect();
+
+        assert_eq!(None, tree.proof(&[9; 32]));
+        assert_eq!(
+            Some(MerkleProof {
+                siblings: vec![
+                    (Side::Left, leaves[2].clone()),
+                    (Side::Left, hash_node(&leaves[0], &leaves[1])),
+                    (Side::Right, leaves[4].clone()),
+                ]
+            }),
+            tree.proof(&hashes[3])
+        );
+        // the last leaf has no sibling until the root
+        assert_eq!(
+            Some(MerkleProof {
+                siblings: vec![(
+                    Side::Left,
+                    hash_node(
+                        &hash_node(&leaves[0], &leaves[1]),
+                        &hash_node(&leaves[2], &leaves[3])
+                    )
+                )]
+            }),
+            tree.proof(&hashes[4])
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        for count in 1..10 {
+            let hashes = hashes(count);
+            let tree = MerkleTree::new(&hashes);
+            for hash in &hashes {
+                let proof = tree.proof(hash).unwrap();
+                assert!(proof.verify(&tree.root(), hash));
+                assert!(!proof.verify(&tree.root(), &[9; 32]));
+                assert!(!proof.verify(&[0; 32], hash));
+            }
+        }
+
+        let hashes = hashes(4);
+        let tree = MerkleTree::new(&hashes);
+        let mut forged = tree.proof(&hashes[1]).unwrap();
+        forged.siblings[0].0 = Side::Right;
+        assert!(!forged.verify(&tree.root(), &hashes[1]));
+
+        // a node of the tree is not a leaf
+        let node = hash_node(&hash_leaf(&hashes[0]), &hash_leaf(&hashes[1]));
+        let mut truncated = tree.proof(&hashes[1]).unwrap();
+        truncated.siblings.remove(0);
+        assert!(!truncated.verify(&tree.root(), &node));
+    }
+
+    #[test]
+    fn test_proof_encoding_roundtrip() {
+        let proof = MerkleProof {
+            siblings: vec![(Side::Left, vec![1; 32]), (Side::Right, vec![2; 32])],
+        };
+        let bytes = proof.to_bytes().unwrap();
+        assert_eq!(1 + 4 + 2 * 33, bytes.len());
+        assert_eq!(Ok(proof), MerkleProof::from_bytes(&bytes));
+
+        // a single transaction is its own root
+        let empty = MerkleTree::new(hashes(1)).proof(&[0; 32]).unwrap();
+        assert_eq!(
+            Ok(empty.clone()),
+            MerkleProof::from_bytes(&empty.to_bytes().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_proof_decoding_rejects_malformed_bytes() {
+        let bytes = MerkleProof {
+            siblings: vec![(Side::Left, vec![1; 32]), (Side::Right, vec![2; 32])],
+        }
+        .to_bytes()
+        .unwrap();
+
+        for length in 0..bytes.len() {
+            assert!(MerkleProof::from_bytes(&bytes[..length]).is_err());
+        }
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[0] = 2;
+        assert_eq!(
+            Err("unsupported proof version 2".to_string()),
+            MerkleProof::from_bytes(&wrong_version)
+        );
+
+        let mut wrong_side = bytes.clone();
+        wrong_side[5] = 2;
+        assert_eq!(
+            Err("invalid side 2".to_string()),
+            MerkleProof::from_bytes(&wrong_side)
+        );
+
+        let mut huge_count = bytes;
+        huge_count[1..5].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(
+            Err("invalid proof length".to_string()),
+            MerkleProof::from_bytes(&huge_count)
+        );
+
+        let invalid = MerkleProof {
+            siblings: vec![(Side::Left, vec![1; 3])],
+        };
+        assert_eq!(Err("invalid hash length".to_string()), invalid.to_bytes());
+    }
+
+    #[test]
+    fn test_proof_decoding_fuzz() {
+        // Decoding arbitrary bytes must never panic.
+        let mut seed = blake3::hash(b"fuzz");
+        for _ in 0..1000 {
+            seed = blake3::hash(seed.as_bytes());
+            let bytes = seed.as_bytes();
+            let length = bytes[0] as usize % bytes.len();
+            let mut input = bytes[..length].to_vec();
+            if let Some(version) = input.first_mut() {
+                *version = PROOF_VERSION;
+            }
+
+            if let Ok(proof) = MerkleProof::from_bytes(&input) {
+                assert_eq!(Ok(input), proof.to_bytes());
+            }
+        }
+    }
+}
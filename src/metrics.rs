@@ -0,0 +1,273 @@
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long the server waits for a client to send its request, or to read
+/// the response, before giving up on it.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Upper bounds of the latency histogram buckets, in seconds.
+const LATENCY_BUCKETS: [f64; 6] = [0.000_01, 0.000_1, 0.001, 0.01, 0.1, 1.0];
+
+/// A histogram of durations, with fixed buckets.
+#[derive(Debug, Default)]
+pub struct Histogram {
+    /// Number of observations in each bucket, cumulatively.
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    count: AtomicU64,
+    sum_nanos: AtomicU64,
+}
+
+impl Histogram {
+    /// Record a duration.
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (bucket, bound) in self.buckets.iter().zip(LATENCY_BUCKETS.iter()) {
+            if seconds <= *bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Number of recorded durations.
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
+/**
+Counters and histograms describing the activity of a node, shared between
+the components updating them and the server exposing them.
+
+```
+# use crate::blockchain::metrics::Metrics;
+# use std::sync::atomic::Ordering;
+let metrics = Metrics::default();
+metrics.blocks_applied.fetch_add(1, Ordering::Relaxed);
+
+assert!(metrics.render().contains("blockchain_blocks_applied_total 1\n"));
+```
+*/
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Number of blocks added to the chain.
+    pub blocks_applied: AtomicU64,
+
+    /// Number of blocks rejected.
+    pub blocks_rejected: AtomicU64,
+
+    /// Number of transactions applied as part of a block.
+    pub transactions_applied: AtomicU64,
+
+    /// Number of transactions currently in the mempool.
+    pub mempool_transactions: AtomicU64,
+
+    /// Size of the transactions currently in the mempool, in bytes.
+    pub mempool_size: AtomicU64,
+
+    /// Time taken to validate and apply a transaction.
+    pub transaction_validation: Histogram,
+
+    /// Number of hashes computed while mining blocks: its rate is the
+    /// hashrate.
+    pub mining_hashes: AtomicU64,
+}
+
+impl Metrics {
+    /// Render all metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: &AtomicU64| {
+            let _ = write!(
+                out,
+                "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n",
+                name = name,
+                kind = kind,
+                help = help,
+                value = value.load(Ordering::Relaxed)
+            );
+        };
+
+        metric(
+            "blockchain_blocks_applied_total",
+            "counter",
+            "Number of blocks added to the chain.",
+            &self.blocks_applied,
+        );
+        metric(
+            "blockchain_blocks_rejected_total",
+            "counter",
+            "Number of blocks rejected.",
+            &self.blocks_rejected,
+        );
+        metric(
+            "blockchain_transactions_applied_total",
+            "counter",
+            "Number of transactions applied as part of a block.",
+            &self.transactions_applied,
+        );
+        metric(
+            "blockchain_mempool_transactions",
+            "gauge",
+            "Number of transactions in the mempool.",
+            &self.mempool_transactions,
+        );
+        metric(
+            "blockchain_mempool_size_bytes",
+            "gauge",
+            "Size of the transactions in the mempool.",
+            &self.mempool_size,
+        );
+        metric(
+            "blockchain_mining_hashes_total",
+            "counter",
+            "Number of hashes computed while mining blocks.",
+            &self.mining_hashes,
+        );
+
+        let name = "blockchain_transaction_validation_seconds";
+        let histogram = &self.transaction_validation;
+        let _ = write!(
+            out,
+            "# HELP {name} Time taken to validate and apply a transaction.\n# TYPE {name} histogram\n",
+            name = name
+        );
+        for (bucket, bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS.iter()) {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{le=\"{}\"}} {}",
+                name,
+                bound,
+                bucket.load(Ordering::Relaxed)
+            );
+        }
+        let _ = write!(
+            out,
+            "{name}_bucket{{le=\"+Inf\"}} {count}\n{name}_sum {sum}\n{name}_count {count}\n",
+            name = name,
+            count = histogram.count(),
+            sum = histogram.sum_nanos.load(Ordering::Relaxed) as f64 / 1e9,
+        );
+
+        out
+    }
+}
+
+/// Serve the metrics on `GET /metrics`, forever. Each connection is handled
+/// on its own thread, so a slow client doesn't hold up the others.
+pub fn serve(listener: TcpListener, metrics: Arc<Metrics>) -> std::io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let metrics = metrics.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = handle_connection(stream, &metrics) {
+                tracing::debug!(error = %err, "metrics request failed");
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Answer a single HTTP request.
+fn handle_connection(mut stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+
+    let (status, body) = match request_line.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+        ["GET", "/metrics"] => ("200 OK", metrics.render()),
+        _ => ("404 Not Found", String::new()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod metrics_tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let metrics = Metrics::default();
+        metrics
+            .transaction_validation
+            .observe(Duration::from_micros(5));
+        metrics
+            .transaction_validation
+            .observe(Duration::from_millis(5));
+
+        let rendered = metrics.render();
+        assert!(rendered
+            .contains("blockchain_transaction_validation_seconds_bucket{le=\"0.00001\"} 1\n"));
+        assert!(
+            rendered.contains("blockchain_transaction_validation_seconds_bucket{le=\"0.01\"} 2\n")
+        );
+        assert!(
+            rendered.contains("blockchain_transaction_validation_seconds_bucket{le=\"+Inf\"} 2\n")
+        );
+        assert!(rendered.contains("blockchain_transaction_validation_seconds_count 2\n"));
+    }
+
+    #[test]
+    fn test_serve_metrics() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let metrics = Arc::new(Metrics::default());
+        metrics.blocks_applied.fetch_add(3, Ordering::Relaxed);
+
+        let server_metrics = metrics.clone();
+        let server = std::thread::spawn(move || {
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().unwrap();
+                handle_connection(stream, &server_metrics).unwrap();
+            }
+        });
+
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let response = get("/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("blockchain_blocks_applied_total 3\n"));
+
+        assert!(get("/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_serve_despite_silent_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || serve(listener, Arc::default()));
+
+        // connects, but never sends its request
+        let _silent = TcpStream::connect(address).unwrap();
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT / 2)).unwrap();
+        write!(stream, "GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::id::Id;
+use crate::Error;
+
+/// Longest name that can be registered, in bytes.
+pub const MAX_NAME_LENGTH: usize = 32;
+
+/// Who a name points to, and until when.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameRecord {
+    /// Account the name resolves to.
+    pub owner: Id,
+
+    /// Height from which the name is free to register again, unless renewed.
+    pub expires_at: usize,
+}
+
+impl NameRecord {
+    /// Is the name still owned at that height?
+    pub fn is_active(&self, height: usize) -> bool {
+        height < self.expires_at
+    }
+}
+
+/// Check that a name can be registered: short, lowercase letters, digits
+/// and dashes only, so that names cannot look alike.
+pub fn validate_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        return Err(format!(
+            "name must be 1 to {} characters long",
+            MAX_NAME_LENGTH
+        ));
+    }
+    if !name
+        .bytes()
+        .all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'-')
+    {
+        return Err("name can only contain lowercase letters, digits and dashes".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod names_tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_name() {
+        assert_eq!(Ok(()), validate_name("alice-42"));
+        assert!(validate_name("").is_err());
+        assert!(validate_name(&"a".repeat(MAX_NAME_LENGTH + 1)).is_err());
+        assert!(validate_name("Alice").is_err());
+        assert!(validate_name("alice.eth").is_err());
+        assert!(validate_name("аlice").is_err()); // cyrillic "а"
+    }
+
+    #[test]
+    fn test_is_active() {
+        let record = NameRecord {
+            owner: "alice".into(),
+            expires_at: 10,
+        };
+        assert!(record.is_active(9));
+        assert!(!record.is_active(10));
+    }
+}
@@ -0,0 +1,234 @@
+use crate::account::{Account, Amount};
+use crate::error::BlockchainError;
+use crate::id::Id;
+use crate::names::NameRecord;
+use crate::params::ChainParams;
+use crate::transaction::TransactionEvent;
+use crate::world::WorldState;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+/**
+A copy-on-write layer on top of a world state: changes are kept in the
+overlay, and the underlying world state is never modified. Accounts are only
+copied when a transaction changes them.
+
+```
+# use crate::blockchain::overlay::Overlay;
+# use crate::blockchain::testing::MockWorldState;
+# use crate::blockchain::transaction::{Transaction, TransactionRecord};
+# use crate::blockchain::world::WorldState;
+let world = MockWorldState::new()
+    .with_account("alice", 10)
+    .with_account("bob", 0);
+
+let mut overlay = Overlay::new(&world);
+let transfer = Transaction::new(
+    0,
+    TransactionRecord::SendTokens { to: "bob".into(), amount: 4 },
+    Some("alice".into()),
+);
+transfer.apply(&mut overlay).unwrap();
+
+assert_eq!(4, overlay.get_account_by_id(&"bob".into()).unwrap().tokens);
+assert_eq!(0, world.get_account_by_id(&"bob".into()).unwrap().tokens);
+```
+*/
+#[derive(Debug)]
+pub struct Overlay<'a, W: WorldState> {
+    base: &'a W,
+    accounts: HashMap<Id, Account>,
+    names: HashMap<String, NameRecord>,
+    events: Vec<TransactionEvent>,
+    params: Vec<(usize, ChainParams)>,
+    beneficiary: Option<Id>,
+    fees: Amount,
+}
+
+impl<'a, W: WorldState> Overlay<'a, W> {
+    /// Constructor
+    pub fn new(base: &'a W) -> Self {
+        Overlay {
+            base,
+            accounts: HashMap::new(),
+            names: HashMap::new(),
+            events: vec![],
+            params: vec![],
+            beneficiary: None,
+            fees: 0,
+        }
+    }
+
+    /// Credit the fees to an account, as the beneficiary of a block would
+    /// be. Without one, fees are held by the overlay.
+    pub fn with_beneficiary(mut self, beneficiary: Id) -> Self {
+        self.beneficiary = Some(beneficiary);
+        self
+    }
+
+    /// Fees paid while no beneficiary was set, e.g. when checking pending
+    /// transactions before knowing who mines them.
+    pub fn held_fees(&self) -> Amount {
+        self.fees
+    }
+
+    /// Economic rules scheduled by the transactions applied to the overlay,
+    /// along with the height they activate at, in order. They never apply to
+    /// the block being applied, so the overlay keeps following the rules of
+    /// the underlying world state.
+    pub fn scheduled_params(&self) -> &[(usize, ChainParams)] {
+        &self.params
+    }
+
+    /// Events emitted by the transactions applied to the overlay, in order.
+    pub fn into_events(self) -> Vec<TransactionEvent> {
+        self.events
+    }
+}
+
+impl<W: WorldState> WorldState for Overlay<'_, W> {
+    fn get_account_by_id(&self, id: &Id) -> Result<&Account, BlockchainError> {
+        match self.accounts.get(id) {
+            Some(account) => Ok(account),
+            None => self.base.get_account_by_id(id),
+        }
+    }
+
+    fn get_account_by_id_mut(&mut self, id: &Id) -> Result<&mut Account, BlockchainError> {
+        match self.accounts.entry(id.clone()) {
+            Entry::Occupied(account) => Ok(account.into_mut()),
+            Entry::Vacant(account) => Ok(account.insert(self.base.get_account_by_id(id)?.clone())),
+        }
+    }
+
+    fn add_account(&mut self, id: Id) -> Result<(), BlockchainError> {
+        if self.get_account_by_id(&id).is_ok() {
+            return Err(BlockchainError::AccountAlreadyExists(id));
+        }
+        self.accounts.insert(id, Account::new());
+        Ok(())
+    }
+
+    fn height(&self) -> usize {
+        self.base.height()
+    }
+
+    fn get_name(&self, name: &str) -> Option<&NameRecord> {
+        self.names.get(name).or_else(|| self.base.get_name(name))
+    }
+
+    fn set_name(&mut self, name: String, record: NameRecord) {
+        self.names.insert(name, record);
+    }
+
+    fn emit(&mut self, event: TransactionEvent) {
+        self.events.push(event);
+    }
+
+    fn params(&self) -> ChainParams {
+        self.base.params()
+    }
+
+    fn schedule_params(&mut self, height: usize, params: ChainParams) {
+        self.params.push((height, params));
+    }
+
+    fn collect_fee(&mut self, amount: Amount) -> Result<(), BlockchainError> {
+        let beneficiary = match self.beneficiary.clone() {
+            Some(beneficiary) => beneficiary,
+            None => {
+                self.fees = self
+                    .fees
+                    .checked_add(amount)
+                    .ok_or(BlockchainError::Overflow)?;
+                return Ok(());
+            }
+        };
+        let account = self.get_account_by_id_mut(&beneficiary)?;
+        account.tokens = account
+            .tokens
+            .checked_add(amount)
+            .ok_or(BlockchainError::Overflow)?;
+        self.emit(TransactionEvent::FeesCollected {
+            to: beneficiary,
+            amount,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod overlay_tests {
+    use super::*;
+    use crate::testing::MockWorldState;
+    use crate::transaction::{Transaction, TransactionRecord};
+
+    #[test]
+    fn test_overlay_leaves_base_untouched() {
+        let bob = Id::from_public_key(b"bob");
+        let world = MockWorldState::new().with_account("alice", 10);
+
+        let mut overlay = Overlay::new(&world);
+        let create = Transaction::new(0, TransactionRecord::create_user_account(b"bob"), None);
+        assert_eq!(Ok(()), create.apply(&mut overlay));
+        assert_eq!(
+            Err(BlockchainError::AccountAlreadyExists("alice".into())),
+            overlay.add_account("alice".into())
+        );
+
+        let transfer = Transaction::new(
+            1,
+            TransactionRecord::SendTokens {
+                to: bob.clone(),
+                amount: 3,
+            },
+            Some("alice".into()),
+        );
+        assert_eq!(Ok(()), transfer.apply(&mut overlay));
+        assert_eq!(
+            7,
+            overlay.get_account_by_id(&"alice".into()).unwrap().tokens
+        );
+        assert_eq!(3, overlay.get_account_by_id(&bob).unwrap().tokens);
+        assert_eq!(2, overlay.into_events().len());
+
+        assert_eq!(10, world.get_account_by_id(&"alice".into()).unwrap().tokens);
+        assert!(world.get_account_by_id(&bob).is_err());
+    }
+
+    #[test]
+    fn test_overlay_fees() {
+        let world = MockWorldState::new()
+            .with_account("alice", 10)
+            .with_account("miner", 0);
+        let transfer = Transaction::new(
+            1,
+            TransactionRecord::SendTokens {
+                to: "miner".into(),
+                amount: 3,
+            },
+            Some("alice".into()),
+        )
+        .with_fee(2);
+
+        let mut overlay = Overlay::new(&world);
+        assert_eq!(Ok(()), transfer.apply(&mut overlay));
+        assert_eq!(2, overlay.held_fees());
+        assert_eq!(
+            3,
+            overlay.get_account_by_id(&"miner".into()).unwrap().tokens
+        );
+
+        let mut overlay = Overlay::new(&world).with_beneficiary("miner".into());
+        assert_eq!(Ok(()), transfer.apply(&mut overlay));
+        assert_eq!(0, overlay.held_fees());
+        assert_eq!(
+            5,
+            overlay.get_account_by_id(&"miner".into()).unwrap().tokens
+        );
+        assert_eq!(
+            5,
+            overlay.get_account_by_id(&"alice".into()).unwrap().tokens
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use std::convert::Infallible;
+
+/// Maximum number of items returned in a single page.
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/**
+A page of results of a list query.
+
+Items are always listed in the same order, so walking the pages by passing
+`next` back as the cursor of the following query lists every item once.
+
+```
+# use crate::blockchain::page::Page;
+let page = Page::collect(vec![(0, "a"), (1, "b"), (2, "c")].into_iter(), 2);
+
+assert_eq!(vec!["a", "b"], page.items);
+assert_eq!(Some(2), page.next);
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page<T, C> {
+    /// Items of this page.
+    pub items: Vec<T>,
+
+    /// Cursor of the next page, if there are more items.
+    pub next: Option<C>,
+}
+
+impl<T, C> Page<T, C> {
+    /// Build a page from the items following the cursor, each along with its
+    /// own cursor. The limit is capped to `MAX_PAGE_SIZE`.
+    pub fn collect<I: Iterator<Item = (C, T)>>(items: I, limit: usize) -> Self {
+        match Self::try_collect(items.map(Ok::<_, Infallible>), limit) {
+            Ok(page) => page,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Build a page like `collect`, from items that can fail to be read,
+    /// e.g. loaded from a store. Stops at the first error.
+    pub fn try_collect<E, I: Iterator<Item = Result<(C, T), E>>>(
+        items: I,
+        limit: usize,
+    ) -> Result<Self, E> {
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+
+        let mut page = Page {
+            items: vec![],
+            next: None,
+        };
+        for item in items {
+            let (cursor, item) = item?;
+            if page.items.len() == limit {
+                page.next = Some(cursor);
+                break;
+            }
+            page.items.push(item);
+        }
+        Ok(page)
+    }
+}
+
+#[cfg(test)]
+mod page_tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_last_page() {
+        let page = Page::collect(vec![(0, "a"), (1, "b")].into_iter(), 2);
+        assert_eq!(vec!["a", "b"], page.items);
+        assert_eq!(None, page.next);
+    }
+
+    #[test]
+    fn test_try_collect_stops_at_error() {
+        let items = vec![Ok((0, "a")), Err("unreadable"), Ok((2, "c"))];
+        assert_eq!(Err("unreadable"), Page::try_collect(items.into_iter(), 2));
+
+        let items = vec![Ok((0, "a")), Ok((1, "b")), Err("unreadable")];
+        assert_eq!(
+            Ok(Page {
+                items: vec!["a"],
+                next: Some(1)
+            }),
+            Page::try_collect(items.into_iter(), 1)
+        );
+    }
+
+    #[test]
+    fn test_collect_caps_limit() {
+        let page = Page::collect((0..2 * MAX_PAGE_SIZE).map(|i| (i, i)), usize::MAX);
+        assert_eq!(MAX_PAGE_SIZE, page.items.len());
+        assert_eq!(Some(MAX_PAGE_SIZE), page.next);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::account::Amount;
+use crate::encoding::{put_u32, put_u64, Reader};
+use crate::Error;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+/// Size of economic rules once encoded by `put_params`, in bytes.
+pub(crate) const ENCODED_PARAMS_SIZE: usize = 3 * std::mem::size_of::<u64>() + 1 + 4;
+
+/**
+Economic rules of the chain. They are set at genesis, and the system
+authority can change them from a future height on, with a
+`TransactionRecord::ScheduleParams` transaction.
+
+```
+# use crate::blockchain::params::ChainParams;
+let params = ChainParams {
+    min_transfer: 10,
+    min_balance: 100,
+    ..ChainParams::default()
+};
+
+assert!(!params.is_dust(0));
+assert!(params.is_dust(99));
+assert!(!params.is_dust(100));
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChainParams {
+    /// Smallest number of tokens that can be sent at once.
+    pub min_transfer: Amount,
+
+    /// Smallest number of tokens an account can hold, unless it is empty.
+    pub min_balance: Amount,
+
+    /// For how many blocks a name is registered, or renewed.
+    pub name_registration_period: usize,
+
+    /// Can a faucet hand out free tokens? Only on development and test
+    /// chains, where tokens are worthless.
+    pub faucet: bool,
+
+    /// Minimum number of leading zero bits the hash of each block must have.
+    /// The actual difficulty adjusts to the observed block times.
+    pub difficulty: u32,
+}
+
+impl Default for ChainParams {
+    fn default() -> Self {
+        ChainParams {
+            min_transfer: 0,
+            min_balance: 0,
+            // about a year, with a block every minute
+            name_registration_period: 525_600,
+            faucet: false,
+            difficulty: 0,
+        }
+    }
+}
+
+impl ChainParams {
+    /// Would an account holding that many tokens be a dust account, i.e.
+    /// not empty but below the minimum balance?
+    pub fn is_dust(&self, tokens: Amount) -> bool {
+        tokens > 0 && tokens < self.min_balance
+    }
+}
+
+/// Append the economic rules of a chain.
+pub(crate) fn put_params(out: &mut Vec<u8>, params: &ChainParams) {
+    put_u64(out, params.min_transfer);
+    put_u64(out, params.min_balance);
+    put_u64(out, params.name_registration_period as u64);
+    out.push(params.faucet.into());
+    put_u32(out, params.difficulty);
+}
+
+/// Read rules appended by `put_params`.
+pub(crate) fn get_params(reader: &mut Reader) -> Result<ChainParams, Error> {
+    Ok(ChainParams {
+        min_transfer: reader.get_u64()?,
+        min_balance: reader.get_u64()?,
+        name_registration_period: usize::try_from(reader.get_u64()?)
+            .map_err(|_| "value out of range")?,
+        faucet: reader.get_u8()? != 0,
+        difficulty: reader.get_u32()?,
+    })
+}
+
+/**
+The economic rules in effect at each height: changes are scheduled ahead
+of time, and activate at a given height.
+
+```
+# use crate::blockchain::params::{ChainParams, ParamsSchedule};
+let mut schedule = ParamsSchedule::new(ChainParams::default());
+let stricter = ChainParams {
+    min_transfer: 10,
+    ..ChainParams::default()
+};
+schedule.schedule(100, stricter.clone());
+
+assert_eq!(&ChainParams::default(), schedule.at(99));
+assert_eq!(&stricter, schedule.at(100));
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParamsSchedule {
+    /// Rules, by the height they activate at.
+    changes: BTreeMap<usize, ChainParams>,
+}
+
+impl ParamsSchedule {
+    /// Constructor, with the rules in effect from genesis.
+    pub fn new(genesis: ChainParams) -> Self {
+        let mut changes = BTreeMap::new();
+        changes.insert(0, genesis);
+        ParamsSchedule { changes }
+    }
+
+    /// Make new rules activate at some height, replacing any change
+    /// already scheduled at that height, or the genesis rules at height 0.
+    pub fn schedule(&mut self, height: usize, params: ChainParams) {
+        self.changes.insert(height, params);
+    }
+
+    /// All the rules, along with the height they activate at, from genesis.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &ChainParams)> {
+        self.changes
+            .iter()
+            .map(|(height, params)| (*height, params))
+    }
+
+    /// The rules in effect at some height.
+    pub fn at(&self, height: usize) -> &ChainParams {
+        self.changes
+            .range(..=height)
+            .next_back()
+            .map(|(_, params)| params)
+            .expect("genesis rules are always set")
+    }
+}
+
+impl Default for ParamsSchedule {
+    fn default() -> Self {
+        Self::new(ChainParams::default())
+    }
+}
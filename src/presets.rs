@@ -0,0 +1,168 @@
+use crate::account::{AccountType, Amount};
+use crate::genesis::{Allocation, GenesisConfig};
+use crate::id::Id;
+use crate::params::ChainParams;
+use crate::Error;
+
+/// Tokens held by the treasury of public networks from genesis.
+const TREASURY_ALLOCATION: Amount = 1_000_000_000;
+/// Tokens available to the faucet of test networks.
+const FAUCET_ALLOCATION: Amount = 1_000_000_000_000;
+
+/// Key of the treasury of public networks.
+const TREASURY_KEY: &str = "936449ee77e068a318c2687336e93d406bf5dff40cc947a17193d718fe21e67f";
+/// Key of the faucet of the test network.
+const TESTNET_FAUCET_KEY: &str = "2b6c8c93d35d4a3732ae05fbcd207819fef434b21e713869c3cba8d9061dc2d0";
+/// Key of the faucet of the development network.
+const DEVNET_FAUCET_KEY: &str = "ab2a9e744535ace43a8e28bfe750f9f8b3b57dfa37c5085f281ebc8b4bf88991";
+
+/**
+A well-known network, with a predefined genesis, so that nodes don't have to
+share genesis files for common setups.
+
+```
+# use crate::blockchain::blockchain::Blockchain;
+# use crate::blockchain::presets::Network;
+let network = Network::from_name("testnet").unwrap();
+let chain = Blockchain::from_genesis(&network.genesis()).unwrap();
+
+assert_eq!("blockchain-testnet", network.chain_id());
+assert!(chain.params_at(0).faucet);
+```
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    /// The main network, whose tokens have value.
+    Mainnet,
+
+    /// A public test network, with a faucet.
+    Testnet,
+
+    /// A local development network, with a faucet and no minimum amounts.
+    Devnet,
+}
+
+impl Network {
+    /// All the networks, in order of importance.
+    pub const ALL: [Network; 3] = [Network::Mainnet, Network::Testnet, Network::Devnet];
+
+    /// The network with that name, e.g. `testnet`.
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|network| network.name() == name)
+            .ok_or_else(|| format!("unknown network {}", name))
+    }
+
+    /// Name of the network, as selected in the configuration.
+    pub fn name(self) -> &'static str {
+        match self {
+            Network::Mainnet => "mainnet",
+            Network::Testnet => "testnet",
+            Network::Devnet => "devnet",
+        }
+    }
+
+    /// Identifies the chain of the network, e.g. in a `ChainRegistry`.
+    pub fn chain_id(self) -> String {
+        format!("blockchain-{}", self.name())
+    }
+
+    /// Peers to connect to on startup, as `host:port`.
+    /// None yet: no public node runs these networks.
+    pub fn seeds(self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Genesis of the network. Each network starts at a different time.
+    pub fn genesis(self) -> GenesisConfig {
+        match self {
+            Network::Mainnet => GenesisConfig {
+                timestamp: 1_700_000_000,
+                max_supply: Some(21 * TREASURY_ALLOCATION),
+                params: ChainParams {
+                    min_transfer: 1,
+                    min_balance: 10,
+                    ..ChainParams::default()
+                },
+                allocations: vec![system_allocation(TREASURY_KEY, TREASURY_ALLOCATION)],
+            },
+            Network::Testnet => GenesisConfig {
+                timestamp: 1_700_000_001,
+                max_supply: None,
+                params: ChainParams {
+                    min_transfer: 1,
+                    min_balance: 10,
+                    faucet: true,
+                    ..ChainParams::default()
+                },
+                allocations: vec![
+                    system_allocation(TREASURY_KEY, TREASURY_ALLOCATION),
+                    user_allocation(TESTNET_FAUCET_KEY, FAUCET_ALLOCATION),
+                ],
+            },
+            Network::Devnet => GenesisConfig {
+                timestamp: 1_700_000_002,
+                max_supply: None,
+                params: ChainParams {
+                    faucet: true,
+                    ..ChainParams::default()
+                },
+                allocations: vec![user_allocation(DEVNET_FAUCET_KEY, FAUCET_ALLOCATION)],
+            },
+        }
+    }
+}
+
+fn system_allocation(public_key: &str, tokens: Amount) -> Allocation {
+    Allocation {
+        account_type: AccountType::System,
+        ..user_allocation(public_key, tokens)
+    }
+}
+
+fn user_allocation(public_key: &str, tokens: Amount) -> Allocation {
+    let public_key: Vec<u8> = (0..public_key.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&public_key[i..i + 2], 16).expect("invalid preset key"))
+        .collect();
+    Allocation {
+        account: Id::from_public_key(&public_key),
+        public_key,
+        tokens,
+        account_type: AccountType::User,
+    }
+}
+
+#[cfg(test)]
+mod presets_tests {
+    use super::*;
+    use crate::blockchain::Blockchain;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_presets_are_valid_and_distinct() {
+        let mut chain_ids = HashSet::new();
+        let mut timestamps = HashSet::new();
+        for network in Network::ALL {
+            assert_eq!(Ok(network), Network::from_name(network.name()));
+
+            let genesis = network.genesis();
+            assert_eq!(Ok(()), genesis.validate(), "{}", network.name());
+            assert!(Blockchain::from_genesis(&genesis).is_ok());
+
+            assert!(chain_ids.insert(network.chain_id()));
+            assert!(timestamps.insert(genesis.timestamp));
+        }
+        assert!(!Network::Mainnet.genesis().params.faucet);
+    }
+
+    #[test]
+    fn test_unknown_network() {
+        assert_eq!(
+            Err("unknown network moonnet".to_string()),
+            Network::from_name("moonnet")
+        );
+    }
+}
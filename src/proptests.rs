@@ -0,0 +1,148 @@
+use crate::account::Amount;
+use crate::block::Block;
+use crate::blockchain::Blockchain;
+use crate::clock::MockClock;
+use crate::id::Id;
+use crate::testing::ChainBuilder;
+use crate::transaction::{Transaction, TransactionRecord};
+use crate::world::WorldState;
+use proptest::prelude::*;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Few account IDs, so that generated transactions often involve the same
+/// accounts.
+const ACCOUNTS: [&str; 4] = ["alice", "bob", "carol", "dave"];
+
+fn arb_id() -> impl Strategy<Value = Id> {
+    prop::sample::select(&ACCOUNTS[..]).prop_map(Id::from)
+}
+
+fn arb_record() -> impl Strategy<Value = TransactionRecord> {
+    prop_oneof![
+        prop::collection::vec(any::<u8>(), 0..32)
+            .prop_map(|public_key| TransactionRecord::create_user_account(&public_key)),
+        (arb_id(), any::<Amount>())
+            .prop_map(|(to, amount)| TransactionRecord::SendTokens { to, amount }),
+    ]
+}
+
+impl Arbitrary for Transaction {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            any::<u64>(),
+            arb_record(),
+            prop::option::of(arb_id()),
+            any::<u32>(),
+        )
+            .prop_map(|(nonce, record, from, secs)| {
+                let clock = MockClock::new(UNIX_EPOCH + Duration::from_secs(secs.into()));
+                Transaction::with_clock(nonce, record, from, &clock)
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Block {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop::collection::vec(any::<Transaction>(), 0..8)
+            .prop_map(|transactions| {
+                let mut block = Block::new();
+                block.transactions = transactions;
+                block.seal();
+                block
+            })
+            .boxed()
+    }
+}
+
+/// Transfers between funded accounts, grouped in blocks.
+type Transfers = Vec<Vec<(usize, usize, Amount)>>;
+
+/// A valid chain: funded accounts, then blocks of transfers between them,
+/// along with the number of tokens minted at genesis.
+/// Blocks that would be rejected (e.g. not enough tokens) are left out.
+fn arb_chain() -> impl Strategy<Value = (Blockchain, Amount)> {
+    let balances = prop::collection::vec(0..1_000 as Amount, ACCOUNTS.len());
+    let transfers: BoxedStrategy<Transfers> = prop::collection::vec(
+        prop::collection::vec(
+            (0..ACCOUNTS.len(), 0..ACCOUNTS.len(), 0..500 as Amount),
+            0..4,
+        ),
+        0..6,
+    )
+    .boxed();
+
+    (balances, transfers).prop_map(|(balances, transfers)| {
+        let minted = balances.iter().sum();
+        let mut builder = ChainBuilder::new();
+        for (id, tokens) in ACCOUNTS.iter().zip(balances) {
+            builder = builder.with_account(id, tokens);
+        }
+        let mut chain = builder.build().unwrap();
+
+        for transfers in transfers {
+            let mut block = Block::new();
+            block.header.height = chain.height();
+            block.header.previous_hash = chain.get_last_block_hash().cloned();
+            for (from, to, amount) in transfers {
+                block.transactions.push(Transaction::new(
+                    0,
+                    TransactionRecord::SendTokens {
+                        to: ACCOUNTS[to].into(),
+                        amount,
+                    },
+                    Some(ACCOUNTS[from].into()),
+                ));
+            }
+            block.seal();
+            let _ = chain.add_block(block);
+        }
+        (chain, minted)
+    })
+}
+
+proptest! {
+    #[test]
+    fn prop_transaction_hash_is_deterministic(transaction in any::<Transaction>()) {
+        prop_assert_eq!(transaction.calculate_hash(), transaction.calculate_hash());
+    }
+
+    #[test]
+    fn prop_block_hash_is_valid(block in any::<Block>()) {
+        prop_assert!(block.is_hash_valid());
+    }
+
+    #[test]
+    fn prop_bloom_has_no_false_negatives(block in any::<Block>()) {
+        let mut block = block;
+        block.header.bloom = block.calculate_bloom();
+        for transaction in &block.transactions {
+            for id in transaction.touched_accounts() {
+                prop_assert!(block.involves(id));
+            }
+        }
+    }
+
+    #[test]
+    fn prop_transfers_conserve_supply((chain, minted) in arb_chain()) {
+        prop_assert_eq!(minted, chain.total_supply());
+        prop_assert_eq!(Ok(()), chain.verify_chain());
+    }
+
+    #[test]
+    fn prop_blocks_are_linked((chain, _) in arb_chain()) {
+        let blocks: Vec<Block> = chain.iter_blocks(..).collect::<Result<_, _>>().unwrap();
+        for pair in blocks.windows(2) {
+            prop_assert_eq!(&pair[0].hash, &pair[1].header.previous_hash);
+        }
+        for block in blocks {
+            prop_assert!(block.is_hash_valid());
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use crate::account::Amount;
+use crate::blockchain::Blockchain;
+use crate::genesis::GenesisConfig;
+use crate::transaction::{Transaction, TransactionRecord};
+use crate::world::WorldState;
+use crate::Nonce;
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use std::time::UNIX_EPOCH;
+
+/// A chain, driven from Python.
+#[pyclass(name = "Blockchain", unsendable)]
+pub struct PyBlockchain(Blockchain);
+
+#[pymethods]
+impl PyBlockchain {
+    /// Constructor
+    #[new]
+    fn new() -> Self {
+        PyBlockchain(Blockchain::new())
+    }
+
+    /// Start a chain from a genesis configuration, in TOML.
+    #[staticmethod]
+    fn from_genesis(genesis: &str) -> PyResult<Self> {
+        GenesisConfig::from_toml(genesis)
+            .and_then(|genesis| Blockchain::from_genesis(&genesis).map_err(String::from))
+            .map(PyBlockchain)
+            .map_err(PyValueError::new_err)
+    }
+
+    /// Number of blocks in the chain.
+    fn height(&self) -> usize {
+        self.0.height()
+    }
+
+    /// Number of tokens held by an account.
+    fn balance(&self, id: &str) -> PyResult<Amount> {
+        self.0
+            .get_account_by_id(&id.into())
+            .map(|account| account.tokens)
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
+    /// Queue a transaction in the mempool, if it is valid against the
+    /// current state.
+    fn submit(&mut self, transaction: &PyTransaction) -> PyResult<()> {
+        self.0
+            .submit_transaction(transaction.0.clone())
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
+    /// Number of transactions waiting to be included in a block.
+    fn pending_count(&self) -> usize {
+        self.0.pending_count()
+    }
+
+    /// A block, as a dict with its hash, previous hash, creation time in
+    /// seconds since the epoch and transactions.
+    fn block<'py>(&self, py: Python<'py>, height: usize) -> PyResult<Bound<'py, PyDict>> {
+        let block = self
+            .0
+            .get_block(height)
+            .map_err(|err| PyValueError::new_err(err.to_string()))?
+            .ok_or_else(|| PyIndexError::new_err("no block at this height"))?;
+
+        let dict = PyDict::new(py);
+        dict.set_item("height", height)?;
+        dict.set_item("hash", block.hash.as_deref().map(|h| PyBytes::new(py, h)))?;
+        dict.set_item(
+            "previous_hash",
+            block
+                .header
+                .previous_hash
+                .as_deref()
+                .map(|h| PyBytes::new(py, h)),
+        )?;
+        dict.set_item(
+            "created_at",
+            block
+                .header
+                .timestamp
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64(),
+        )?;
+        dict.set_item(
+            "transactions",
+            block
+                .iter_transactions()
+                .map(|transaction| PyTransaction(transaction.clone()))
+                .collect::<Vec<_>>(),
+        )?;
+        Ok(dict)
+    }
+}
+
+/// A transaction, built or read from Python.
+#[pyclass(name = "Transaction")]
+pub struct PyTransaction(Transaction);
+
+#[pymethods]
+impl PyTransaction {
+    /// A transaction sending tokens from an account to another.
+    #[staticmethod]
+    fn transfer(nonce: Nonce, from: &str, to: &str, amount: Amount) -> Self {
+        PyTransaction(Transaction::new(
+            nonce,
+            TransactionRecord::SendTokens {
+                to: to.into(),
+                amount,
+            },
+            Some(from.into()),
+        ))
+    }
+
+    /// A transaction creating the account owned by a public key.
+    #[staticmethod]
+    fn create_account(public_key: &[u8]) -> Self {
+        PyTransaction(Transaction::new(
+            0,
+            TransactionRecord::create_user_account(public_key),
+            None,
+        ))
+    }
+
+    #[getter]
+    fn nonce(&self) -> Nonce {
+        self.0.nonce
+    }
+
+    #[getter]
+    fn sender(&self) -> Option<String> {
+        self.0.from_account_id.as_ref().map(ToString::to_string)
+    }
+
+    #[getter]
+    fn hash<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.calculate_hash())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+/// The `blockchain` Python extension.
+#[pymodule]
+fn blockchain(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyBlockchain>()?;
+    m.add_class::<PyTransaction>()?;
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use crate::blockchain::Blockchain;
+use crate::Error;
+use std::collections::BTreeMap;
+
+/// Identifies a chain among the ones hosted by a node, e.g. `mainnet`.
+pub type ChainId = String;
+
+/**
+Independent chains hosted by the same node, by chain ID. Each chain keeps its
+own state, mempool and subscribers.
+
+```
+# use crate::blockchain::blockchain::Blockchain;
+# use crate::blockchain::registry::ChainRegistry;
+let mut registry = ChainRegistry::new();
+registry.add("mainnet", Blockchain::new()).unwrap();
+registry.add("devnet", Blockchain::new()).unwrap();
+
+assert!(registry.get("devnet").is_some());
+assert_eq!(vec!["devnet", "mainnet"], registry.ids().collect::<Vec<_>>());
+```
+*/
+#[derive(Debug, Default)]
+pub struct ChainRegistry {
+    chains: BTreeMap<ChainId, Blockchain>,
+}
+
+impl ChainRegistry {
+    /// Constructor
+    pub fn new() -> Self {
+        ChainRegistry {
+            chains: BTreeMap::new(),
+        }
+    }
+
+    /// Host a new chain.
+    pub fn add(&mut self, id: &str, chain: Blockchain) -> Result<(), Error> {
+        if self.chains.contains_key(id) {
+            return Err(format!("chain {} is already hosted", id));
+        }
+        tracing::info!(chain = id, "chain added");
+        self.chains.insert(id.to_string(), chain);
+        Ok(())
+    }
+
+    /// Stop hosting a chain, and give it back.
+    pub fn remove(&mut self, id: &str) -> Option<Blockchain> {
+        self.chains.remove(id)
+    }
+
+    /// A hosted chain, by ID.
+    pub fn get(&self, id: &str) -> Option<&Blockchain> {
+        self.chains.get(id)
+    }
+
+    /// A hosted chain, by ID, to add blocks or transactions to it.
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut Blockchain> {
+        self.chains.get_mut(id)
+    }
+
+    /// IDs of all the hosted chains, in alphabetical order.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.chains.keys().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod registry_tests {
+    use super::*;
+    use crate::transaction::{Transaction, TransactionRecord};
+
+    #[test]
+    fn test_chains_are_isolated() {
+        let mut registry = ChainRegistry::new();
+        registry.add("mainnet", Blockchain::new()).unwrap();
+        registry.add("devnet", Blockchain::new()).unwrap();
+        assert_eq!(
+            Err("chain devnet is already hosted".to_string()),
+            registry.add("devnet", Blockchain::new())
+        );
+
+        let transaction = Transaction::new(0, TransactionRecord::create_user_account(b"hi"), None);
+        registry
+            .get_mut("devnet")
+            .unwrap()
+            .mempool_mut()
+            .add(transaction)
+            .unwrap();
+        assert_eq!(1, registry.get("devnet").unwrap().mempool().len());
+        assert!(registry.get("mainnet").unwrap().mempool().is_empty());
+
+        assert!(registry.remove("devnet").is_some());
+        assert!(registry.get("devnet").is_none());
+        assert_eq!(vec!["mainnet"], registry.ids().collect::<Vec<_>>());
+    }
+}
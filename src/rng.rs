@@ -0,0 +1,82 @@
+use std::sync::Mutex;
+
+/// Provides random bytes, e.g. to generate IDs.
+pub trait Rng {
+    /// Fill `dest` with random bytes.
+    fn fill(&self, dest: &mut [u8]);
+}
+
+/// The cryptographically secure generator of the operating system.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OsRng;
+
+impl Rng for OsRng {
+    /// Panics if the operating system has no random source.
+    fn fill(&self, dest: &mut [u8]) {
+        getrandom::fill(dest).expect("the operating system has no random source");
+    }
+}
+
+/**
+A generator that always gives the same bytes for the same seed, for
+deterministic tests and simulations. Not secure: anyone knowing the seed
+can predict its output.
+
+```
+# use crate::blockchain::rng::{Rng, SeededRng};
+let (a, b) = (SeededRng::new(42), SeededRng::new(42));
+let (mut x, mut y) = ([0; 8], [0; 8]);
+a.fill(&mut x);
+b.fill(&mut y);
+
+assert_eq!(x, y);
+```
+*/
+#[derive(Debug)]
+pub struct SeededRng {
+    seed: u64,
+    calls: Mutex<u64>,
+}
+
+impl SeededRng {
+    /// Constructor
+    pub fn new(seed: u64) -> Self {
+        SeededRng {
+            seed,
+            calls: Mutex::new(0),
+        }
+    }
+}
+
+impl Rng for SeededRng {
+    fn fill(&self, dest: &mut [u8]) {
+        let mut calls = self.calls.lock().unwrap();
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&self.seed.to_be_bytes());
+        hasher.update(&calls.to_be_bytes());
+        hasher.finalize_xof().fill(dest);
+        *calls += 1;
+    }
+}
+
+#[cfg(test)]
+mod rng_tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_rng() {
+        let rng = SeededRng::new(1);
+        let (mut first, mut second) = ([0; 16], [0; 16]);
+        rng.fill(&mut first);
+        rng.fill(&mut second);
+        assert_ne!(first, second);
+
+        let mut other = [0; 16];
+        SeededRng::new(2).fill(&mut other);
+        assert_ne!(first, other);
+
+        let mut again = [0; 16];
+        SeededRng::new(1).fill(&mut again);
+        assert_eq!(first, again);
+    }
+}
@@ -0,0 +1,249 @@
+use crate::Error;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// A recurring maintenance task.
+pub struct Task {
+    /// Name of the task, for logs.
+    pub name: String,
+
+    /// Time between two runs.
+    pub interval: Duration,
+
+    /// Maximum random delay added to each interval, so that tasks started
+    /// together don't keep running at the same time.
+    pub jitter: Duration,
+
+    /// Maximum time to wait before retrying a failing task.
+    pub max_backoff: Duration,
+
+    run: Box<dyn FnMut() -> Result<(), Error> + Send>,
+}
+
+impl Task {
+    /// Constructor, without jitter and with a maximum backoff of ten intervals.
+    pub fn new<F>(name: &str, interval: Duration, run: F) -> Self
+    where
+        F: FnMut() -> Result<(), Error> + Send + 'static,
+    {
+        Task {
+            name: name.to_string(),
+            interval,
+            jitter: Duration::from_secs(0),
+            max_backoff: interval * 10,
+            run: Box::new(run),
+        }
+    }
+
+    /// Delay before the next run, given the number of consecutive failures
+    /// so far and how many times the task ran.
+    /// Failing tasks wait twice as long after each failure, up to
+    /// `max_backoff`.
+    fn next_delay(&self, failures: u32, runs: u64) -> Duration {
+        if failures > 0 {
+            let backoff = self
+                .interval
+                .checked_mul(2u32.saturating_pow(failures))
+                .unwrap_or(self.max_backoff);
+            return backoff.min(self.max_backoff);
+        }
+
+        self.interval + self.jitter_for(runs)
+    }
+
+    /// Pseudo-random delay between 0 and `jitter`, different for every run.
+    fn jitter_for(&self, runs: u64) -> Duration {
+        if self.jitter == Duration::from_secs(0) {
+            return self.jitter;
+        }
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(self.name.as_bytes());
+        hasher.update(&runs.to_be_bytes());
+        let hash = hasher.finalize();
+        let bytes = hash.as_bytes();
+        let fraction = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+        self.jitter.mul_f64(fraction as f64 / u32::MAX as f64)
+    }
+}
+
+impl std::fmt::Debug for Task {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Task")
+            .field("name", &self.name)
+            .field("interval", &self.interval)
+            .field("jitter", &self.jitter)
+            .field("max_backoff", &self.max_backoff)
+            .finish()
+    }
+}
+
+/**
+Runs recurring maintenance tasks on a background thread, until shut down.
+
+```
+# use crate::blockchain::scheduler::{Scheduler, Task};
+# use std::time::Duration;
+let mut scheduler = Scheduler::new();
+scheduler.add(Task::new("prune", Duration::from_secs(60), || Ok(())));
+
+let running = scheduler.start();
+running.shutdown();
+```
+*/
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    tasks: Vec<Task>,
+}
+
+impl Scheduler {
+    /// Constructor
+    pub fn new() -> Self {
+        Scheduler { tasks: vec![] }
+    }
+
+    /// Schedule a task. Its first run happens after one interval.
+    pub fn add(&mut self, task: Task) {
+        self.tasks.push(task);
+    }
+
+    /// Start running the tasks in the background.
+    pub fn start(self) -> RunningScheduler {
+        let (shutdown, shutdown_requested) = channel();
+        let thread = std::thread::spawn(move || self.run(shutdown_requested));
+        RunningScheduler { shutdown, thread }
+    }
+
+    fn run(mut self, shutdown_requested: Receiver<()>) {
+        let now = Instant::now();
+        let mut states: Vec<TaskState> = self
+            .tasks
+            .iter()
+            .map(|task| TaskState {
+                next_run: now + task.next_delay(0, 0),
+                failures: 0,
+                runs: 0,
+            })
+            .collect();
+
+        loop {
+            let next_run = match states.iter().map(|state| state.next_run).min() {
+                Some(next_run) => next_run,
+                None => {
+                    // nothing to run: only wait for the shutdown
+                    let _ = shutdown_requested.recv();
+                    return;
+                }
+            };
+
+            let timeout = next_run.saturating_duration_since(Instant::now());
+            match shutdown_requested.recv_timeout(timeout) {
+                Err(RecvTimeoutError::Timeout) => {}
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            let now = Instant::now();
+            for (task, state) in self.tasks.iter_mut().zip(states.iter_mut()) {
+                if state.next_run > now {
+                    continue;
+                }
+
+                state.runs += 1;
+                match (task.run)() {
+                    Ok(()) => state.failures = 0,
+                    Err(err) => {
+                        state.failures = state.failures.saturating_add(1);
+                        tracing::warn!(task = %task.name, error = %err, failures = state.failures, "task failed");
+                    }
+                }
+                state.next_run = Instant::now() + task.next_delay(state.failures, state.runs);
+            }
+        }
+    }
+}
+
+/// Scheduling state of a task.
+struct TaskState {
+    next_run: Instant,
+    failures: u32,
+    runs: u64,
+}
+
+/// Handle on a started scheduler.
+#[derive(Debug)]
+pub struct RunningScheduler {
+    shutdown: Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+impl RunningScheduler {
+    /// Stop running tasks, and wait for the task currently running if any.
+    pub fn shutdown(self) {
+        let _ = self.shutdown.send(());
+        if self.thread.join().is_err() {
+            tracing::error!("a scheduled task panicked");
+        }
+    }
+}
+
+#[cfg(test)]
+mod scheduler_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_runs_tasks_until_shutdown() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let task_runs = runs.clone();
+
+        let mut scheduler = Scheduler::new();
+        scheduler.add(Task::new("count", Duration::from_millis(1), move || {
+            task_runs.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }));
+
+        let running = scheduler.start();
+        while runs.load(Ordering::SeqCst) < 3 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        running.shutdown();
+
+        let runs_at_shutdown = runs.load(Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(runs_at_shutdown, runs.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_backoff_on_failures() {
+        let task = Task::new("fail", Duration::from_secs(1), || Err("nope".to_string()));
+
+        assert_eq!(Duration::from_secs(1), task.next_delay(0, 1));
+        assert_eq!(Duration::from_secs(2), task.next_delay(1, 1));
+        assert_eq!(Duration::from_secs(8), task.next_delay(3, 1));
+        assert_eq!(Duration::from_secs(10), task.next_delay(4, 1));
+        assert_eq!(Duration::from_secs(10), task.next_delay(u32::MAX, 1));
+    }
+
+    #[test]
+    fn test_jitter_stays_bounded() {
+        let mut task = Task::new("jitter", Duration::from_secs(1), || Ok(()));
+        task.jitter = Duration::from_millis(500);
+
+        let delays: Vec<Duration> = (0..100).map(|runs| task.next_delay(0, runs)).collect();
+        assert!(
+            delays
+                .iter()
+                .all(|delay| *delay >= Duration::from_secs(1)
+                    && *delay <= Duration::from_millis(1500))
+        );
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+    }
+
+    #[test]
+    fn test_shutdown_without_tasks() {
+        Scheduler::new().start().shutdown();
+    }
+}
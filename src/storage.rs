@@ -0,0 +1,973 @@
+use crate::account::{Account, AccountType, Amount};
+use crate::block::{Block, BlockHeader};
+use crate::difficulty::DifficultyAdjuster;
+use crate::encoding::{put_bytes, put_optional_bytes, put_u32, put_u64, Reader};
+use crate::error::BlockchainError;
+use crate::genesis::{Allocation, GenesisConfig};
+use crate::id::Id;
+use crate::names::NameRecord;
+use crate::params::{get_params, put_params, ParamsSchedule};
+use crate::transaction::{Signature, Transaction};
+use crate::{Error, Hash};
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// File the blocks are appended to, in the storage directory.
+pub const BLOCKS_FILE: &str = "blocks.dat";
+
+/// File holding the snapshot of the state, in the storage directory.
+pub const STATE_FILE: &str = "state.dat";
+
+/// Version of the storage format, leading the state snapshot.
+pub const STORAGE_VERSION: u8 = 1;
+
+/**
+Where a chain keeps its blocks and the snapshot of its state, so that
+embedders can choose their persistence layer.
+
+Blocks are stored in order, from genesis. The state is stored after the
+blocks it results from: when reloading, blocks stored after the last state,
+e.g. by an interrupted flush, are dropped with `truncate`.
+*/
+pub trait ChainStore {
+    /// Store the block following the stored ones.
+    fn put_block(&mut self, block: &Block) -> Result<(), BlockchainError>;
+
+    /// Get a stored block by its hash.
+    fn get_block_by_hash(&self, hash: &[u8]) -> Result<Option<Block>, BlockchainError>;
+
+    /// Get a stored block by its height.
+    fn get_block_by_height(&self, height: usize) -> Result<Option<Block>, BlockchainError>;
+
+    /// Number of stored blocks.
+    fn height(&self) -> usize;
+
+    /// Drop the stored blocks from that height on.
+    fn truncate(&mut self, height: usize) -> Result<(), BlockchainError>;
+
+    /// Replace the stored state.
+    fn put_state(&mut self, snapshot: &Snapshot) -> Result<(), BlockchainError>;
+
+    /// Get the stored state, if any.
+    fn get_state(&self) -> Result<Option<Snapshot>, BlockchainError>;
+
+    /// Make everything stored so far durable.
+    fn flush(&mut self) -> Result<(), BlockchainError>;
+}
+
+/**
+Encode a block to be stored: its header, its hash, then its transactions,
+each with its signature.
+*/
+pub fn encode_block(block: &Block) -> Vec<u8> {
+    let mut out = vec![];
+    put_bytes(&mut out, &block.header.to_canonical_bytes());
+    put_optional_bytes(&mut out, block.hash.as_ref());
+    put_u64(&mut out, block.transactions.len() as u64);
+    for transaction in &block.transactions {
+        put_bytes(&mut out, &transaction.to_canonical_bytes());
+        put_optional_bytes(
+            &mut out,
+            transaction.signature.map(|signature| signature.to_bytes()),
+        );
+    }
+    out
+}
+
+/// Decode a block encoded by `encode_block`.
+pub fn decode_block(bytes: &[u8]) -> Result<Block, Error> {
+    let mut reader = Reader::new(bytes);
+    let header = BlockHeader::from_canonical_bytes(reader.get_bytes()?)?;
+    let hash = reader.get_optional_bytes()?.map(<[u8]>::to_vec);
+
+    let count = reader.get_u64()?;
+    let mut transactions = vec![];
+    for _ in 0..count {
+        let mut transaction = Transaction::from_canonical_bytes(reader.get_bytes()?)?;
+        transaction.signature = match reader.get_optional_bytes()? {
+            Some(signature) => Some(Signature::from_bytes(
+                signature
+                    .try_into()
+                    .map_err(|_| "invalid signature length")?,
+            )),
+            None => None,
+        };
+        transactions.push(transaction);
+    }
+    reader.finish()?;
+
+    let mut block = Block::new();
+    block.header = header;
+    block.transactions = transactions;
+    block.hash = hash;
+    Ok(block)
+}
+
+/// The state of the chain after a number of blocks, to reload it without
+/// applying every block again.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    /// Number of blocks the state is the result of.
+    pub height: usize,
+
+    /// All the accounts.
+    pub accounts: HashMap<Id, Account>,
+
+    /// Registered names, including expired ones.
+    pub names: HashMap<String, NameRecord>,
+
+    /// Number of tokens created since genesis.
+    pub minted: Amount,
+
+    /// Maximum number of tokens that can ever exist, if limited.
+    pub max_supply: Option<Amount>,
+
+    /// Genesis the chain started from, if not a bare genesis block, so that
+    /// it can be reindexed.
+    pub genesis: Option<GenesisConfig>,
+
+    /// Economic rules, including the changes scheduled for later heights.
+    pub params: ParamsSchedule,
+
+    /// How the difficulty adjusts to the observed block times.
+    pub difficulty_adjuster: DifficultyAdjuster,
+}
+
+impl Snapshot {
+    /// Encode the snapshot. Accounts and names are sorted, so that the same
+    /// state is always encoded the same way.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![STORAGE_VERSION];
+        put_u64(&mut out, self.height as u64);
+        put_u64(&mut out, self.minted);
+        put_optional_bytes(&mut out, self.max_supply.map(Amount::to_be_bytes));
+        put_genesis(&mut out, self.genesis.as_ref());
+        let params: Vec<_> = self.params.iter().collect();
+        put_u64(&mut out, params.len() as u64);
+        for (height, params) in params {
+            put_u64(&mut out, height as u64);
+            put_params(&mut out, params);
+        }
+        put_duration(&mut out, self.difficulty_adjuster.target_interval);
+        put_u64(&mut out, self.difficulty_adjuster.window as u64);
+
+        let mut accounts: Vec<_> = self.accounts.iter().collect();
+        accounts.sort_by(|(a, _), (b, _)| a.as_ref().cmp(b.as_ref()));
+        put_u64(&mut out, accounts.len() as u64);
+        for (id, account) in accounts {
+            put_bytes(&mut out, id.as_ref());
+            put_u64(&mut out, account.tokens);
+            put_optional_bytes(&mut out, account.public_key.as_ref());
+            out.push(account_type_tag(account.account_type));
+            out.push(account.frozen.into());
+            put_u64(&mut out, account.nonce);
+            put_u64(&mut out, account.metadata.len() as u64);
+            for (key, value) in &account.metadata {
+                put_bytes(&mut out, key.as_bytes());
+                put_bytes(&mut out, value.as_bytes());
+            }
+        }
+
+        let mut names: Vec<_> = self.names.iter().collect();
+        names.sort_by_key(|(name, _)| *name);
+        put_u64(&mut out, names.len() as u64);
+        for (name, record) in names {
+            put_bytes(&mut out, name.as_bytes());
+            put_bytes(&mut out, record.owner.as_ref());
+            put_u64(&mut out, record.expires_at as u64);
+        }
+        out
+    }
+
+    /// Decode a snapshot encoded by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        let version = reader.get_u8()?;
+        if version != STORAGE_VERSION {
+            return Err(format!("unsupported storage version {}", version));
+        }
+        let usize = |value: u64| usize::try_from(value).map_err(|_| "value out of range");
+
+        let height = usize(reader.get_u64()?)?;
+        let minted = reader.get_u64()?;
+        let max_supply = match reader.get_optional_bytes()? {
+            Some(bytes) => Some(Amount::from_be_bytes(
+                bytes.try_into().map_err(|_| "invalid maximum supply")?,
+            )),
+            None => None,
+        };
+        let genesis = get_genesis(&mut reader)?;
+        let mut params = ParamsSchedule::default();
+        for _ in 0..reader.get_u64()? {
+            let height = usize(reader.get_u64()?)?;
+            params.schedule(height, get_params(&mut reader)?);
+        }
+        let difficulty_adjuster =
+            DifficultyAdjuster::new(get_duration(&mut reader)?, usize(reader.get_u64()?)?);
+
+        let mut accounts = HashMap::new();
+        for _ in 0..reader.get_u64()? {
+            let id = Id::from(reader.get_string()?);
+            let mut account = Account::new();
+            account.tokens = reader.get_u64()?;
+            account.public_key = reader.get_optional_bytes()?.map(<[u8]>::to_vec);
+            account.account_type = get_account_type(&mut reader)?;
+            account.frozen = reader.get_u8()? != 0;
+            account.nonce = reader.get_u64()?;
+            for _ in 0..reader.get_u64()? {
+                let key = reader.get_string()?;
+                account.metadata.insert(key, reader.get_string()?);
+            }
+            accounts.insert(id, account);
+        }
+
+        let mut names = HashMap::new();
+        for _ in 0..reader.get_u64()? {
+            let name = reader.get_string()?;
+            let record = NameRecord {
+                owner: reader.get_string()?.into(),
+                expires_at: usize(reader.get_u64()?)?,
+            };
+            names.insert(name, record);
+        }
+        reader.finish()?;
+
+        Ok(Snapshot {
+            height,
+            accounts,
+            names,
+            minted,
+            max_supply,
+            genesis,
+            params,
+            difficulty_adjuster,
+        })
+    }
+}
+
+/// Tag of an account type in the snapshot.
+fn account_type_tag(account_type: AccountType) -> u8 {
+    match account_type {
+        AccountType::User => 0,
+        AccountType::Contract => 1,
+        AccountType::System => 2,
+    }
+}
+
+/// Read an account type tagged by `account_type_tag`.
+fn get_account_type(reader: &mut Reader) -> Result<AccountType, Error> {
+    match reader.get_u8()? {
+        0 => Ok(AccountType::User),
+        1 => Ok(AccountType::Contract),
+        2 => Ok(AccountType::System),
+        tag => Err(format!("unknown account type {}", tag)),
+    }
+}
+
+/// Append a duration: seconds as a `u64`, then nanoseconds as a `u32`.
+fn put_duration(out: &mut Vec<u8>, duration: Duration) {
+    put_u64(out, duration.as_secs());
+    put_u32(out, duration.subsec_nanos());
+}
+
+/// Read a duration appended by `put_duration`.
+fn get_duration(reader: &mut Reader) -> Result<Duration, Error> {
+    let secs = reader.get_u64()?;
+    let nanos = reader.get_u32()?;
+    if nanos >= 1_000_000_000 {
+        return Err("invalid duration".to_string());
+    }
+    Ok(Duration::new(secs, nanos))
+}
+
+/// Append a `0` without a genesis, or a `1` followed by the genesis.
+fn put_genesis(out: &mut Vec<u8>, genesis: Option<&GenesisConfig>) {
+    let genesis = match genesis {
+        None => return out.push(0),
+        Some(genesis) => genesis,
+    };
+    out.push(1);
+    put_u64(out, genesis.timestamp);
+    put_optional_bytes(out, genesis.max_supply.map(Amount::to_be_bytes));
+    put_params(out, &genesis.params);
+    put_u64(out, genesis.allocations.len() as u64);
+    for allocation in &genesis.allocations {
+        put_bytes(out, allocation.account.as_ref());
+        put_bytes(out, &allocation.public_key);
+        put_u64(out, allocation.tokens);
+        out.push(account_type_tag(allocation.account_type));
+    }
+}
+
+/// Read a genesis appended by `put_genesis`.
+fn get_genesis(reader: &mut Reader) -> Result<Option<GenesisConfig>, Error> {
+    if reader.get_u8()? == 0 {
+        return Ok(None);
+    }
+    let timestamp = reader.get_u64()?;
+    let max_supply = match reader.get_optional_bytes()? {
+        Some(bytes) => Some(Amount::from_be_bytes(
+            bytes.try_into().map_err(|_| "invalid maximum supply")?,
+        )),
+        None => None,
+    };
+    let params = get_params(reader)?;
+    let mut allocations = vec![];
+    for _ in 0..reader.get_u64()? {
+        allocations.push(Allocation {
+            account: Id::from(reader.get_string()?),
+            public_key: reader.get_bytes()?.to_vec(),
+            tokens: reader.get_u64()?,
+            account_type: get_account_type(reader)?,
+        });
+    }
+    Ok(Some(GenesisConfig {
+        timestamp,
+        max_supply,
+        params,
+        allocations,
+    }))
+}
+
+/**
+Keeps a chain in memory, e.g. for tests or short-lived chains: what it
+stores is lost on exit.
+
+```
+# use crate::blockchain::block::Block;
+# use crate::blockchain::storage::{ChainStore, MemoryStore};
+let mut store = MemoryStore::new();
+let mut block = Block::new();
+block.seal();
+store.put_block(&block).unwrap();
+
+let stored = store.get_block_by_hash(block.hash.as_ref().unwrap()).unwrap();
+assert_eq!(Some(block.header), stored.map(|stored| stored.header));
+```
+*/
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    blocks: Vec<Block>,
+    heights: HashMap<Hash, usize>,
+    state: Option<Snapshot>,
+}
+
+impl MemoryStore {
+    /// Constructor
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ChainStore for MemoryStore {
+    fn put_block(&mut self, block: &Block) -> Result<(), BlockchainError> {
+        if let Some(hash) = &block.hash {
+            self.heights.insert(hash.clone(), self.blocks.len());
+        }
+        self.blocks.push(block.clone());
+        Ok(())
+    }
+
+    fn get_block_by_hash(&self, hash: &[u8]) -> Result<Option<Block>, BlockchainError> {
+        match self.heights.get(hash) {
+            Some(height) => self.get_block_by_height(*height),
+            None => Ok(None),
+        }
+    }
+
+    fn get_block_by_height(&self, height: usize) -> Result<Option<Block>, BlockchainError> {
+        Ok(self.blocks.get(height).cloned())
+    }
+
+    fn height(&self) -> usize {
+        self.blocks.len()
+    }
+
+    fn truncate(&mut self, height: usize) -> Result<(), BlockchainError> {
+        self.blocks.truncate(height);
+        self.heights
+            .retain(|_, block_height| *block_height < height);
+        Ok(())
+    }
+
+    fn put_state(&mut self, snapshot: &Snapshot) -> Result<(), BlockchainError> {
+        self.state = Some(snapshot.clone());
+        Ok(())
+    }
+
+    fn get_state(&self) -> Result<Option<Snapshot>, BlockchainError> {
+        Ok(self.state.clone())
+    }
+
+    fn flush(&mut self) -> Result<(), BlockchainError> {
+        Ok(())
+    }
+}
+
+/**
+Keeps a chain in a directory: blocks are appended to `BLOCKS_FILE`, each
+prefixed with its length, and `STATE_FILE` holds a snapshot of the state
+after them.
+
+The snapshot is replaced as a whole, and tells how much of the blocks file
+it covers: blocks appended after it, e.g. by a flush that was interrupted,
+are dropped when the directory is opened again.
+*/
+#[derive(Debug)]
+pub struct FileStore {
+    /// Directory holding the files.
+    path: PathBuf,
+
+    /// The blocks file, open for reading and appending.
+    blocks: File,
+
+    /// Position and length of each block in the blocks file.
+    offsets: Vec<(u64, usize)>,
+
+    /// Height of each block, by hash.
+    heights: HashMap<Hash, usize>,
+
+    /// Length of the blocks file, in bytes.
+    blocks_len: u64,
+}
+
+impl FileStore {
+    /// Open the store in a directory, creating it if needed.
+    pub fn open(path: &Path) -> Result<Self, BlockchainError> {
+        let io_error = |err: std::io::Error| storage_error(path, err);
+        std::fs::create_dir_all(path).map_err(io_error)?;
+
+        let (height, blocks_len) = match Self::read_state(path)? {
+            Some((blocks_len, snapshot)) => (snapshot.height, blocks_len),
+            None => (0, 0),
+        };
+
+        let mut blocks = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(path.join(BLOCKS_FILE))
+            .map_err(io_error)?;
+        if blocks.metadata().map_err(io_error)?.len() < blocks_len {
+            return Err(BlockchainError::Storage(
+                "the blocks file is shorter than the snapshot".to_string(),
+            ));
+        }
+        // drop the blocks appended after the snapshot
+        blocks.set_len(blocks_len).map_err(io_error)?;
+
+        let mut bytes = vec![];
+        blocks.read_to_end(&mut bytes).map_err(io_error)?;
+        let mut store = FileStore {
+            path: path.to_path_buf(),
+            blocks,
+            offsets: vec![],
+            heights: HashMap::new(),
+            blocks_len,
+        };
+
+        let mut reader = Reader::new(&bytes);
+        let mut offset = 0;
+        while store.offsets.len() < height {
+            let block = reader.get_bytes().map_err(BlockchainError::Storage)?;
+            offset += std::mem::size_of::<u64>() as u64;
+            let hash = decode_block(block).map_err(BlockchainError::Storage)?.hash;
+            if let Some(hash) = hash {
+                store.heights.insert(hash, store.offsets.len());
+            }
+            store.offsets.push((offset, block.len()));
+            offset += block.len() as u64;
+        }
+        reader.finish().map_err(BlockchainError::Storage)?;
+        Ok(store)
+    }
+
+    /// Read the length of the blocks file covered by the snapshot, and the
+    /// snapshot, if there is one.
+    fn read_state(path: &Path) -> Result<Option<(u64, Snapshot)>, BlockchainError> {
+        let bytes = match std::fs::read(path.join(STATE_FILE)) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(storage_error(path, err)),
+        };
+        let mut reader = Reader::new(&bytes);
+        let blocks_len = reader.get_u64().map_err(BlockchainError::Storage)?;
+        let snapshot = Snapshot::from_bytes(reader.get_bytes().map_err(BlockchainError::Storage)?)
+            .map_err(BlockchainError::Storage)?;
+        reader.finish().map_err(BlockchainError::Storage)?;
+        Ok(Some((blocks_len, snapshot)))
+    }
+
+    /// Directory holding the files.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl ChainStore for FileStore {
+    fn put_block(&mut self, block: &Block) -> Result<(), BlockchainError> {
+        let mut bytes = vec![];
+        put_bytes(&mut bytes, &encode_block(block));
+        self.blocks
+            .seek(SeekFrom::Start(self.blocks_len))
+            .and_then(|_| self.blocks.write_all(&bytes))
+            .map_err(|err| storage_error(&self.path, err))?;
+
+        if let Some(hash) = &block.hash {
+            self.heights.insert(hash.clone(), self.offsets.len());
+        }
+        let header_len = std::mem::size_of::<u64>();
+        self.offsets.push((
+            self.blocks_len + header_len as u64,
+            bytes.len() - header_len,
+        ));
+        self.blocks_len += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn get_block_by_hash(&self, hash: &[u8]) -> Result<Option<Block>, BlockchainError> {
+        match self.heights.get(hash) {
+            Some(height) => self.get_block_by_height(*height),
+            None => Ok(None),
+        }
+    }
+
+    fn get_block_by_height(&self, height: usize) -> Result<Option<Block>, BlockchainError> {
+        let (offset, len) = match self.offsets.get(height) {
+            Some(location) => *location,
+            None => return Ok(None),
+        };
+        let mut bytes = vec![0; len];
+        let mut file = &self.blocks;
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut bytes))
+            .map_err(|err| storage_error(&self.path, err))?;
+        decode_block(&bytes)
+            .map(Some)
+            .map_err(BlockchainError::Storage)
+    }
+
+    fn height(&self) -> usize {
+        self.offsets.len()
+    }
+
+    fn truncate(&mut self, height: usize) -> Result<(), BlockchainError> {
+        if let Some((offset, _)) = self.offsets.get(height) {
+            self.blocks_len = offset - std::mem::size_of::<u64>() as u64;
+            self.blocks
+                .set_len(self.blocks_len)
+                .map_err(|err| storage_error(&self.path, err))?;
+            self.offsets.truncate(height);
+            self.heights
+                .retain(|_, block_height| *block_height < height);
+        }
+        Ok(())
+    }
+
+    fn put_state(&mut self, snapshot: &Snapshot) -> Result<(), BlockchainError> {
+        let io_error = |err: std::io::Error| storage_error(&self.path, err);
+        // the snapshot must only cover blocks already on disk
+        self.blocks.sync_all().map_err(io_error)?;
+
+        let mut bytes = vec![];
+        put_u64(&mut bytes, self.blocks_len);
+        put_bytes(&mut bytes, &snapshot.to_bytes());
+        let temporary = self.path.join(format!("{}.tmp", STATE_FILE));
+        let mut state = File::create(&temporary).map_err(io_error)?;
+        state.write_all(&bytes).map_err(io_error)?;
+        state.sync_all().map_err(io_error)?;
+        std::fs::rename(&temporary, self.path.join(STATE_FILE)).map_err(io_error)
+    }
+
+    fn get_state(&self) -> Result<Option<Snapshot>, BlockchainError> {
+        Ok(Self::read_state(&self.path)?.map(|(_, snapshot)| snapshot))
+    }
+
+    fn flush(&mut self) -> Result<(), BlockchainError> {
+        self.blocks
+            .sync_all()
+            .map_err(|err| storage_error(&self.path, err))
+    }
+}
+
+/// Report an I/O error on the files of a store.
+fn storage_error(path: &Path, err: std::io::Error) -> BlockchainError {
+    BlockchainError::Storage(format!("{}: {}", path.display(), err))
+}
+
+/// Key of the state in a `SledStore`.
+#[cfg(feature = "sled")]
+const STATE_KEY: &[u8] = b"state";
+
+/**
+Keeps a chain in a sled database, with the `sled` feature: blocks by height
+and their heights by hash in their own trees, and the state in the default
+tree.
+*/
+#[cfg(feature = "sled")]
+#[derive(Debug)]
+pub struct SledStore {
+    db: sled::Db,
+    blocks: sled::Tree,
+    heights: sled::Tree,
+}
+
+#[cfg(feature = "sled")]
+impl SledStore {
+    /// Open the database in a directory, creating it if needed.
+    pub fn open(path: &Path) -> Result<Self, BlockchainError> {
+        let db = sled::open(path).map_err(sled_error)?;
+        Ok(SledStore {
+            blocks: db.open_tree("blocks").map_err(sled_error)?,
+            heights: db.open_tree("heights").map_err(sled_error)?,
+            db,
+        })
+    }
+}
+
+#[cfg(feature = "sled")]
+impl ChainStore for SledStore {
+    fn put_block(&mut self, block: &Block) -> Result<(), BlockchainError> {
+        let height = (self.height() as u64).to_be_bytes();
+        self.blocks
+            .insert(height, encode_block(block))
+            .map_err(sled_error)?;
+        if let Some(hash) = &block.hash {
+            self.heights
+                .insert(hash.as_slice(), &height)
+                .map_err(sled_error)?;
+        }
+        Ok(())
+    }
+
+    fn get_block_by_hash(&self, hash: &[u8]) -> Result<Option<Block>, BlockchainError> {
+        let height = match self.heights.get(hash).map_err(sled_error)? {
+            Some(height) => height,
+            None => return Ok(None),
+        };
+        let height = height
+            .as_ref()
+            .try_into()
+            .map(u64::from_be_bytes)
+            .map_err(|_| BlockchainError::Storage("invalid height".to_string()))?;
+        match usize::try_from(height) {
+            Ok(height) => self.get_block_by_height(height),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn get_block_by_height(&self, height: usize) -> Result<Option<Block>, BlockchainError> {
+        match self
+            .blocks
+            .get((height as u64).to_be_bytes())
+            .map_err(sled_error)?
+        {
+            Some(bytes) => decode_block(&bytes)
+                .map(Some)
+                .map_err(BlockchainError::Storage),
+            None => Ok(None),
+        }
+    }
+
+    fn height(&self) -> usize {
+        self.blocks.len()
+    }
+
+    fn truncate(&mut self, height: usize) -> Result<(), BlockchainError> {
+        while self.height() > height {
+            let (_, bytes) = match self.blocks.pop_max().map_err(sled_error)? {
+                Some(entry) => entry,
+                None => break,
+            };
+            if let Some(hash) = decode_block(&bytes).map_err(BlockchainError::Storage)?.hash {
+                self.heights.remove(hash).map_err(sled_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn put_state(&mut self, snapshot: &Snapshot) -> Result<(), BlockchainError> {
+        self.db
+            .insert(STATE_KEY, snapshot.to_bytes())
+            .map_err(sled_error)?;
+        Ok(())
+    }
+
+    fn get_state(&self) -> Result<Option<Snapshot>, BlockchainError> {
+        match self.db.get(STATE_KEY).map_err(sled_error)? {
+            Some(bytes) => Snapshot::from_bytes(&bytes)
+                .map(Some)
+                .map_err(BlockchainError::Storage),
+            None => Ok(None),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), BlockchainError> {
+        self.db.flush().map_err(sled_error)?;
+        Ok(())
+    }
+}
+
+/// Report an error of the sled database.
+#[cfg(feature = "sled")]
+fn sled_error(err: sled::Error) -> BlockchainError {
+    BlockchainError::Storage(err.to_string())
+}
+
+#[cfg(test)]
+mod storage_tests {
+    use super::*;
+    use crate::account::generate_keypair;
+    use crate::params::ChainParams;
+    use crate::rng::SeededRng;
+    use crate::testing::TempDir;
+    use crate::transaction::TransactionRecord;
+
+    fn block(height: usize) -> Block {
+        let mut block = Block::new();
+        block.header.height = height;
+        block.header.previous_hash = Some(vec![1; 32]);
+        let mut transaction = Transaction::new(
+            1,
+            TransactionRecord::SendTokens {
+                to: "bob".into(),
+                amount: 10,
+            },
+            Some("alice".into()),
+        );
+        transaction.sign(&generate_keypair(&SeededRng::new(0)));
+        block.transactions.push(transaction);
+        block.transactions.push(Transaction::new(
+            0,
+            TransactionRecord::create_user_account(b"hi"),
+            None,
+        ));
+        block.mine(2);
+        block
+    }
+
+    #[test]
+    fn test_block_encoding_roundtrip() {
+        let block = block(1);
+        let bytes = encode_block(&block);
+        let decoded = decode_block(&bytes).unwrap();
+
+        assert_eq!(block.header, decoded.header);
+        assert_eq!(block.hash, decoded.hash);
+        assert_eq!(bytes, encode_block(&decoded));
+        assert!(decoded.transactions[0].signature.is_some());
+        assert!(decoded.transactions[1].signature.is_none());
+
+        for length in 0..bytes.len() {
+            assert!(decode_block(&bytes[..length]).is_err());
+        }
+    }
+
+    /// A snapshot of a chain started from a genesis, whose rules change.
+    fn snapshot_with_rules() -> Snapshot {
+        let mut genesis = GenesisConfig {
+            timestamp: 1_600_000_000,
+            max_supply: Some(1000),
+            params: ChainParams {
+                min_transfer: 1,
+                faucet: true,
+                ..ChainParams::default()
+            },
+            ..GenesisConfig::default()
+        }
+        .with_allocation(&[1; 32], 100);
+        genesis.allocations[0].account_type = AccountType::System;
+        let mut params = ParamsSchedule::new(genesis.params.clone());
+        params.schedule(
+            10,
+            ChainParams {
+                min_balance: 5,
+                name_registration_period: 7,
+                difficulty: 3,
+                ..ChainParams::default()
+            },
+        );
+
+        Snapshot {
+            height: 2,
+            genesis: Some(genesis),
+            params,
+            difficulty_adjuster: DifficultyAdjuster::new(Duration::new(30, 500), 12),
+            ..Snapshot::default()
+        }
+    }
+
+    /// Check that the rules of the chain were kept.
+    fn assert_same_rules(expected: &Snapshot, actual: &Snapshot) {
+        assert_eq!(expected.genesis, actual.genesis);
+        assert_eq!(expected.params, actual.params);
+        assert_eq!(expected.difficulty_adjuster, actual.difficulty_adjuster);
+    }
+
+    #[test]
+    fn test_snapshot_encoding_roundtrip() {
+        let mut account = Account::new();
+        account.tokens = 42;
+        account.public_key = Some(vec![7; 32]);
+        account.account_type = AccountType::Contract;
+        account.frozen = true;
+        account.nonce = 7;
+        account
+            .set_metadata("url", Some("https://example.com"))
+            .unwrap();
+
+        let mut snapshot = Snapshot {
+            height: 2,
+            minted: 42,
+            max_supply: Some(1000),
+            ..Snapshot::default()
+        };
+        snapshot.accounts.insert("alice".into(), account);
+        snapshot.accounts.insert("bob".into(), Account::new());
+        snapshot.names.insert(
+            "alice".to_string(),
+            NameRecord {
+                owner: "alice".into(),
+                expires_at: 10,
+            },
+        );
+
+        let bytes = snapshot.to_bytes();
+        let decoded = Snapshot::from_bytes(&bytes).unwrap();
+        assert_eq!(bytes, decoded.to_bytes());
+        let alice = &decoded.accounts[&"alice".into()];
+        assert_eq!(42, alice.tokens);
+        assert_eq!(AccountType::Contract, alice.account_type);
+        assert!(alice.frozen);
+        assert_eq!(7, alice.nonce);
+        assert_eq!(Some(1000), decoded.max_supply);
+
+        let snapshot = snapshot_with_rules();
+        let bytes = snapshot.to_bytes();
+        let decoded = Snapshot::from_bytes(&bytes).unwrap();
+        assert_same_rules(&snapshot, &decoded);
+        assert_eq!(bytes, decoded.to_bytes());
+        for length in 0..bytes.len() {
+            assert!(Snapshot::from_bytes(&bytes[..length]).is_err());
+        }
+
+        assert_eq!(
+            Err("unsupported storage version 2".to_string()),
+            Snapshot::from_bytes(&[2]).map(|_| ())
+        );
+    }
+
+    /// Check the behaviour every store must have.
+    fn check_store<S: ChainStore>(store: &mut S) {
+        assert_eq!(0, store.height());
+        assert!(store.get_state().unwrap().is_none());
+
+        let blocks: Vec<Block> = (0..3).map(block).collect();
+        for block in &blocks {
+            store.put_block(block).unwrap();
+        }
+        assert_eq!(3, store.height());
+        for (height, block) in blocks.iter().enumerate() {
+            let hash = block.hash.as_ref().unwrap();
+            let stored = store.get_block_by_hash(hash).unwrap().unwrap();
+            assert_eq!(block.header, stored.header);
+            assert_eq!(
+                Some(hash),
+                store
+                    .get_block_by_height(height)
+                    .unwrap()
+                    .unwrap()
+                    .hash
+                    .as_ref()
+            );
+        }
+        assert!(store.get_block_by_height(3).unwrap().is_none());
+        assert!(store.get_block_by_hash(&[0; 32]).unwrap().is_none());
+
+        store.truncate(1).unwrap();
+        assert_eq!(1, store.height());
+        assert!(store
+            .get_block_by_hash(blocks[2].hash.as_ref().unwrap())
+            .unwrap()
+            .is_none());
+        store.put_block(&blocks[1]).unwrap();
+        assert_eq!(
+            blocks[1].hash,
+            store.get_block_by_height(1).unwrap().unwrap().hash
+        );
+
+        let snapshot = Snapshot {
+            minted: 5,
+            ..snapshot_with_rules()
+        };
+        store.put_state(&snapshot).unwrap();
+        store.flush().unwrap();
+        let stored = store.get_state().unwrap().unwrap();
+        assert_same_rules(&snapshot, &stored);
+        assert_eq!(snapshot.to_bytes(), stored.to_bytes());
+    }
+
+    #[test]
+    fn test_memory_store() {
+        check_store(&mut MemoryStore::new());
+    }
+
+    #[test]
+    fn test_file_store() {
+        let dir = TempDir::new("file-store");
+        check_store(&mut FileStore::open(dir.path()).unwrap());
+
+        let store = FileStore::open(dir.path()).unwrap();
+        assert_eq!(2, store.height());
+        let stored = store.get_state().unwrap().unwrap();
+        assert_eq!(2, stored.height);
+        assert_same_rules(&snapshot_with_rules(), &stored);
+    }
+
+    #[test]
+    #[cfg(feature = "sled")]
+    fn test_sled_store() {
+        let dir = TempDir::new("sled-store");
+        check_store(&mut SledStore::open(dir.path()).unwrap());
+
+        let store = SledStore::open(dir.path()).unwrap();
+        assert_same_rules(&snapshot_with_rules(), &store.get_state().unwrap().unwrap());
+    }
+
+    #[test]
+    fn test_file_store_drops_blocks_after_the_snapshot() {
+        let dir = TempDir::new("file-store-interrupted");
+        let mut store = FileStore::open(dir.path()).unwrap();
+        store.put_block(&block(0)).unwrap();
+        store
+            .put_state(&Snapshot {
+                height: 1,
+                ..Snapshot::default()
+            })
+            .unwrap();
+        // a flush interrupted between the blocks and the snapshot
+        store.put_block(&block(1)).unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(BLOCKS_FILE))
+            .unwrap();
+        file.write_all(b"partial block").unwrap();
+        drop(store);
+
+        let store = FileStore::open(dir.path()).unwrap();
+        assert_eq!(1, store.height());
+
+        std::fs::write(dir.path().join(BLOCKS_FILE), b"").unwrap();
+        assert_eq!(
+            Err(BlockchainError::Storage(
+                "the blocks file is shorter than the snapshot".to_string()
+            )),
+            FileStore::open(dir.path()).map(|_| ())
+        );
+    }
+}
@@ -0,0 +1,441 @@
+use crate::account::{Account, Amount, Keypair};
+use crate::block::Block;
+use crate::blockchain::Blockchain;
+use crate::error::BlockchainError;
+use crate::genesis::GenesisConfig;
+use crate::id::Id;
+use crate::names::NameRecord;
+use crate::params::ChainParams;
+use crate::transaction::{Transaction, TransactionEvent, TransactionRecord};
+use crate::world::WorldState;
+use crate::Nonce;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Keypair of a named test account, derived from the name so that tests can
+/// sign on its behalf.
+pub fn keypair(name: &str) -> Keypair {
+    Keypair::from_bytes(blake3::hash(name.as_bytes()).as_bytes())
+}
+
+/// ID of a named test account: the ID owned by its `keypair`.
+pub fn account_id(name: &str) -> Id {
+    Id::from_public_key(keypair(name).verifying_key().as_bytes())
+}
+
+/**
+Builds a chain for tests, without hand-assembling blocks and hashes.
+
+Accounts are named: they are owned by the `keypair` of their name, created
+and funded by the genesis block. Then every block is appended on top of the
+previous one, with the transactions of named accounts signed.
+
+```
+# use crate::blockchain::testing::{account_id, ChainBuilder};
+# use crate::blockchain::world::WorldState;
+let chain = ChainBuilder::new()
+    .with_account("alice", 1000)
+    .with_account("bob", 0)
+    .block(|b| b.transfer("alice", "bob", 10))
+    .build()
+    .unwrap();
+
+assert_eq!(10, chain.get_account_by_id(&account_id("bob")).unwrap().tokens);
+```
+*/
+#[derive(Debug, Default)]
+pub struct ChainBuilder {
+    genesis: GenesisConfig,
+    blocks: Vec<BlockBuilder>,
+}
+
+impl ChainBuilder {
+    /// Constructor
+    pub fn new() -> Self {
+        ChainBuilder {
+            genesis: GenesisConfig::default(),
+            blocks: vec![],
+        }
+    }
+
+    /// Create a named account holding some tokens from the start.
+    pub fn with_account(mut self, name: &str, tokens: Amount) -> Self {
+        self.genesis = self
+            .genesis
+            .with_allocation(keypair(name).verifying_key().as_bytes(), tokens);
+        self
+    }
+
+    /// Append a block, whose transactions are added by the closure.
+    pub fn block<F>(mut self, build: F) -> Self
+    where
+        F: FnOnce(&mut BlockBuilder) -> &mut BlockBuilder,
+    {
+        let mut block = BlockBuilder::new();
+        build(&mut block);
+        self.blocks.push(block);
+        self
+    }
+
+    /// Build the chain: a genesis block, then all the appended blocks.
+    pub fn build(self) -> Result<Blockchain, BlockchainError> {
+        let mut chain = Blockchain::from_genesis(&self.genesis)?;
+
+        let mut nonces: HashMap<Id, Nonce> = HashMap::new();
+        for builder in self.blocks {
+            let mut block = Block::new();
+            block.header.height = chain.height();
+            block.header.previous_hash = chain.get_last_block_hash().cloned();
+
+            for (record, from) in builder.transactions {
+                let transaction = match from {
+                    Some(from) => {
+                        let nonce = nonces.entry(account_id(&from)).or_insert(0);
+                        *nonce += 1;
+                        let mut transaction =
+                            Transaction::new(*nonce, record, Some(account_id(&from)));
+                        transaction.sign(&keypair(&from));
+                        transaction
+                    }
+                    None => Transaction::new(0, record, None),
+                };
+                block.transactions.push(transaction);
+            }
+
+            chain.mine_block(&mut block);
+            chain.add_block(block)?;
+        }
+
+        Ok(chain)
+    }
+}
+
+/// Collects the transactions of a block built by a `ChainBuilder`, along
+/// with the names of their senders.
+#[derive(Debug, Default)]
+pub struct BlockBuilder {
+    transactions: Vec<(TransactionRecord, Option<String>)>,
+}
+
+impl BlockBuilder {
+    /// Constructor
+    pub fn new() -> Self {
+        BlockBuilder {
+            transactions: vec![],
+        }
+    }
+
+    /// Add a transaction creating the account owned by a public key.
+    /// Its ID is `Id::from_public_key(public_key)`.
+    pub fn create_account(&mut self, public_key: &[u8]) -> &mut Self {
+        self.transactions
+            .push((TransactionRecord::create_user_account(public_key), None));
+        self
+    }
+
+    /// Add a transaction sending tokens from a named account to another
+    /// named account.
+    pub fn transfer(&mut self, from: &str, to: &str, amount: Amount) -> &mut Self {
+        self.transfer_to(from, &account_id(to), amount)
+    }
+
+    /// Add a transaction sending tokens from a named account to any account.
+    pub fn transfer_to(&mut self, from: &str, to: &Id, amount: Amount) -> &mut Self {
+        self.transactions.push((
+            TransactionRecord::SendTokens {
+                to: to.clone(),
+                amount,
+            },
+            Some(from.to_string()),
+        ));
+        self
+    }
+}
+
+/**
+A world state backed by a map, to test applying transactions without
+building a `Blockchain`.
+
+```
+# use crate::blockchain::testing::MockWorldState;
+# use crate::blockchain::transaction::{Transaction, TransactionRecord};
+let mut world = MockWorldState::new()
+    .with_account("alice", 10)
+    .fail_on("bob", "storage is down");
+
+let transaction = Transaction::new(
+    0,
+    TransactionRecord::SendTokens { to: "bob".into(), amount: 5 },
+    Some("alice".into()),
+);
+assert!(transaction.apply(&mut world).is_err());
+```
+*/
+#[derive(Debug, Default)]
+pub struct MockWorldState {
+    accounts: HashMap<Id, Account>,
+    failures: HashMap<Id, String>,
+    names: HashMap<String, NameRecord>,
+    events: Vec<TransactionEvent>,
+    params: ChainParams,
+    scheduled_params: Vec<(usize, ChainParams)>,
+    height: usize,
+    fees: Amount,
+}
+
+impl MockWorldState {
+    /// Constructor
+    pub fn new() -> Self {
+        MockWorldState {
+            accounts: HashMap::new(),
+            failures: HashMap::new(),
+            names: HashMap::new(),
+            events: vec![],
+            params: ChainParams::default(),
+            scheduled_params: vec![],
+            height: 0,
+            fees: 0,
+        }
+    }
+
+    /// Create an account holding some tokens.
+    pub fn with_account(mut self, id: &str, tokens: Amount) -> Self {
+        self.accounts.insert(
+            id.into(),
+            Account {
+                tokens,
+                ..Account::new()
+            },
+        );
+        self
+    }
+
+    /// Set the economic rules transactions must follow.
+    pub fn with_params(mut self, params: ChainParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Set the height of the block being applied.
+    pub fn with_height(mut self, height: usize) -> Self {
+        self.height = height;
+        self
+    }
+
+    /// Make every access to the account fail with a storage error.
+    pub fn fail_on(mut self, id: &str, error: &str) -> Self {
+        self.failures.insert(id.into(), error.to_string());
+        self
+    }
+
+    /// Events emitted so far, in order.
+    pub fn events(&self) -> &[TransactionEvent] {
+        &self.events
+    }
+
+    /// Economic rules scheduled so far, along with the height they activate
+    /// at, in order.
+    pub fn scheduled_params(&self) -> &[(usize, ChainParams)] {
+        &self.scheduled_params
+    }
+
+    /// Fees paid so far.
+    pub fn fees(&self) -> Amount {
+        self.fees
+    }
+
+    fn check(&self, id: &Id) -> Result<(), BlockchainError> {
+        match self.failures.get(id) {
+            Some(error) => Err(BlockchainError::Storage(error.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl WorldState for MockWorldState {
+    fn get_account_by_id(&self, id: &Id) -> Result<&Account, BlockchainError> {
+        self.check(id)?;
+        self.accounts
+            .get(id)
+            .ok_or_else(|| BlockchainError::AccountNotFound(id.clone()))
+    }
+
+    fn get_account_by_id_mut(&mut self, id: &Id) -> Result<&mut Account, BlockchainError> {
+        self.check(id)?;
+        self.accounts
+            .get_mut(id)
+            .ok_or_else(|| BlockchainError::AccountNotFound(id.clone()))
+    }
+
+    fn add_account(&mut self, id: Id) -> Result<(), BlockchainError> {
+        self.check(&id)?;
+        if self.accounts.contains_key(&id) {
+            return Err(BlockchainError::AccountAlreadyExists(id));
+        }
+        self.accounts.insert(id, Account::new());
+        Ok(())
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn get_name(&self, name: &str) -> Option<&NameRecord> {
+        self.names.get(name)
+    }
+
+    fn set_name(&mut self, name: String, record: NameRecord) {
+        self.names.insert(name, record);
+    }
+
+    fn emit(&mut self, event: TransactionEvent) {
+        self.events.push(event);
+    }
+
+    fn params(&self) -> ChainParams {
+        self.params.clone()
+    }
+
+    fn schedule_params(&mut self, height: usize, params: ChainParams) {
+        self.scheduled_params.push((height, params));
+    }
+
+    fn collect_fee(&mut self, amount: Amount) -> Result<(), BlockchainError> {
+        self.fees = self
+            .fees
+            .checked_add(amount)
+            .ok_or(BlockchainError::Overflow)?;
+        Ok(())
+    }
+}
+
+/**
+A directory for a test, emptied when created and removed when dropped.
+
+```
+# use crate::blockchain::testing::TempDir;
+let dir = TempDir::new("doc-temp-dir");
+std::fs::create_dir_all(dir.path()).unwrap();
+let path = dir.path().to_path_buf();
+
+drop(dir);
+assert!(!path.exists());
+```
+*/
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Constructor, naming the directory after the test and the process.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("blockchain-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        TempDir { path }
+    }
+
+    /// Where the directory is.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod testing_tests {
+    use super::*;
+
+    #[test]
+    fn test_build_chain() {
+        let carol = Id::from_public_key(b"carol");
+        let chain = ChainBuilder::new()
+            .with_account("alice", 1000)
+            .with_account("bob", 0)
+            .block(|b| b.transfer("alice", "bob", 10).create_account(b"carol"))
+            .block(|b| {
+                b.transfer_to("bob", &carol, 4)
+                    .transfer_to("alice", &carol, 1)
+            })
+            .build()
+            .unwrap();
+
+        assert_eq!(3, chain.iter_blocks(..).count());
+        let tokens = |id: &Id| chain.get_account_by_id(id).unwrap().tokens;
+        assert_eq!(989, tokens(&account_id("alice")));
+        assert_eq!(6, tokens(&account_id("bob")));
+        assert_eq!(5, tokens(&carol));
+
+        let nonces: Vec<Nonce> = chain
+            .iter_blocks(2..)
+            .flat_map(|block| block.unwrap().transactions)
+            .map(|transaction| transaction.nonce)
+            .collect();
+        assert_eq!(vec![1, 2], nonces);
+    }
+
+    #[test]
+    fn test_build_invalid_chain() {
+        let result = ChainBuilder::new()
+            .with_account("alice", 10)
+            .with_account("bob", 0)
+            .block(|b| b.transfer("alice", "bob", 11))
+            .build();
+
+        assert_eq!(
+            Err(BlockchainError::InvalidTransaction {
+                index: 0,
+                error: Box::new(BlockchainError::InsufficientFunds {
+                    available: 10,
+                    required: 11
+                })
+            }),
+            result.map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_mock_world_state() {
+        let mut world = MockWorldState::new()
+            .with_account("alice", 10)
+            .with_account("bob", 0);
+
+        let transfer = Transaction::new(
+            0,
+            TransactionRecord::SendTokens {
+                to: "bob".into(),
+                amount: 4,
+            },
+            Some("alice".into()),
+        );
+        assert_eq!(Ok(()), transfer.apply(&mut world));
+        assert_eq!(6, world.get_account_by_id(&"alice".into()).unwrap().tokens);
+        assert_eq!(4, world.get_account_by_id(&"bob".into()).unwrap().tokens);
+        assert_eq!(
+            &[TransactionEvent::Transfer {
+                from: "alice".into(),
+                to: "bob".into(),
+                amount: 4
+            }],
+            world.events()
+        );
+    }
+
+    #[test]
+    fn test_mock_world_state_failures() {
+        let alice = Id::from_public_key(b"alice").to_string();
+        let mut world = MockWorldState::new().fail_on(&alice, "storage is down");
+
+        let create = Transaction::new(0, TransactionRecord::create_user_account(b"alice"), None);
+        assert_eq!(
+            Err(BlockchainError::Storage("storage is down".to_string())),
+            create.apply(&mut world)
+        );
+        assert!(world.events().is_empty());
+    }
+}
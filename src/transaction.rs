@@ -1,18 +1,45 @@
-use crate::account::Amount;
+use crate::account::{Amount, Keypair, PublicKey};
+use crate::clock::{Clock, SystemClock};
+use crate::encoding::{
+    put_bytes, put_optional_bytes, put_time, put_u64, Reader, CANONICAL_VERSION,
+};
+use crate::error::BlockchainError;
 use crate::id::Id;
+use crate::names::{validate_name, NameRecord};
+use crate::params::{get_params, put_params, ChainParams, ENCODED_PARAMS_SIZE};
 use crate::world::WorldState;
 use crate::{Error, Hash, Nonce};
+use ed25519_dalek::{Signer, VerifyingKey};
+use std::convert::TryFrom;
 use std::time::SystemTime;
 
-/// The cryptographic signature of a transaction.
-pub type Signature = String;
+/// The ed25519 signature of a transaction's hash, by its sender.
+pub type Signature = ed25519_dalek::Signature;
+
+/// Longest memo a transaction can carry, in bytes.
+pub const MAX_MEMO_LENGTH: usize = 64;
+
+/// Priority class of a transaction, when building blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lane {
+    /// Sent by the system authority, included ahead of everything else.
+    System,
+
+    /// Everything else.
+    User,
+}
 
 /// A transaction record is describing the action a transaction
 /// executes against the Blockchain.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum TransactionRecord {
     /// Creates a new account from a public key.
-    CreateUserAccount(Id),
+    CreateUserAccount {
+        /// ID of the new account, must be the hash of the public key.
+        id: Id,
+        /// Key owning the new account.
+        public_key: PublicKey,
+    },
 
     /// Sends tokens to another account.
     SendTokens {
@@ -22,15 +49,183 @@ pub enum TransactionRecord {
         amount: Amount,
     },
 
-    /// Create new tokens.
-    MintTokens {
+    /// Registers a name pointing to the sender, or renews it.
+    RegisterName {
+        /// Human-readable name.
+        name: String,
+    },
+
+    /// Prevents an account from sending tokens. Only the system authority
+    /// can send it.
+    FreezeAccount(Id),
+
+    /// Lets a frozen account send tokens again. Only the system authority
+    /// can send it.
+    UnfreezeAccount(Id),
+
+    /// Sets a metadata entry of the sender's account, or removes it.
+    SetMetadata {
+        /// Key of the entry, e.g. `display_name` or `url`.
+        key: String,
+        /// New value, or `None` to remove the entry.
+        value: Option<String>,
+    },
+
+    /// Gives a name owned by the sender to another account.
+    TransferName {
+        /// Human-readable name.
+        name: String,
+        /// ID of the account receiving the name.
+        to: Id,
+    },
+
+    /// Changes the economic rules from a future height on, once a governance
+    /// proposal has been approved. Only the system authority can send it, so
+    /// that every node derives the same rules from the blocks.
+    ScheduleParams {
+        /// Height of the first block following the new rules.
+        height: usize,
+        /// New rules.
+        params: ChainParams,
+    },
+}
+
+impl TransactionRecord {
+    /// Create the account owned by a public key.
+    pub fn create_user_account(public_key: &[u8]) -> Self {
+        TransactionRecord::CreateUserAccount {
+            id: Id::from_public_key(public_key),
+            public_key: public_key.to_vec(),
+        }
+    }
+}
+
+/// An event emitted when applying a transaction, to let off-chain indexers
+/// follow what happened without re-executing transactions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionEvent {
+    /// An account has been created.
+    AccountCreated {
+        /// ID of the new account.
+        id: Id,
+    },
+
+    /// New tokens have been created by a genesis allocation.
+    Minted {
         /// ID of the account receiving the tokens.
         to: Id,
-        /// Number of tokens to send.
+        /// Number of tokens created.
+        amount: Amount,
+    },
+
+    /// Tokens have been sent from an account to another.
+    Transfer {
+        /// ID of the account sending the tokens.
+        from: Id,
+        /// ID of the account receiving the tokens.
+        to: Id,
+        /// Number of tokens sent.
+        amount: Amount,
+    },
+
+    /// An account has been frozen or unfrozen.
+    FreezeChanged {
+        /// ID of the account.
+        id: Id,
+        /// Is the account now frozen?
+        frozen: bool,
+    },
+
+    /// A metadata entry of an account has been set or removed.
+    MetadataSet {
+        /// ID of the account.
+        account: Id,
+        /// Key of the entry.
+        key: String,
+        /// New value, if not removed.
+        value: Option<String>,
+    },
+
+    /// A name has been registered or renewed.
+    NameRegistered {
+        /// Human-readable name.
+        name: String,
+        /// ID of the account the name points to.
+        owner: Id,
+        /// Height from which the name is free again, unless renewed.
+        expires_at: usize,
+    },
+
+    /// A name has been given to another account.
+    NameTransferred {
+        /// Human-readable name.
+        name: String,
+        /// ID of the previous owner.
+        from: Id,
+        /// ID of the new owner.
+        to: Id,
+    },
+
+    /// New economic rules have been scheduled.
+    ParamsScheduled {
+        /// Height of the first block following the new rules.
+        height: usize,
+        /// New rules.
+        params: ChainParams,
+    },
+
+    /// The sender of a transaction has paid its fee.
+    FeePaid {
+        /// ID of the account paying the fee.
+        from: Id,
+        /// Number of tokens paid.
+        amount: Amount,
+    },
+
+    /// The beneficiary of a block has received the fee of one of its
+    /// transactions.
+    FeesCollected {
+        /// ID of the beneficiary.
+        to: Id,
+        /// Number of tokens received.
         amount: Amount,
     },
 }
 
+impl TransactionEvent {
+    /// Name of the kind of event, to filter events by.
+    pub fn topic(&self) -> &'static str {
+        match self {
+            TransactionEvent::AccountCreated { .. } => "account_created",
+            TransactionEvent::Minted { .. } => "minted",
+            TransactionEvent::Transfer { .. } => "transfer",
+            TransactionEvent::FreezeChanged { .. } => "freeze_changed",
+            TransactionEvent::MetadataSet { .. } => "metadata_set",
+            TransactionEvent::NameRegistered { .. } => "name_registered",
+            TransactionEvent::NameTransferred { .. } => "name_transferred",
+            TransactionEvent::ParamsScheduled { .. } => "params_scheduled",
+            TransactionEvent::FeePaid { .. } => "fee_paid",
+            TransactionEvent::FeesCollected { .. } => "fees_collected",
+        }
+    }
+
+    /// Does this event involve the account?
+    pub fn involves(&self, id: &Id) -> bool {
+        match self {
+            TransactionEvent::AccountCreated { id: created } => created == id,
+            TransactionEvent::Minted { to, .. } => to == id,
+            TransactionEvent::Transfer { from, to, .. } => from == id || to == id,
+            TransactionEvent::FreezeChanged { id: frozen, .. } => frozen == id,
+            TransactionEvent::MetadataSet { account, .. } => account == id,
+            TransactionEvent::NameRegistered { owner, .. } => owner == id,
+            TransactionEvent::NameTransferred { from, to, .. } => from == id || to == id,
+            TransactionEvent::ParamsScheduled { .. } => false,
+            TransactionEvent::FeePaid { from, .. } => from == id,
+            TransactionEvent::FeesCollected { to, .. } => to == id,
+        }
+    }
+}
+
 /** A change of state in the blockchain.
 
 ```
@@ -38,13 +233,13 @@ pub enum TransactionRecord {
 # use crate::blockchain::blockchain::Blockchain;
 # let mut blockchain = Blockchain::new();
 
-let id = "some unique ID";
-let transaction = Transaction::new(0, TransactionRecord::CreateUserAccount(id.into()), None);
+let public_key = b"some public key";
+let transaction = Transaction::new(0, TransactionRecord::create_user_account(public_key), None);
 
 transaction.apply(&mut blockchain);
 ```
 */
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Transaction {
     /// "number only used once".
     pub nonce: Nonce,
@@ -60,128 +255,600 @@ pub struct Transaction {
 
     /// Local time of creation.
     pub created_at: SystemTime,
+
+    /// Free-form tag, e.g. the deposit reference an exchange gave to a
+    /// customer. The chain indexes transactions by memo.
+    pub memo: Option<String>,
+
+    /// Tokens paid by the sender to the beneficiary of the block including
+    /// the transaction.
+    pub fee: Amount,
 }
 
 impl Transaction {
     /// Constructor
     pub fn new(nonce: Nonce, record: TransactionRecord, from: Option<Id>) -> Self {
+        Self::with_clock(nonce, record, from, &SystemClock)
+    }
+
+    /// Constructor, timestamping the transaction with the given clock.
+    pub fn with_clock<C: Clock>(
+        nonce: Nonce,
+        record: TransactionRecord,
+        from: Option<Id>,
+        clock: &C,
+    ) -> Self {
         Transaction {
             nonce,
             from_account_id: from,
             record,
             signature: None,
-            created_at: SystemTime::now(),
+            created_at: clock.now(),
+            memo: None,
+            fee: 0,
         }
     }
 
-    /// Calculate the cryptographic hash of this transaction.
+    /// Tag the transaction with a memo.
+    pub fn with_memo(mut self, memo: &str) -> Self {
+        self.memo = Some(memo.to_string());
+        self
+    }
+
+    /// Offer a fee to the beneficiary of the block including the
+    /// transaction.
+    pub fn with_fee(mut self, fee: Amount) -> Self {
+        self.fee = fee;
+        self
+    }
+
+    /// Calculate the cryptographic hash of this transaction: the hash of its
+    /// canonical encoding.
     pub fn calculate_hash(&self) -> Hash {
-        blake3::hash(
-            format!(
-                "{:?}_{:?}_{:?}_{:?}",
-                self.record, self.nonce, self.from_account_id, self.created_at,
-            )
-            .as_bytes(),
-        )
-        .as_bytes()
-        .to_vec()
+        blake3::hash(&self.to_canonical_bytes()).as_bytes().to_vec()
+    }
+
+    /**
+    Encode everything this transaction commits to, to be hashed and signed.
+    The signature itself is left out. All integers are big endian, and
+    variable-length fields are prefixed with their length as a `u64`:
+
+    | bytes      | content                                            |
+    |------------|----------------------------------------------------|
+    | 1          | version, `CANONICAL_VERSION`                       |
+    | 8          | nonce                                              |
+    | 1 (+ ...)  | `0` without a sender, or `1` and the sender's ID   |
+    | 1          | record type, see below                             |
+    | ...        | record fields, in order                            |
+    | 8 + 4      | creation time: seconds and nanoseconds since the UNIX epoch |
+    | 1 (+ ...)  | `0` without a memo, or `1` and the memo            |
+    | 8          | fee                                                |
+
+    | type | record              | fields                                |
+    |------|---------------------|---------------------------------------|
+    | 0    | `CreateUserAccount` | ID, public key                        |
+    | 1    | `SendTokens`        | recipient ID, amount (8 bytes)        |
+    | 2    | `RegisterName`      | name                                  |
+    | 3    | `FreezeAccount`     | ID                                    |
+    | 4    | `UnfreezeAccount`   | ID                                    |
+    | 5    | `SetMetadata`       | key, `0` to remove or `1` and the value |
+    | 6    | `TransferName`      | name, recipient ID                    |
+    | 7    | `ScheduleParams`    | activation height, minimum transfer, minimum balance, name registration period (8 bytes each), faucet (1 byte), difficulty (4 bytes) |
+
+    ```
+    # use crate::blockchain::transaction::{Transaction, TransactionRecord};
+    let transaction = Transaction::new(
+        7,
+        TransactionRecord::RegisterName { name: "alice".to_string() },
+        None,
+    );
+    let bytes = transaction.to_canonical_bytes();
+
+    assert_eq!([1, 0, 0, 0, 0, 0, 0, 0, 7, 0, 2], bytes[..11]);
+    ```
+    */
+    pub fn to_canonical_bytes(&self) -> Vec<u8> {
+        let mut out = vec![];
+        self.put_canonical_bytes(&mut out);
+        out
+    }
+
+    /// Append the canonical encoding to a buffer, without allocating one for
+    /// each transaction.
+    pub fn put_canonical_bytes(&self, out: &mut Vec<u8>) {
+        out.push(CANONICAL_VERSION);
+        put_u64(out, self.nonce);
+        put_optional_bytes(out, self.from_account_id.as_ref());
+
+        match &self.record {
+            TransactionRecord::CreateUserAccount { id, public_key } => {
+                out.push(0);
+                put_bytes(out, id.as_ref());
+                put_bytes(out, public_key);
+            }
+            TransactionRecord::SendTokens { to, amount } => {
+                out.push(1);
+                put_bytes(out, to.as_ref());
+                put_u64(out, *amount);
+            }
+            TransactionRecord::RegisterName { name } => {
+                out.push(2);
+                put_bytes(out, name.as_bytes());
+            }
+            TransactionRecord::FreezeAccount(id) => {
+                out.push(3);
+                put_bytes(out, id.as_ref());
+            }
+            TransactionRecord::UnfreezeAccount(id) => {
+                out.push(4);
+                put_bytes(out, id.as_ref());
+            }
+            TransactionRecord::SetMetadata { key, value } => {
+                out.push(5);
+                put_bytes(out, key.as_bytes());
+                put_optional_bytes(out, value.as_ref());
+            }
+            TransactionRecord::TransferName { name, to } => {
+                out.push(6);
+                put_bytes(out, name.as_bytes());
+                put_bytes(out, to.as_ref());
+            }
+            TransactionRecord::ScheduleParams { height, params } => {
+                out.push(7);
+                put_u64(out, *height as u64);
+                put_params(out, params);
+            }
+        }
+
+        put_time(out, self.created_at);
+        put_optional_bytes(out, self.memo.as_ref());
+        put_u64(out, self.fee);
+    }
+
+    /// Decode a transaction from its canonical encoding. The signature is
+    /// not part of it: the transaction is unsigned.
+    pub fn from_canonical_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        let version = reader.get_u8()?;
+        if version != CANONICAL_VERSION {
+            return Err(format!("unsupported encoding version {}", version));
+        }
+
+        let nonce = reader.get_u64()?;
+        let from_account_id = reader.get_optional_string()?.map(Id::from);
+        let record = match reader.get_u8()? {
+            0 => TransactionRecord::CreateUserAccount {
+                id: reader.get_string()?.into(),
+                public_key: reader.get_bytes()?.to_vec(),
+            },
+            1 => TransactionRecord::SendTokens {
+                to: reader.get_string()?.into(),
+                amount: reader.get_u64()?,
+            },
+            2 => TransactionRecord::RegisterName {
+                name: reader.get_string()?,
+            },
+            3 => TransactionRecord::FreezeAccount(reader.get_string()?.into()),
+            4 => TransactionRecord::UnfreezeAccount(reader.get_string()?.into()),
+            5 => TransactionRecord::SetMetadata {
+                key: reader.get_string()?,
+                value: reader.get_optional_string()?,
+            },
+            6 => TransactionRecord::TransferName {
+                name: reader.get_string()?,
+                to: reader.get_string()?.into(),
+            },
+            7 => TransactionRecord::ScheduleParams {
+                height: usize::try_from(reader.get_u64()?).map_err(|_| "height out of range")?,
+                params: get_params(&mut reader)?,
+            },
+            tag => return Err(format!("unknown record type {}", tag)),
+        };
+        let created_at = reader.get_time()?;
+        let memo = reader.get_optional_string()?;
+        let fee = reader.get_u64()?;
+        reader.finish()?;
+
+        Ok(Transaction {
+            nonce,
+            from_account_id,
+            record,
+            signature: None,
+            created_at,
+            memo,
+            fee,
+        })
+    }
+
+    /// Sign the hash of this transaction, on behalf of its sender.
+    pub fn sign(&mut self, keypair: &Keypair) {
+        self.signature = Some(keypair.sign(&self.calculate_hash()));
+    }
+
+    /// Was this transaction signed by the owner of that public key?
+    pub fn verify_signature(&self, public_key: &[u8]) -> Result<(), BlockchainError> {
+        let signature = self
+            .signature
+            .as_ref()
+            .ok_or(BlockchainError::MissingSignature)?;
+        let public_key = <[u8; ed25519_dalek::PUBLIC_KEY_LENGTH]>::try_from(public_key)
+            .ok()
+            .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+            .ok_or(BlockchainError::InvalidPublicKey)?;
+        public_key
+            .verify_strict(&self.calculate_hash(), signature)
+            .map_err(|_| BlockchainError::InvalidSignature)
+    }
+
+    /// Size of this transaction once serialized, in bytes.
+    pub fn size(&self) -> usize {
+        let id_size = |id: &Id| std::mem::size_of::<u64>() + id.as_ref().len();
+
+        let record_size = 1 + match &self.record {
+            TransactionRecord::CreateUserAccount { id, public_key } => {
+                id_size(id) + std::mem::size_of::<u64>() + public_key.len()
+            }
+            TransactionRecord::SendTokens { to, .. } => id_size(to) + std::mem::size_of::<Amount>(),
+            TransactionRecord::FreezeAccount(id) | TransactionRecord::UnfreezeAccount(id) => {
+                id_size(id)
+            }
+            TransactionRecord::SetMetadata { key, value } => {
+                std::mem::size_of::<u64>()
+                    + key.len()
+                    + 1
+                    + value
+                        .as_ref()
+                        .map_or(0, |value| std::mem::size_of::<u64>() + value.len())
+            }
+            TransactionRecord::RegisterName { name } => std::mem::size_of::<u64>() + name.len(),
+            TransactionRecord::TransferName { name, to } => {
+                std::mem::size_of::<u64>() + name.len() + id_size(to)
+            }
+            TransactionRecord::ScheduleParams { .. } => {
+                std::mem::size_of::<u64>() + ENCODED_PARAMS_SIZE
+            }
+        };
+
+        std::mem::size_of::<Nonce>()
+            + 1
+            + self.from_account_id.as_ref().map_or(0, id_size)
+            + record_size
+            + 1
+            + self
+                .signature
+                .as_ref()
+                .map_or(0, |_| ed25519_dalek::SIGNATURE_LENGTH)
+            + 1
+            + self
+                .memo
+                .as_ref()
+                .map_or(0, |memo| std::mem::size_of::<u64>() + memo.len())
+            // seconds and nanoseconds since the UNIX epoch
+            + std::mem::size_of::<u64>()
+            + std::mem::size_of::<u32>()
+            + std::mem::size_of::<Amount>()
+    }
+
+    /// Priority class of the transaction, when building blocks: transactions
+    /// sent by a system account go first, whatever their record.
+    pub fn lane<T: WorldState>(&self, world_state: &T) -> Lane {
+        let sender = self
+            .from_account_id
+            .as_ref()
+            .and_then(|id| world_state.get_account_by_id(id).ok());
+        match sender {
+            Some(account) if account.account_type.is_authority() => Lane::System,
+            _ => Lane::User,
+        }
+    }
+
+    /// IDs of all the accounts involved in this transaction.
+    pub fn touched_accounts(&self) -> Vec<&Id> {
+        let mut accounts: Vec<&Id> = self.from_account_id.iter().collect();
+        match &self.record {
+            TransactionRecord::CreateUserAccount { id, .. } => accounts.push(id),
+            TransactionRecord::SendTokens { to, .. } => accounts.push(to),
+            TransactionRecord::FreezeAccount(id) | TransactionRecord::UnfreezeAccount(id) => {
+                accounts.push(id)
+            }
+            TransactionRecord::SetMetadata { .. }
+            | TransactionRecord::RegisterName { .. }
+            | TransactionRecord::ScheduleParams { .. } => {}
+            TransactionRecord::TransferName { to, .. } => accounts.push(to),
+        }
+        accounts
     }
 
     /// Execute this transaction against the Blockchain.
     /// TODO: use a TransactionRecord trait for better polymorphism.
-    pub fn apply<T: WorldState>(&self, world_state: &mut T) -> Result<(), Error> {
+    pub fn apply<T: WorldState>(&self, world_state: &mut T) -> Result<(), BlockchainError> {
+        if self
+            .memo
+            .as_ref()
+            .is_some_and(|memo| memo.len() > MAX_MEMO_LENGTH)
+        {
+            return Err(BlockchainError::MemoTooLong);
+        }
+
+        let fee_payer = self.check_fee(world_state)?;
+        self.apply_record(world_state)?;
+        if let Some(from_id) = fee_payer {
+            world_state.get_account_by_id_mut(from_id)?.tokens -= self.fee;
+            world_state.emit(TransactionEvent::FeePaid {
+                from: from_id.to_owned(),
+                amount: self.fee,
+            });
+            world_state.collect_fee(self.fee)?;
+        }
+        Ok(())
+    }
+
+    /// Can the sender pay the fee, on top of the tokens it sends, without
+    /// being left with dust? Returns who pays, if there is a fee.
+    fn check_fee<T: WorldState>(&self, world_state: &T) -> Result<Option<&Id>, BlockchainError> {
+        if self.fee == 0 {
+            return Ok(None);
+        }
+
+        let from_id = self
+            .from_account_id
+            .as_ref()
+            .ok_or(BlockchainError::MissingSender)?;
+        let sent = match self.record {
+            TransactionRecord::SendTokens { amount, .. } => amount,
+            _ => 0,
+        };
+        let required = sent
+            .checked_add(self.fee)
+            .ok_or(BlockchainError::Overflow)?;
+        let available = world_state.get_account_by_id(from_id)?.tokens;
+        if available < required {
+            return Err(BlockchainError::InsufficientFunds {
+                available,
+                required,
+            });
+        }
+
+        // tokens sent to oneself come back
+        let spent = match &self.record {
+            TransactionRecord::SendTokens { to, .. } if to == from_id => self.fee,
+            _ => required,
+        };
+        if world_state.params().is_dust(available - spent) {
+            return Err(BlockchainError::DustBalance);
+        }
+        Ok(Some(from_id))
+    }
+
+    /// Execute the record of this transaction, leaving the fee aside.
+    fn apply_record<T: WorldState>(&self, world_state: &mut T) -> Result<(), BlockchainError> {
         match &self.record {
-            TransactionRecord::CreateUserAccount(id) => {
-                world_state
-                    .get_account_by_id(id)
-                    .map_or(Ok(()), |_| Err("account already exists".to_string()))?;
+            TransactionRecord::CreateUserAccount { id, public_key } => {
+                if *id != Id::from_public_key(public_key) {
+                    return Err(BlockchainError::AccountIdMismatch);
+                }
                 world_state.add_account(id.to_owned())?;
+                world_state.get_account_by_id_mut(id)?.public_key = Some(public_key.to_owned());
+                world_state.emit(TransactionEvent::AccountCreated { id: id.to_owned() });
                 Ok(())
             }
 
-            TransactionRecord::MintTokens { to, amount } => match &self.from_account_id {
-                Some(_) => Err("users cannot mint tokens".to_string()),
-                None => {
-                    if !world_state.is_genesis() {
-                        return Err("cannot mint tokens after genesis".to_string());
-                    }
+            TransactionRecord::SendTokens { to, amount } => {
+                let params = world_state.params();
+                if *amount < params.min_transfer {
+                    return Err(BlockchainError::BelowMinimumTransfer);
+                }
 
-                    let to_acc = world_state.get_account_by_id_mut(to)?;
+                let from_id = self
+                    .from_account_id
+                    .as_ref()
+                    .ok_or(BlockchainError::MissingSender)?;
+                let from = world_state.get_account_by_id(from_id)?;
+                if !from.account_type.may_send() {
+                    return Err(BlockchainError::CannotSend);
+                }
+                if from.frozen {
+                    return Err(BlockchainError::AccountFrozen);
+                }
+                let from_tokens = from.tokens;
+                let to_tokens = world_state.get_account_by_id(to)?.tokens;
 
-                    println!("minting {:?} tokens for {:?}", amount, to);
-                    to_acc.tokens = to_acc
-                        .tokens
+                // check everything before moving tokens, not to leave the
+                // world half-updated
+                let required = amount
+                    .checked_add(self.fee)
+                    .ok_or(BlockchainError::Overflow)?;
+                let remaining = from_tokens.checked_sub(required).ok_or(
+                    BlockchainError::InsufficientFunds {
+                        available: from_tokens,
+                        required,
+                    },
+                )?;
+                if from_id != to {
+                    let received = to_tokens
                         .checked_add(*amount)
-                        .ok_or("too many tokens")?;
-                    Ok(())
+                        .ok_or(BlockchainError::Overflow)?;
+                    if params.is_dust(remaining) || params.is_dust(received) {
+                        return Err(BlockchainError::DustBalance);
+                    }
                 }
-            },
 
-            TransactionRecord::SendTokens { to, amount } => {
-                let from = world_state
-                    .get_account_by_id_mut(
-                        self.from_account_id
-                            .as_ref()
-                            .ok_or("missing from account")?,
-                    )
-                    .map_err(|_| "from account doesn't exist")?;
-                from.tokens = from
-                    .tokens
-                    .checked_sub(*amount)
-                    .ok_or("not enough tokens")?;
-
-                let to = world_state
-                    .get_account_by_id_mut(to)
-                    .map_err(|_| "to account doesn't exist")?;
-                to.tokens = to.tokens.checked_add(*amount).ok_or("too many tokens")?;
+                world_state.get_account_by_id_mut(from_id)?.tokens -= *amount;
+                world_state.get_account_by_id_mut(to)?.tokens += *amount;
+
+                world_state.emit(TransactionEvent::Transfer {
+                    from: from_id.to_owned(),
+                    to: to.to_owned(),
+                    amount: *amount,
+                });
+                Ok(())
+            }
+
+            TransactionRecord::FreezeAccount(id) | TransactionRecord::UnfreezeAccount(id) => {
+                let frozen = matches!(self.record, TransactionRecord::FreezeAccount(_));
+                self.check_authority(world_state)?;
+
+                world_state.get_account_by_id_mut(id)?.frozen = frozen;
+                world_state.emit(TransactionEvent::FreezeChanged {
+                    id: id.to_owned(),
+                    frozen,
+                });
+                Ok(())
+            }
+
+            TransactionRecord::SetMetadata { key, value } => {
+                let from_id = self
+                    .from_account_id
+                    .as_ref()
+                    .ok_or(BlockchainError::MissingSender)?;
+                world_state
+                    .get_account_by_id_mut(from_id)?
+                    .set_metadata(key, value.as_deref())
+                    .map_err(BlockchainError::InvalidMetadata)?;
+
+                world_state.emit(TransactionEvent::MetadataSet {
+                    account: from_id.to_owned(),
+                    key: key.to_owned(),
+                    value: value.to_owned(),
+                });
+                Ok(())
+            }
+
+            TransactionRecord::RegisterName { name } => {
+                validate_name(name).map_err(BlockchainError::InvalidName)?;
+                let owner = self
+                    .from_account_id
+                    .as_ref()
+                    .ok_or(BlockchainError::MissingSender)?;
+                world_state.get_account_by_id(owner)?;
+
+                let height = world_state.height();
+                let expires_at = match world_state.get_name(name) {
+                    Some(record) if record.is_active(height) && record.owner != *owner => {
+                        return Err(BlockchainError::NameAlreadyRegistered)
+                    }
+                    // renewal: extend the current registration
+                    Some(record) if record.is_active(height) => record.expires_at,
+                    _ => height,
+                }
+                .checked_add(world_state.params().name_registration_period)
+                .ok_or(BlockchainError::RegistrationTooLong)?;
+
+                world_state.set_name(
+                    name.to_owned(),
+                    NameRecord {
+                        owner: owner.to_owned(),
+                        expires_at,
+                    },
+                );
+                world_state.emit(TransactionEvent::NameRegistered {
+                    name: name.to_owned(),
+                    owner: owner.to_owned(),
+                    expires_at,
+                });
+                Ok(())
+            }
+
+            TransactionRecord::TransferName { name, to } => {
+                let from_id = self
+                    .from_account_id
+                    .as_ref()
+                    .ok_or(BlockchainError::MissingSender)?;
+                let height = world_state.height();
+                let expires_at = match world_state.get_name(name) {
+                    Some(record) if record.is_active(height) && record.owner == *from_id => {
+                        record.expires_at
+                    }
+                    _ => return Err(BlockchainError::NameNotOwned),
+                };
+                world_state.get_account_by_id(to)?;
+
+                world_state.set_name(
+                    name.to_owned(),
+                    NameRecord {
+                        owner: to.to_owned(),
+                        expires_at,
+                    },
+                );
+                world_state.emit(TransactionEvent::NameTransferred {
+                    name: name.to_owned(),
+                    from: from_id.to_owned(),
+                    to: to.to_owned(),
+                });
+                Ok(())
+            }
+
+            TransactionRecord::ScheduleParams { height, params } => {
+                self.check_authority(world_state)?;
+                // the transactions of the block being applied were checked
+                // against the rules in effect at its height
+                if *height <= world_state.height() {
+                    return Err(BlockchainError::ActivationHeightInPast);
+                }
 
+                world_state.schedule_params(*height, params.to_owned());
+                world_state.emit(TransactionEvent::ParamsScheduled {
+                    height: *height,
+                    params: params.to_owned(),
+                });
                 Ok(())
             }
         }
     }
+
+    /// Is the sender the system authority, and did it sign the transaction?
+    /// What it can do is too powerful to trust the caller to have checked the
+    /// signature.
+    fn check_authority<T: WorldState>(&self, world_state: &T) -> Result<(), BlockchainError> {
+        let from_id = self
+            .from_account_id
+            .as_ref()
+            .ok_or(BlockchainError::MissingSender)?;
+        let authority = world_state.get_account_by_id(from_id)?;
+        if !authority.account_type.is_authority() {
+            return Err(BlockchainError::NotAuthority);
+        }
+        let public_key = authority
+            .public_key
+            .as_ref()
+            .ok_or(BlockchainError::MissingPublicKey)?;
+        self.verify_signature(public_key)
+    }
 }
 
 #[cfg(test)]
 mod transaction_tests {
     use super::*;
-    use crate::block::Block;
     use crate::blockchain::Blockchain;
 
-    fn create_user(world_state: &mut impl WorldState, id: &str) -> Result<(), Error> {
-        let transaction =
-            Transaction::new(0, TransactionRecord::CreateUserAccount(id.into()), None);
-        transaction.apply(world_state)
+    /// ID of the account owned by the key `name`.
+    fn id(name: &str) -> Id {
+        Id::from_public_key(name.as_bytes())
     }
 
-    fn mint_tokens(
-        world_state: &mut impl WorldState,
-        id: &str,
-        amount: Amount,
-    ) -> Result<(), Error> {
+    fn create_user(world_state: &mut impl WorldState, name: &str) -> Result<(), BlockchainError> {
         let transaction = Transaction::new(
             0,
-            TransactionRecord::MintTokens {
-                to: id.into(),
-                amount,
-            },
+            TransactionRecord::create_user_account(name.as_bytes()),
             None,
         );
         transaction.apply(world_state)
     }
 
+    fn fund(world_state: &mut impl WorldState, name: &str, amount: Amount) {
+        world_state.get_account_by_id_mut(&id(name)).unwrap().tokens = amount;
+    }
+
     fn send_tokens(
         world_state: &mut impl WorldState,
         from: &str,
-        id: &str,
+        to: &str,
         amount: Amount,
-    ) -> Result<(), Error> {
+    ) -> Result<(), BlockchainError> {
         let transaction = Transaction::new(
             0,
-            TransactionRecord::SendTokens {
-                to: id.into(),
-                amount,
-            },
-            Some(from.into()),
+            TransactionRecord::SendTokens { to: id(to), amount },
+            Some(id(from)),
         );
         transaction.apply(world_state)
     }
@@ -190,6 +857,27 @@ mod transaction_tests {
     fn test_apply_create_user() {
         let mut chain = Blockchain::new();
         assert_eq!(Ok(()), create_user(&mut chain, "someone"));
+
+        let account = chain.get_account_by_id(&id("someone")).unwrap();
+        assert_eq!(Some(b"someone".to_vec()), account.public_key);
+    }
+
+    #[test]
+    fn test_apply_create_user_with_unrelated_id() {
+        let mut chain = Blockchain::new();
+        let transaction = Transaction::new(
+            0,
+            TransactionRecord::CreateUserAccount {
+                id: "someone".into(),
+                public_key: b"someone".to_vec(),
+            },
+            None,
+        );
+
+        assert_eq!(
+            Err(BlockchainError::AccountIdMismatch),
+            transaction.apply(&mut chain)
+        );
     }
 
     #[test]
@@ -198,109 +886,491 @@ mod transaction_tests {
         create_user(&mut chain, "someone").unwrap();
 
         assert_eq!(
-            Err("account already exists".to_string()),
+            Err(BlockchainError::AccountAlreadyExists(id("someone"))),
             create_user(&mut chain, "someone")
         );
     }
 
     #[test]
-    fn test_apply_mint() {
+    fn test_send_tokens() {
         let mut chain = Blockchain::new();
-        let account_id = "someone";
 
-        create_user(&mut chain, account_id).unwrap();
+        create_user(&mut chain, "sender").unwrap();
+        create_user(&mut chain, "receiver").unwrap();
+        fund(&mut chain, "sender", 200);
 
-        assert_eq!(Ok(()), mint_tokens(&mut chain, account_id, 200));
+        let res = send_tokens(&mut chain, "sender", "receiver", 180);
+        assert_eq!(Ok(()), res);
+
+        let sender = chain.get_account_by_id(&id("sender")).unwrap();
+        assert_eq!(20, sender.tokens);
 
-        let account = chain.get_account_by_id(&account_id.into()).unwrap();
-        assert_eq!(200, account.tokens)
+        let receiver = chain.get_account_by_id(&id("receiver")).unwrap();
+        assert_eq!(180, receiver.tokens);
     }
 
     #[test]
-    fn test_apply_mint_missing_user() {
+    fn test_send_tokens_not_enough_tokens() {
         let mut chain = Blockchain::new();
+
+        create_user(&mut chain, "sender").unwrap();
+        create_user(&mut chain, "receiver").unwrap();
+        fund(&mut chain, "sender", 200);
+
+        let res = send_tokens(&mut chain, "sender", "receiver", 5000);
         assert_eq!(
-            Err("account doesn't exist".to_string()),
-            mint_tokens(&mut chain, "I don't exist", 200),
+            Err(BlockchainError::InsufficientFunds {
+                available: 200,
+                required: 5000
+            }),
+            res
         );
     }
 
     #[test]
-    fn test_prevent_using_minting() {
-        let mut chain = Blockchain::new();
-        let account_id = "someone";
+    fn test_fee() {
+        use crate::testing::MockWorldState;
 
-        create_user(&mut chain, account_id).unwrap();
+        let mut world = MockWorldState::new()
+            .with_account("sender", 100)
+            .with_account("receiver", 0);
+        let id = |name: &str| Id::from(name);
 
-        let transaction = Transaction::new(
-            1,
-            TransactionRecord::MintTokens {
-                to: account_id.into(),
-                amount: 200,
+        let transfer = |amount, fee| {
+            Transaction::new(
+                0,
+                TransactionRecord::SendTokens {
+                    to: id("receiver"),
+                    amount,
+                },
+                Some(id("sender")),
+            )
+            .with_fee(fee)
+        };
+        assert_eq!(
+            Err(BlockchainError::InsufficientFunds {
+                available: 100,
+                required: 101
+            }),
+            transfer(95, 6).apply(&mut world)
+        );
+        assert_eq!(
+            Err(BlockchainError::Overflow),
+            transfer(1, Amount::MAX).apply(&mut world)
+        );
+        assert_eq!(Ok(()), transfer(90, 5).apply(&mut world));
+        assert_eq!(5, world.get_account_by_id(&id("sender")).unwrap().tokens);
+        assert_eq!(90, world.get_account_by_id(&id("receiver")).unwrap().tokens);
+        assert_eq!(5, world.fees());
+
+        // other records pay their fee too, but only with a sender
+        let register = Transaction::new(
+            0,
+            TransactionRecord::RegisterName {
+                name: "sender".to_string(),
             },
-            Some(account_id.into()),
+            Some(id("sender")),
+        );
+        assert_eq!(
+            Err(BlockchainError::InsufficientFunds {
+                available: 5,
+                required: 6
+            }),
+            register.clone().with_fee(6).apply(&mut world)
         );
+        assert_eq!(Ok(()), register.with_fee(5).apply(&mut world));
+        assert_eq!(0, world.get_account_by_id(&id("sender")).unwrap().tokens);
+        assert_eq!(10, world.fees());
         assert_eq!(
-            Err("users cannot mint tokens".to_string()),
-            transaction.apply(&mut chain),
+            Err(BlockchainError::MissingSender),
+            Transaction::new(0, TransactionRecord::create_user_account(b"x"), None)
+                .with_fee(1)
+                .apply(&mut world)
         );
     }
 
     #[test]
-    fn test_apply_mint_not_genesis() {
+    fn test_fee_needs_a_beneficiary() {
         let mut chain = Blockchain::new();
+        create_user(&mut chain, "sender").unwrap();
+        fund(&mut chain, "sender", 100);
 
-        let mut block = Block::new();
-        block.hash = Some(block.calculate_hash());
-        chain.add_block(block).unwrap();
-
-        create_user(&mut chain, "someone").unwrap();
+        // outside of a block, nobody would collect the fee
+        let register = Transaction::new(
+            0,
+            TransactionRecord::RegisterName {
+                name: "sender".to_string(),
+            },
+            Some(id("sender")),
+        );
         assert_eq!(
-            Err("cannot mint tokens after genesis".to_string()),
-            mint_tokens(&mut chain, "someone", 200)
+            Err(BlockchainError::MissingBeneficiary),
+            register.with_fee(1).apply(&mut chain)
         );
     }
 
     #[test]
-    fn test_send_tokens() {
+    fn test_send_tokens_overflow() {
         let mut chain = Blockchain::new();
 
         create_user(&mut chain, "sender").unwrap();
+        fund(&mut chain, "sender", Amount::MAX);
         create_user(&mut chain, "receiver").unwrap();
-        mint_tokens(&mut chain, "sender", 200).unwrap();
+        fund(&mut chain, "receiver", Amount::MAX);
 
-        let res = send_tokens(&mut chain, "sender", "receiver", 180);
-        assert_eq!(Ok(()), res);
+        let res = send_tokens(&mut chain, "sender", "receiver", 5000);
+        assert_eq!(Err(BlockchainError::Overflow), res);
+    }
 
-        let sender = chain.get_account_by_id(&"sender".into()).unwrap();
-        assert_eq!(20, sender.tokens);
+    #[test]
+    fn test_send_tokens_dust() {
+        use crate::params::ChainParams;
+        use crate::testing::MockWorldState;
 
-        let receiver = chain.get_account_by_id(&"receiver".into()).unwrap();
-        assert_eq!(180, receiver.tokens);
+        let mut world = MockWorldState::new().with_params(ChainParams {
+            min_transfer: 10,
+            min_balance: 50,
+            ..ChainParams::default()
+        });
+        create_user(&mut world, "sender").unwrap();
+        create_user(&mut world, "receiver").unwrap();
+        fund(&mut world, "sender", 200);
+
+        assert_eq!(
+            Err(BlockchainError::BelowMinimumTransfer),
+            send_tokens(&mut world, "sender", "receiver", 9)
+        );
+        assert_eq!(
+            Err(BlockchainError::DustBalance),
+            send_tokens(&mut world, "sender", "receiver", 160)
+        );
+        assert_eq!(
+            Err(BlockchainError::DustBalance),
+            send_tokens(&mut world, "sender", "receiver", 10)
+        );
+        assert_eq!(Ok(()), send_tokens(&mut world, "sender", "receiver", 50));
+        assert_eq!(Ok(()), send_tokens(&mut world, "sender", "receiver", 150));
     }
 
     #[test]
-    fn test_send_tokens_not_enough_tokens() {
-        let mut chain = Blockchain::new();
+    fn test_only_users_send_tokens() {
+        use crate::account::AccountType;
 
-        create_user(&mut chain, "sender").unwrap();
+        let mut chain = Blockchain::new();
+        create_user(&mut chain, "treasury").unwrap();
         create_user(&mut chain, "receiver").unwrap();
-        mint_tokens(&mut chain, "sender", 200).unwrap();
+        fund(&mut chain, "treasury", 200);
+        fund(&mut chain, "receiver", 10);
+        chain
+            .get_account_by_id_mut(&id("treasury"))
+            .unwrap()
+            .account_type = AccountType::System;
 
-        let res = send_tokens(&mut chain, "sender", "receiver", 5000);
-        assert_eq!(Err("not enough tokens".to_string()), res);
+        assert_eq!(
+            Err(BlockchainError::CannotSend),
+            send_tokens(&mut chain, "treasury", "receiver", 10)
+        );
+        assert_eq!(Ok(()), send_tokens(&mut chain, "receiver", "treasury", 10));
+    }
+
+    fn register_name(
+        world_state: &mut impl WorldState,
+        from: &str,
+        name: &str,
+    ) -> Result<(), BlockchainError> {
+        let transaction = Transaction::new(
+            0,
+            TransactionRecord::RegisterName {
+                name: name.to_string(),
+            },
+            Some(id(from)),
+        );
+        transaction.apply(world_state)
     }
 
     #[test]
-    fn test_send_tokens_overflow() {
+    fn test_register_name() {
+        use crate::params::ChainParams;
+        use crate::testing::MockWorldState;
+
+        let params = ChainParams {
+            name_registration_period: 10,
+            ..ChainParams::default()
+        };
+        let mut world = MockWorldState::new().with_params(params.clone());
+        create_user(&mut world, "alice").unwrap();
+        create_user(&mut world, "bob").unwrap();
+
+        assert_eq!(Ok(()), register_name(&mut world, "alice", "alice"));
+        assert_eq!(
+            Some(&NameRecord {
+                owner: id("alice"),
+                expires_at: 10
+            }),
+            world.get_name("alice")
+        );
+        assert_eq!(
+            Err(BlockchainError::NameAlreadyRegistered),
+            register_name(&mut world, "bob", "alice")
+        );
+        assert!(register_name(&mut world, "bob", "Bob").is_err());
+
+        // renewing extends the registration
+        assert_eq!(Ok(()), register_name(&mut world, "alice", "alice"));
+        assert_eq!(20, world.get_name("alice").unwrap().expires_at);
+
+        // once expired, anyone can register it
+        let mut world = MockWorldState::new().with_params(params).with_height(20);
+        create_user(&mut world, "bob").unwrap();
+        world.set_name(
+            "alice".to_string(),
+            NameRecord {
+                owner: id("alice"),
+                expires_at: 20,
+            },
+        );
+        assert_eq!(Ok(()), register_name(&mut world, "bob", "alice"));
+        assert_eq!(id("bob"), world.get_name("alice").unwrap().owner);
+    }
+
+    #[test]
+    fn test_transfer_name() {
+        use crate::testing::MockWorldState;
+
+        let mut world = MockWorldState::new();
+        create_user(&mut world, "alice").unwrap();
+        create_user(&mut world, "bob").unwrap();
+        register_name(&mut world, "alice", "shop").unwrap();
+
+        let transfer = |from: &str| {
+            Transaction::new(
+                0,
+                TransactionRecord::TransferName {
+                    name: "shop".to_string(),
+                    to: id("bob"),
+                },
+                Some(id(from)),
+            )
+        };
+        assert_eq!(
+            Err(BlockchainError::NameNotOwned),
+            transfer("bob").apply(&mut world)
+        );
+        assert_eq!(Ok(()), transfer("alice").apply(&mut world));
+        assert_eq!(id("bob"), world.get_name("shop").unwrap().owner);
+        assert_eq!(
+            Some(&TransactionEvent::NameTransferred {
+                name: "shop".to_string(),
+                from: id("alice"),
+                to: id("bob"),
+            }),
+            world.events().last()
+        );
+    }
+
+    #[test]
+    fn test_set_metadata() {
         let mut chain = Blockchain::new();
+        create_user(&mut chain, "someone").unwrap();
 
-        create_user(&mut chain, "sender").unwrap();
-        mint_tokens(&mut chain, "sender", Amount::MAX).unwrap();
-        create_user(&mut chain, "receiver").unwrap();
-        mint_tokens(&mut chain, "receiver", Amount::MAX).unwrap();
+        let set = |value: Option<&str>| {
+            Transaction::new(
+                0,
+                TransactionRecord::SetMetadata {
+                    key: "url".to_string(),
+                    value: value.map(ToString::to_string),
+                },
+                Some(id("someone")),
+            )
+        };
+        assert_eq!(Ok(()), set(Some("https://example.com")).apply(&mut chain));
+        let account = chain.get_account_by_id(&id("someone")).unwrap();
+        assert_eq!(
+            Some("https://example.com"),
+            account.metadata.get("url").map(String::as_str)
+        );
 
-        let res = send_tokens(&mut chain, "sender", "receiver", 5000);
-        assert_eq!(Err("too many tokens".to_string()), res);
+        assert_eq!(Ok(()), set(None).apply(&mut chain));
+        let account = chain.get_account_by_id(&id("someone")).unwrap();
+        assert!(account.metadata.is_empty());
+    }
+
+    #[test]
+    fn test_freeze_account() {
+        use crate::account::AccountType;
+        use crate::testing::keypair;
+
+        let mut chain = Blockchain::new();
+        create_user(&mut chain, "authority").unwrap();
+        create_user(&mut chain, "someone").unwrap();
+        fund(&mut chain, "someone", 100);
+
+        let freeze = |from: &str, record: TransactionRecord| {
+            let mut transaction = Transaction::new(0, record, Some(id(from)));
+            transaction.sign(&keypair(from));
+            transaction
+        };
+        assert_eq!(
+            Err(BlockchainError::NotAuthority),
+            freeze("authority", TransactionRecord::FreezeAccount(id("someone"))).apply(&mut chain)
+        );
+
+        let authority = chain.get_account_by_id_mut(&id("authority")).unwrap();
+        authority.account_type = AccountType::System;
+        authority.public_key = Some(keypair("authority").verifying_key().to_bytes().to_vec());
+        assert_eq!(
+            Ok(()),
+            freeze("authority", TransactionRecord::FreezeAccount(id("someone"))).apply(&mut chain)
+        );
+        assert_eq!(
+            Err(BlockchainError::AccountFrozen),
+            send_tokens(&mut chain, "someone", "authority", 10)
+        );
+
+        assert_eq!(
+            Ok(()),
+            freeze(
+                "authority",
+                TransactionRecord::UnfreezeAccount(id("someone"))
+            )
+            .apply(&mut chain)
+        );
+        assert_eq!(Ok(()), send_tokens(&mut chain, "someone", "authority", 10));
+    }
+
+    #[test]
+    fn test_unsigned_freeze_is_rejected() {
+        use crate::account::AccountType;
+        use crate::testing::keypair;
+
+        let mut chain = Blockchain::new();
+        create_user(&mut chain, "authority").unwrap();
+        create_user(&mut chain, "someone").unwrap();
+        let authority = chain.get_account_by_id_mut(&id("authority")).unwrap();
+        authority.account_type = AccountType::System;
+        let freeze = Transaction::new(
+            0,
+            TransactionRecord::FreezeAccount(id("someone")),
+            Some(id("authority")),
+        );
+
+        authority.public_key = None;
+        assert_eq!(
+            Err(BlockchainError::MissingPublicKey),
+            freeze.apply(&mut chain)
+        );
+
+        chain
+            .get_account_by_id_mut(&id("authority"))
+            .unwrap()
+            .public_key = Some(keypair("authority").verifying_key().to_bytes().to_vec());
+        assert_eq!(
+            Err(BlockchainError::MissingSignature),
+            freeze.apply(&mut chain)
+        );
+        let mut forged = freeze.clone();
+        forged.sign(&keypair("someone"));
+        assert_eq!(
+            Err(BlockchainError::InvalidSignature),
+            forged.apply(&mut chain)
+        );
+        assert!(!chain.get_account_by_id(&id("someone")).unwrap().frozen);
+    }
+
+    #[test]
+    fn test_sign_and_verify_signature() {
+        use crate::account::generate_keypair;
+        use crate::rng::SeededRng;
+
+        let rng = SeededRng::new(0);
+        let (keypair, other) = (generate_keypair(&rng), generate_keypair(&rng));
+        let public_key = keypair.verifying_key().to_bytes();
+
+        let mut transaction = Transaction::new(
+            1,
+            TransactionRecord::SendTokens {
+                to: id("someone"),
+                amount: 10,
+            },
+            Some(Id::from_public_key(&public_key)),
+        );
+        assert_eq!(
+            Err(BlockchainError::MissingSignature),
+            transaction.verify_signature(&public_key)
+        );
+
+        transaction.sign(&keypair);
+        assert_eq!(Ok(()), transaction.verify_signature(&public_key));
+        assert_eq!(
+            Err(BlockchainError::InvalidSignature),
+            transaction.verify_signature(other.verifying_key().as_bytes())
+        );
+        assert_eq!(
+            Err(BlockchainError::InvalidPublicKey),
+            transaction.verify_signature(b"someone")
+        );
+
+        transaction.nonce += 1;
+        assert_eq!(
+            Err(BlockchainError::InvalidSignature),
+            transaction.verify_signature(&public_key)
+        );
+    }
+
+    #[test]
+    fn test_canonical_encoding_roundtrip() {
+        let records = vec![
+            TransactionRecord::create_user_account(b"someone"),
+            TransactionRecord::SendTokens {
+                to: id("someone"),
+                amount: Amount::MAX,
+            },
+            TransactionRecord::RegisterName {
+                name: "alice".to_string(),
+            },
+            TransactionRecord::FreezeAccount(id("someone")),
+            TransactionRecord::UnfreezeAccount(id("someone")),
+            TransactionRecord::SetMetadata {
+                key: "url".to_string(),
+                value: Some("https://example.com".to_string()),
+            },
+            TransactionRecord::SetMetadata {
+                key: "url".to_string(),
+                value: None,
+            },
+            TransactionRecord::TransferName {
+                name: "alice".to_string(),
+                to: id("someone"),
+            },
+            TransactionRecord::ScheduleParams {
+                height: 100,
+                params: ChainParams {
+                    min_transfer: 10,
+                    faucet: true,
+                    difficulty: 12,
+                    ..ChainParams::default()
+                },
+            },
+        ];
+        for record in records {
+            let mut transaction = Transaction::new(3, record, Some(id("sender"))).with_fee(7);
+            transaction.memo = Some("ref-42".to_string());
+            let bytes = transaction.to_canonical_bytes();
+            let decoded = Transaction::from_canonical_bytes(&bytes).unwrap();
+            assert_eq!(bytes, decoded.to_canonical_bytes());
+            assert_eq!(transaction.calculate_hash(), decoded.calculate_hash());
+        }
+
+        let bytes = Transaction::new(0, TransactionRecord::create_user_account(b"hi"), None)
+            .to_canonical_bytes();
+        for length in 0..bytes.len() {
+            assert!(Transaction::from_canonical_bytes(&bytes[..length]).is_err());
+        }
+        let mut unknown = bytes;
+        unknown[10] = 8;
+        assert_eq!(
+            Err("unknown record type 8".to_string()),
+            Transaction::from_canonical_bytes(&unknown).map(|_| ())
+        );
     }
 }
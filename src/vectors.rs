@@ -0,0 +1,209 @@
+use crate::account::{Amount, Keypair};
+use crate::block::Block;
+use crate::blockchain::Blockchain;
+use crate::clock::MockClock;
+use crate::export::{export_balances, export_blocks, export_transactions};
+use crate::genesis::GenesisConfig;
+use crate::testing::{account_id, keypair};
+use crate::transaction::{Transaction, TransactionRecord};
+use crate::world::WorldState;
+use crate::Hash;
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Expected hashes, one `name hex` pair per line.
+/// Never update them to make tests pass: a change means that nodes running
+/// different versions would disagree on hashes.
+const HASHES: &str = include_str!("../tests/vectors/hashes.txt");
+
+fn transactions() -> Vec<(&'static str, Transaction)> {
+    let clock = MockClock::new(UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+    let mut transactions = vec![];
+    let mut push = |name, nonce, record, from: Option<&str>| {
+        transactions.push((
+            name,
+            Transaction::with_clock(nonce, record, from.map(Into::into), &clock),
+        ));
+        clock.advance(Duration::from_millis(1500));
+    };
+
+    push(
+        "create_account",
+        0,
+        TransactionRecord::create_user_account(b"alice"),
+        None,
+    );
+    push(
+        "send_tokens",
+        1,
+        TransactionRecord::SendTokens {
+            to: "bob".into(),
+            amount: 10,
+        },
+        Some("alice"),
+    );
+    push(
+        "send_tokens_max",
+        u64::MAX,
+        TransactionRecord::SendTokens {
+            to: "bob".into(),
+            amount: Amount::MAX,
+        },
+        Some("alice"),
+    );
+    push(
+        "create_account_unicode",
+        0,
+        TransactionRecord::create_user_account("émile, \"🦀\"".as_bytes()),
+        Some("alice"),
+    );
+    // the largest fee, along with the largest nonce and amount
+    transactions[2].1.fee = Amount::MAX;
+    transactions
+}
+
+fn vectors() -> Vec<(&'static str, Hash)> {
+    let clock = MockClock::new(UNIX_EPOCH);
+    let transactions = transactions();
+    let mut vectors: Vec<(&str, Hash)> = transactions
+        .iter()
+        .map(|(name, transaction)| (*name, transaction.calculate_hash()))
+        .collect();
+
+    vectors.push(("empty_block", Block::with_clock(&clock).calculate_hash()));
+
+    let mut block = Block::with_clock(&clock);
+    block.transactions = transactions.into_iter().map(|(_, t)| t).collect();
+    block.header.beneficiary = Some("miner".into());
+    block.seal();
+    vectors.push(("merkle_root", block.merkle_root().to_vec()));
+    vectors.push(("block", block.calculate_hash()));
+
+    // ed25519 signatures are deterministic
+    let mut signed = block.transactions[1].clone();
+    signed.sign(&Keypair::from_bytes(&[7; 32]));
+    vectors.push((
+        "send_tokens_signature",
+        signed.signature.unwrap().to_bytes().to_vec(),
+    ));
+
+    vectors
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[test]
+fn test_hash_vectors() {
+    let expected: Vec<(&str, &str)> = HASHES
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut fields = line.split_whitespace();
+            (fields.next().unwrap(), fields.next().unwrap())
+        })
+        .collect();
+    let actual = vectors();
+
+    assert_eq!(expected.len(), actual.len(), "missing test vectors");
+    for ((expected_name, expected_hash), (name, hash)) in expected.into_iter().zip(actual) {
+        assert_eq!(expected_name, name);
+        assert_eq!(expected_hash, to_hex(&hash), "hash of {} changed", name);
+    }
+}
+
+/// Compare an encoding with its golden file, in `tests/golden`.
+/// Run the tests with `UPDATE_GOLDEN=1` to write the files instead, only
+/// when the encoding is meant to change.
+fn check_golden(name: &str, actual: &[u8]) {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "golden", name]
+        .iter()
+        .collect();
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read(&path).unwrap();
+    assert!(expected == actual, "encoding of {} changed", name);
+}
+
+/// A small chain, with deterministic timestamps.
+fn golden_chain() -> Blockchain {
+    let genesis = GenesisConfig {
+        timestamp: 1_600_000_000,
+        ..GenesisConfig::default()
+    }
+    .with_allocation(keypair("alice").verifying_key().as_bytes(), 1000);
+    let mut chain = Blockchain::from_genesis(&genesis).unwrap();
+    // a recipient whose ID needs quoting in CSV
+    chain.add_account("bob, \"the builder\"".into()).unwrap();
+
+    let clock = MockClock::new(UNIX_EPOCH + Duration::from_secs(genesis.timestamp));
+    clock.advance(Duration::from_secs(10));
+    let mut block = Block::with_clock(&clock);
+    block.header.height = chain.height();
+    block.header.previous_hash = chain.get_last_block_hash().cloned();
+    let mut transaction = Transaction::with_clock(
+        1,
+        TransactionRecord::SendTokens {
+            to: "bob, \"the builder\"".into(),
+            amount: 10,
+        },
+        Some(account_id("alice")),
+        &clock,
+    );
+    transaction.sign(&keypair("alice"));
+    block.transactions.push(transaction);
+    block.seal();
+    chain.add_block(block).unwrap();
+
+    chain
+}
+
+#[test]
+fn test_golden_inclusion_proof() {
+    let mut block = Block::with_clock(&MockClock::new(UNIX_EPOCH));
+    block.transactions = transactions().into_iter().map(|(_, t)| t).collect();
+    let proof = block
+        .merkle_tree()
+        .proof(&block.transactions[2].calculate_hash())
+        .unwrap();
+
+    check_golden("inclusion_proof.bin", &proof.to_bytes().unwrap());
+}
+
+#[test]
+fn test_golden_canonical_encoding() {
+    let clock = MockClock::new(UNIX_EPOCH);
+    let mut block = Block::with_clock(&clock);
+    for (name, transaction) in transactions() {
+        check_golden(&format!("{}.bin", name), &transaction.to_canonical_bytes());
+        block.transactions.push(transaction);
+    }
+    block.header.height = 1;
+    block.header.beneficiary = Some("miner".into());
+    block.header.nonce = 42;
+    block.header.difficulty = 3;
+    block.seal();
+    check_golden("block.bin", &block.to_canonical_bytes());
+}
+
+#[test]
+fn test_golden_csv() {
+    let chain = golden_chain();
+
+    let mut csv = vec![];
+    export_blocks(&chain, &mut csv).unwrap();
+    check_golden("blocks.csv", &csv);
+
+    let mut csv = vec![];
+    export_transactions(&chain, &mut csv).unwrap();
+    check_golden("transactions.csv", &csv);
+
+    let mut csv = vec![];
+    export_balances(&chain, &mut csv).unwrap();
+    check_golden("balances.csv", &csv);
+}
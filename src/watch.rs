@@ -0,0 +1,183 @@
+use crate::events::EventBus;
+use crate::id::Id;
+use crate::transaction::{Transaction, TransactionEvent};
+use crate::Hash;
+use std::collections::HashSet;
+use std::sync::mpsc::Receiver;
+
+/// Sent when a block touches a watched account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchNotification {
+    /// ID of the watched account.
+    pub account: Id,
+
+    /// Height of the block touching the account.
+    pub block_height: usize,
+
+    /// Hash of the transaction touching the account.
+    pub transaction_hash: Hash,
+
+    /// How much the balance of the account changed with that transaction.
+    pub balance_delta: i128,
+}
+
+/**
+A list of accounts to watch, and the subscribers to notify whenever a block
+touches one of them.
+
+```
+# use crate::blockchain::watch::WatchList;
+let mut watch_list = WatchList::new();
+watch_list.watch("someone".into());
+let notifications = watch_list.subscribe();
+
+assert!(watch_list.is_watched(&"someone".into()));
+assert!(notifications.try_recv().is_err());
+```
+*/
+#[derive(Debug, Default)]
+pub struct WatchList {
+    accounts: HashSet<Id>,
+    subscribers: EventBus<WatchNotification>,
+}
+
+impl WatchList {
+    /// Constructor
+    pub fn new() -> Self {
+        WatchList {
+            accounts: HashSet::new(),
+            subscribers: EventBus::new(),
+        }
+    }
+
+    /// Start watching an account.
+    pub fn watch(&mut self, id: Id) {
+        self.accounts.insert(id);
+    }
+
+    /// Stop watching an account.
+    pub fn unwatch(&mut self, id: &Id) {
+        self.accounts.remove(id);
+    }
+
+    /// Is the account watched?
+    pub fn is_watched(&self, id: &Id) -> bool {
+        self.accounts.contains(id)
+    }
+
+    /// Receive a notification every time a block touches a watched account.
+    pub fn subscribe(&mut self) -> Receiver<WatchNotification> {
+        self.subscribers.subscribe()
+    }
+
+    /// Notifications for the watched accounts touched by a transaction,
+    /// given the events it emitted.
+    pub fn notifications(
+        &self,
+        block_height: usize,
+        transaction: &Transaction,
+        events: &[TransactionEvent],
+    ) -> Vec<WatchNotification> {
+        if self.accounts.is_empty() {
+            return vec![];
+        }
+
+        let mut touched = transaction.touched_accounts();
+        touched.dedup();
+        touched
+            .into_iter()
+            .filter(|id| self.is_watched(id))
+            .map(|id| WatchNotification {
+                account: id.to_owned(),
+                block_height,
+                transaction_hash: transaction.calculate_hash(),
+                balance_delta: balance_delta(events, id),
+            })
+            .collect()
+    }
+
+    /// Send notifications to all the subscribers still listening.
+    pub fn notify(&mut self, notifications: Vec<WatchNotification>) {
+        for notification in notifications {
+            self.subscribers.publish(notification);
+        }
+    }
+}
+
+/// How much the events changed the balance of the account.
+fn balance_delta(events: &[TransactionEvent], id: &Id) -> i128 {
+    events.iter().fold(0, |delta, event| match event {
+        TransactionEvent::Minted { to, amount }
+        | TransactionEvent::FeesCollected { to, amount }
+            if to == id =>
+        {
+            delta + *amount as i128
+        }
+        TransactionEvent::FeePaid { from, amount } if from == id => delta - *amount as i128,
+        TransactionEvent::Transfer { from, to, amount } => {
+            let mut delta = delta;
+            if from == id {
+                delta -= *amount as i128;
+            }
+            if to == id {
+                delta += *amount as i128;
+            }
+            delta
+        }
+        _ => delta,
+    })
+}
+
+#[cfg(test)]
+mod watch_tests {
+    use super::*;
+    use crate::transaction::TransactionRecord;
+
+    #[test]
+    fn test_notifications_only_for_watched_accounts() {
+        let mut watch_list = WatchList::new();
+        watch_list.watch("sender".into());
+
+        let transaction = Transaction::new(
+            0,
+            TransactionRecord::SendTokens {
+                to: "receiver".into(),
+                amount: 20,
+            },
+            Some("sender".into()),
+        );
+        let events = [TransactionEvent::Transfer {
+            from: "sender".into(),
+            to: "receiver".into(),
+            amount: 20,
+        }];
+
+        assert_eq!(
+            vec![WatchNotification {
+                account: "sender".into(),
+                block_height: 3,
+                transaction_hash: transaction.calculate_hash(),
+                balance_delta: -20,
+            }],
+            watch_list.notifications(3, &transaction, &events)
+        );
+    }
+
+    #[test]
+    fn test_notify_drops_gone_subscribers() {
+        let mut watch_list = WatchList::new();
+        let receiver = watch_list.subscribe();
+        drop(watch_list.subscribe());
+
+        let notification = WatchNotification {
+            account: "someone".into(),
+            block_height: 0,
+            transaction_hash: vec![],
+            balance_delta: 0,
+        };
+        watch_list.notify(vec![notification.clone()]);
+
+        assert_eq!(1, watch_list.subscribers.subscriber_count());
+        assert_eq!(Ok(notification), receiver.try_recv());
+    }
+}
@@ -1,16 +1,41 @@
-use crate::account::Account;
+use crate::account::{Account, Amount};
+use crate::error::BlockchainError;
 use crate::id::Id;
-use crate::Error;
+use crate::names::NameRecord;
+use crate::params::ChainParams;
+use crate::transaction::TransactionEvent;
 
 /// Snapshot of the world, not to have to rebuild it every time we query it.
 pub trait WorldState {
     /// Return an account that exists in the world, by its ID.
-    fn get_account_by_id(&self, id: &Id) -> Result<&Account, Error>;
+    fn get_account_by_id(&self, id: &Id) -> Result<&Account, BlockchainError>;
     /// Return a mutable reference to an account that exists in the world, by its ID.
-    fn get_account_by_id_mut(&mut self, id: &Id) -> Result<&mut Account, Error>;
+    fn get_account_by_id_mut(&mut self, id: &Id) -> Result<&mut Account, BlockchainError>;
     /// Register a new account in the world.
-    fn add_account(&mut self, id: Id) -> Result<(), Error>;
+    fn add_account(&mut self, id: Id) -> Result<(), BlockchainError>;
 
-    /// Is the world in its genesis, i.e. are we currently creating that world?
-    fn is_genesis(&self) -> bool;
+    /// Height of the block being applied.
+    fn height(&self) -> usize;
+
+    /// Look a name up, whether its registration expired or not.
+    fn get_name(&self, name: &str) -> Option<&NameRecord>;
+    /// Register a name, or update its registration.
+    fn set_name(&mut self, name: String, record: NameRecord);
+
+    /// Record an event emitted while applying a transaction.
+    /// Events are discarded unless the world state keeps track of them.
+    fn emit(&mut self, _event: TransactionEvent) {}
+
+    /// Economic rules transactions must follow.
+    fn params(&self) -> ChainParams {
+        ChainParams::default()
+    }
+
+    /// Make new economic rules activate at a height after the block being
+    /// applied.
+    fn schedule_params(&mut self, height: usize, params: ChainParams);
+
+    /// Credit a fee paid by a transaction to whoever collects the fees of the
+    /// block being applied, so that fees are never lost.
+    fn collect_fee(&mut self, amount: Amount) -> Result<(), BlockchainError>;
 }